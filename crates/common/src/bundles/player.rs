@@ -0,0 +1,60 @@
+use bevy::{prelude::{Bundle, Vec2, default}, time::{Timer, TimerMode}};
+use bevy_rapier2d::prelude::{RigidBody, Collider, KinematicCharacterController, QueryFilterFlags, CollisionGroups, Group, CharacterLength};
+use kt_util::constants::{PLAYER_HIT_RESPAWN_TIME, PLAYER_MAX_SLOPE_ANGLE, PLAYER_HALF_WIDTH, PLAYER_HALF_HEIGHT};
+
+use crate::components::{player::Player, jump::Jump, gravity::GravityDir, velocity::Velocity, acceleration::Acceleration, ground_detector::GroundDetector};
+
+// Everything the movement systems need to simulate the player, without any of
+// the sprites. Shared by the game and the headless movement harness.
+#[derive(Bundle)]
+pub struct PlayerBundle {
+    pub rigid_body: RigidBody,
+    pub collider: Collider,
+    pub ground_detector: GroundDetector,
+    pub gravity_dir: GravityDir,
+    pub velocity: Velocity,
+    pub acceleration: Acceleration,
+    pub jump: Jump,
+    pub kcc: KinematicCharacterController,
+    pub player: Player,
+}
+
+impl Default for PlayerBundle {
+    fn default() -> Self {
+        let mut respawn_timer = Timer::from_seconds(PLAYER_HIT_RESPAWN_TIME, TimerMode::Once);
+        respawn_timer.pause();
+
+        PlayerBundle {
+            rigid_body: RigidBody::KinematicVelocityBased,
+            collider: Collider::cuboid(PLAYER_HALF_WIDTH, PLAYER_HALF_HEIGHT),
+            ground_detector: GroundDetector::default(),
+            gravity_dir: GravityDir {
+                dir: 0.0,
+                slow_down: 1.0,
+            },
+            velocity: Velocity {
+                damping: 0.1,
+                max: Vec2::new(80.0, 300.0),
+                ..default()
+            },
+            acceleration: Acceleration::default(),
+            jump: Jump::default(),
+            kcc: KinematicCharacterController {
+                filter_flags: QueryFilterFlags::EXCLUDE_SENSORS,
+                // Without groups Rapier doesn't check memberships at all, and
+                // one-way platforms turn passable by dropping theirs
+                filter_groups: Some(CollisionGroups::new(Group::ALL, Group::ALL)),
+                // Walk up slopes without sliding back down, and stay on them
+                // when walking down instead of hopping off every tick
+                max_slope_climb_angle: PLAYER_MAX_SLOPE_ANGLE.to_radians(),
+                min_slope_slide_angle: PLAYER_MAX_SLOPE_ANGLE.to_radians(),
+                snap_to_ground: Some(CharacterLength::Absolute(4.0)),
+                ..default()
+            },
+            player: Player {
+                respawn_timer,
+                ..default()
+            },
+        }
+    }
+}
@@ -1,8 +1,10 @@
-use bevy::{prelude::{Bundle, Component, Vec2, default}, sprite::SpriteSheetBundle, reflect::Reflect};
+use bevy::{prelude::{Bundle, Component, Vec2, IVec2, default}, sprite::SpriteSheetBundle, reflect::Reflect};
 use bevy_ecs_ldtk::{LdtkIntCell, LdtkEntity, EntityInstance, IntGridCell, prelude::LdtkFields};
 use bevy_rapier2d::prelude::{Collider, RigidBody, Sensor};
 
-use super::checkpoint::Checkpoint;
+use kt_util::constants::TILE_SIZE;
+
+use super::{interaction::Interaction, checkpoint::Checkpoint, stapler::Stapler, mover::{Mover, MoverMode, MoverEasing}, wiring::{WireId, Wires, Switch, Gate, Door}};
 
 #[derive(Default, Bundle, LdtkEntity)]
 pub struct CheckpointBundle {
@@ -20,6 +22,49 @@ pub struct SpikesBundle {
     #[sprite_sheet_bundle]
     pub sprite_sheet_bundle: SpriteSheetBundle,
     pub hit_component: HitComponent,
+    pub spikes: Spikes,
+    #[with(WireId::from_field)]
+    pub wire_id: WireId,
+}
+
+// Button, PressurePlate and Lever, they send their signal down `targets`
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct SwitchBundle {
+    #[from_entity_instance]
+    pub sensor_bundle: SensorBundle,
+    #[with(Switch::from_field)]
+    pub switch: Switch,
+    #[with(Wires::from_field)]
+    pub wires: Wires,
+    pub interaction: Interaction,
+}
+
+// Never seen in game, only combines signals on their way to the targets
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct GateBundle {
+    #[with(Gate::from_field)]
+    pub gate: Gate,
+    #[with(WireId::from_field)]
+    pub wire_id: WireId,
+    #[with(Wires::from_field)]
+    pub wires: Wires,
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct DoorBundle {
+    #[with(Door::from_field)]
+    pub door: Door,
+    #[with(WireId::from_field)]
+    pub wire_id: WireId,
+}
+
+// Only hurts while shut, `cycle_staplers` toggles its HitComponent
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct StaplerBundle {
+    #[from_entity_instance]
+    pub sensor_bundle: SensorBundle,
+    #[with(Stapler::from_field)]
+    pub stapler: Stapler,
 }
 
 #[derive(Default, Bundle, LdtkEntity)]
@@ -32,6 +77,16 @@ pub struct ElevatorBundle {
     #[with(Level::from_field)]
     pub level: Level,
     pub elevator: ElevatorInstance,
+    #[with(WireId::from_field)]
+    pub wire_id: WireId,
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct MoverBundle {
+    #[with(MoverInstance::from_field)]
+    pub mover_instance: MoverInstance,
+    #[with(WireId::from_field)]
+    pub wire_id: WireId,
 }
 
 #[derive(Default, Bundle, LdtkEntity)]
@@ -56,6 +111,14 @@ pub struct TextBundle {
     pub value: Value,
 }
 
+// A rect that takes over the camera while the player is inside, `lock` pins
+// the camera to its centre instead of just keeping it inside
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct CameraZoneBundle {
+    #[with(CameraZoneInstance::from_field)]
+    pub camera_zone_instance: CameraZoneInstance,
+}
+
 #[derive(Default, Component, Clone, Debug)]
 pub struct ExitInstance {}
 
@@ -82,11 +145,16 @@ pub struct WallDefinition {}
 #[derive(Clone, Component, Debug, Default)]
 pub struct HitComponent {}
 
+#[derive(Clone, Component, Debug, Default)]
+pub struct Spikes {}
+
 #[derive(Default, Bundle, LdtkEntity)]
 pub struct SharpenerBundle {
     #[with(PointTo::from_field)]
     pub point_to: PointTo,
     pub sharpener_instance: SharpenerInstance,
+    #[with(WireId::from_field)]
+    pub wire_id: WireId,
 }
 
 #[derive(Default, Component, Clone, Debug)]
@@ -147,13 +215,54 @@ impl Value {
 #[derive(Clone, Component, Default, Debug)]
 pub struct TextInstance {}
 
+#[derive(Clone, Component, Default, Debug)]
+pub struct CameraZoneInstance {
+    pub size: Vec2,
+    pub lock: bool,
+}
+
+impl CameraZoneInstance {
+    pub fn from_field(entity_instance: &EntityInstance) -> CameraZoneInstance {
+        CameraZoneInstance {
+            size: Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+            lock: entity_instance.get_bool_field("lock").copied().unwrap_or(false),
+        }
+    }
+}
+
 #[derive(Clone, Component, Default, Debug)]
 pub struct ElevatorInstance {}
 
 #[derive(Clone, Component, Default, Debug)]
-pub struct Elevator {
-    pub initial_position: Vec2,
-    pub direction: Vec2,
+pub struct MoverInstance {
+    pub mover: Mover,
+}
+
+impl MoverInstance {
+    pub fn from_field(entity_instance: &EntityInstance) -> MoverInstance {
+        // Path points are grid cells, LDtk's y goes down
+        let mut nodes = vec![Vec2::ZERO];
+        if let Ok(path) = entity_instance.get_points_field("path") {
+            nodes.extend(path.iter().map(|point| {
+                let offset = IVec2::new(point.x, point.y) - entity_instance.grid;
+                Vec2::new(offset.x as f32, -offset.y as f32) * TILE_SIZE
+            }));
+        }
+
+        let mode = entity_instance.get_string_field("mode").map(|mode| mode.as_str()).unwrap_or("PingPong");
+        let easing = entity_instance.get_string_field("easing").map(|easing| easing.as_str()).unwrap_or("Linear");
+
+        MoverInstance {
+            mover: Mover::new(
+                nodes,
+                MoverMode::from_name(mode),
+                entity_instance.get_float_field("speed").copied().unwrap_or(48.0),
+                MoverEasing::from_name(easing),
+                entity_instance.get_float_field("wait").copied().unwrap_or(0.0),
+                entity_instance.get_bool_field("start_on_touch").copied().unwrap_or(false),
+            ),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, Bundle, LdtkIntCell)]
@@ -163,6 +272,73 @@ pub struct WallBundle {
     pub wall: WallDefinition,
 }
 
+// No collider of its own either, `setup_walls` merges slope tiles that line
+// up into ramps
+#[derive(Clone, Debug, Default, Bundle, LdtkIntCell)]
+pub struct SlopeBundle {
+    #[from_int_grid_cell]
+    pub slope: SlopeDefinition,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SlopeKind {
+    // 45°, a tile up every tile
+    #[default]
+    SteepRight,
+    SteepLeft,
+    // 22.5°, a tile up every two tiles, made of a low and a high half
+    ShallowRightLow,
+    ShallowRightHigh,
+    ShallowLeftLow,
+    ShallowLeftHigh,
+}
+
+impl SlopeKind {
+    // Height of the surface at the left and right edge of the tile, in half
+    // tiles
+    pub fn edge_heights(self) -> (i32, i32) {
+        match self {
+            SlopeKind::SteepRight => (0, 2),
+            SlopeKind::SteepLeft => (2, 0),
+            SlopeKind::ShallowRightLow => (0, 1),
+            SlopeKind::ShallowRightHigh => (1, 2),
+            SlopeKind::ShallowLeftLow => (1, 0),
+            SlopeKind::ShallowLeftHigh => (2, 1),
+        }
+    }
+}
+
+#[derive(Clone, Component, Debug, Default)]
+pub struct SlopeDefinition {
+    pub kind: SlopeKind,
+}
+
+// IntGrid values 3 to 8, in the order of `SlopeKind`
+impl From<IntGridCell> for SlopeDefinition {
+    fn from(int_grid_cell: IntGridCell) -> SlopeDefinition {
+        let kind = match int_grid_cell.value {
+            4 => SlopeKind::SteepLeft,
+            5 => SlopeKind::ShallowRightLow,
+            6 => SlopeKind::ShallowRightHigh,
+            7 => SlopeKind::ShallowLeftLow,
+            8 => SlopeKind::ShallowLeftHigh,
+            _ => SlopeKind::SteepRight,
+        };
+
+        SlopeDefinition { kind }
+    }
+}
+
+// No collider of its own, `setup_one_way_platforms` merges the cells into
+// strips along their tops
+#[derive(Clone, Debug, Default, Bundle, LdtkIntCell)]
+pub struct OneWayPlatformBundle {
+    pub one_way_platform: OneWayPlatformDefinition,
+}
+
+#[derive(Clone, Component, Debug, Default)]
+pub struct OneWayPlatformDefinition {}
+
 #[derive(Clone, Debug, Default, Bundle, LdtkIntCell)]
 pub struct ColliderBundle {
     pub collider: Collider,
@@ -212,6 +388,18 @@ impl From<&EntityInstance> for SensorBundle {
                 collider: Collider::cuboid(12.0, 12.0),
                 ..default()
             },
+            "Button" => SensorBundle {
+                collider: Collider::cuboid(6.0, 4.0),
+                ..default()
+            },
+            "PressurePlate" => SensorBundle {
+                collider: Collider::cuboid(10.0, 3.0),
+                ..default()
+            },
+            "Lever" => SensorBundle {
+                collider: Collider::cuboid(6.0, 8.0),
+                ..default()
+            },
             _ => SensorBundle::default()
         }
     }
@@ -21,3 +21,7 @@ impl Limb {
         }
     }
 }
+
+// Only on the limbs of the player, ghosts are drawn with the same limbs
+#[derive(Clone, Component, Debug, Default)]
+pub struct PlayerLimb {}
@@ -7,11 +7,15 @@ pub mod interaction;
 pub mod ground_detector;
 pub mod player;
 pub mod platform;
+pub mod mover;
+pub mod one_way_platform;
 pub mod jump;
 pub mod pin;
 pub mod stretch;
 pub mod sharpener;
+pub mod stapler;
 pub mod velocity;
 pub mod gravity;
 pub mod ui;
+pub mod wiring;
 pub mod ldtk;
@@ -0,0 +1,167 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::{Component, Vec2, default};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MoverMode {
+    // Back to the first node after the last one
+    Loop,
+    #[default]
+    PingPong,
+    // Stops for good at the last node
+    Once,
+}
+
+impl MoverMode {
+    pub fn from_name(name: &str) -> MoverMode {
+        match name {
+            "Loop" => MoverMode::Loop,
+            "Once" => MoverMode::Once,
+            _ => MoverMode::PingPong,
+        }
+    }
+}
+
+// Easing between two nodes, named like bevy_tweening's
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MoverEasing {
+    #[default]
+    Linear,
+    SineInOut,
+    QuadraticInOut,
+    CubicInOut,
+}
+
+impl MoverEasing {
+    pub fn from_name(name: &str) -> MoverEasing {
+        match name {
+            "SineInOut" => MoverEasing::SineInOut,
+            "QuadraticInOut" => MoverEasing::QuadraticInOut,
+            "CubicInOut" => MoverEasing::CubicInOut,
+            _ => MoverEasing::Linear,
+        }
+    }
+
+    pub fn sample(self, t: f32) -> f32 {
+        match self {
+            MoverEasing::Linear => t,
+            MoverEasing::SineInOut => 0.5 - (PI * t).cos() / 2.0,
+            MoverEasing::QuadraticInOut => {
+                if t < 0.5 { 2.0 * t * t } else { 1.0 - (2.0 - 2.0 * t).powi(2) / 2.0 }
+            },
+            MoverEasing::CubicInOut => {
+                if t < 0.5 { 4.0 * t * t * t } else { 1.0 - (2.0 - 2.0 * t).powi(3) / 2.0 }
+            },
+        }
+    }
+}
+
+// Moves a kinematic body along a path of nodes, given relative to where it
+// starts. `displacement` is how far it went on the last tick, so whatever
+// rides on it can be moved along.
+#[derive(Clone, Component, Debug, Default)]
+pub struct Mover {
+    pub nodes: Vec<Vec2>,
+    pub mode: MoverMode,
+    // Pixels per second
+    pub speed: f32,
+    pub easing: MoverEasing,
+    // Seconds to stop at each node
+    pub wait: f32,
+    pub start_on_touch: bool,
+
+    pub origin: Vec2,
+    pub from: usize,
+    pub to: usize,
+    pub direction: i32,
+    pub progress: f32,
+    pub wait_left: f32,
+    pub is_active: bool,
+    pub displacement: Vec2,
+}
+
+impl Mover {
+    pub fn new(nodes: Vec<Vec2>, mode: MoverMode, speed: f32, easing: MoverEasing, wait: f32, start_on_touch: bool) -> Mover {
+        Mover {
+            nodes,
+            mode,
+            speed,
+            easing,
+            wait,
+            start_on_touch,
+            to: 1,
+            direction: 1,
+            is_active: !start_on_touch,
+            ..default()
+        }
+    }
+
+    pub fn position(&self) -> Vec2 {
+        if self.nodes.len() < 2 {
+            return self.origin;
+        }
+
+        let t = self.easing.sample(self.progress);
+        self.origin + self.nodes[self.from].lerp(self.nodes[self.to], t)
+    }
+
+    pub fn advance(&mut self, delta: f32) {
+        self.displacement = Vec2::ZERO;
+
+        if !self.is_active || self.nodes.len() < 2 {
+            return;
+        }
+
+        if self.wait_left > 0.0 {
+            self.wait_left -= delta;
+            return;
+        }
+
+        let before = self.position();
+        let length = self.nodes[self.from].distance(self.nodes[self.to]);
+
+        self.progress = if length > 0.0 {
+            (self.progress + self.speed * delta / length).min(1.0)
+        } else {
+            1.0
+        };
+
+        let after = self.position();
+
+        if self.progress >= 1.0 {
+            self.arrive();
+        }
+
+        self.displacement = after - before;
+    }
+
+    fn arrive(&mut self) {
+        self.from = self.to;
+        self.progress = 0.0;
+
+        match self.next_node(self.from) {
+            Some((next, direction)) => {
+                self.to = next;
+                self.direction = direction;
+                self.wait_left = self.wait;
+            },
+            None => self.is_active = false,
+        }
+    }
+
+    fn next_node(&self, node: usize) -> Option<(usize, i32)> {
+        let last = self.nodes.len() - 1;
+
+        match self.mode {
+            MoverMode::Loop => Some(((node + 1) % self.nodes.len(), 1)),
+            MoverMode::Once if node < last => Some((node + 1, 1)),
+            MoverMode::Once => None,
+            MoverMode::PingPong => {
+                let turn = (node == last && self.direction > 0) || (node == 0 && self.direction < 0);
+                let direction = if turn { -self.direction } else { self.direction };
+
+                Some(((node as i32 + direction) as usize, direction))
+            },
+        }
+    }
+}
@@ -0,0 +1,15 @@
+use bevy::prelude::Component;
+use bevy_rapier2d::prelude::{CollisionGroups, Group};
+
+// Only solid while the player is above it and not dropping through, the
+// movement systems switch its memberships between this and `Group::NONE`
+pub const ONE_WAY_PLATFORM_GROUP: Group = Group::GROUP_2;
+
+#[derive(Clone, Component, Debug, Default)]
+pub struct OneWayPlatform {}
+
+// For shape casts that should only ever hit full walls, one-way platforms
+// can't be grabbed or bumped into from below or the side
+pub fn solid_groups() -> CollisionGroups {
+    CollisionGroups::new(Group::ALL, Group::ALL - ONE_WAY_PLATFORM_GROUP)
+}
@@ -13,6 +13,8 @@ pub struct Pin {
     pub state: WithPrevious<PinState>,
     pub initial_position: Vec2,
     pub picked: bool,
+    // Picked before the last checkpoint, so dying doesn't put it back
+    pub banked: bool,
 
     pub position: Vec2,
     pub color: Color,
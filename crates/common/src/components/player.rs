@@ -9,8 +9,12 @@ pub struct Player {
     pub grabbed_ceiling: bool,
 
     pub has_x_collision: isize, // #TODO enum
+    // Until the player pops out of the pencil box at the start of a level,
+    // hits and restarts wait for the respawn transition instead
     pub respawn_timer: Timer,
     pub respawning_animation_timer: Timer,
     pub hit_timer: Timer,
     pub is_respawning: bool,
+    // Seconds left of falling through one-way platforms
+    pub drop_through_time: f32,
 }
@@ -0,0 +1,23 @@
+use bevy::{prelude::Component, time::{Timer, TimerMode}};
+use bevy_ecs_ldtk::{EntityInstance, prelude::LdtkFields};
+use kt_util::constants::STAPLER_OPEN_TIME;
+
+#[derive(Debug, Default, Component)]
+pub struct Stapler {
+    pub timer: Timer,
+    pub is_closed: bool,
+}
+
+impl Stapler {
+    pub fn from_field(entity_instance: &EntityInstance) -> Stapler {
+        let delay = entity_instance.get_float_field("delay").copied().unwrap_or(0.0);
+
+        Stapler {
+            timer: Timer::from_seconds(STAPLER_OPEN_TIME + delay, TimerMode::Once),
+            is_closed: false,
+        }
+    }
+}
+
+#[derive(Clone, Component, Debug, Default)]
+pub struct StaplerJaw {}
@@ -14,26 +14,32 @@ pub struct ButtonClickSound {}
 #[derive(Clone, Component, Debug, Default)]
 pub struct MainMenuUi {}
 
-#[derive(Clone, Component, Debug, Default)]
-pub struct MainColumnUi {}
-
 #[derive(Clone, Component, Debug, Default)]
 pub struct SoundUi {}
 
 #[derive(Clone, Component, Debug, Default)]
 pub struct MuteButtonUi {}
 
-#[derive(Clone, Component, Debug, Default)]
-pub struct LevelSelectColumnUi {}
-
 #[derive(Clone, Component, Debug, Default)]
 pub struct TransitionColumnLeftUi {}
 
 #[derive(Clone, Component, Debug, Default)]
 pub struct TransitionColumnRightUi {}
 
+#[derive(Clone, Component, Debug, Default)]
+pub struct InGameUi {}
+
 #[derive(Clone, Component, Debug, Default)]
 pub struct PinsContainerUI {}
 
 #[derive(Clone, Component, Debug, Default)]
 pub struct PinUI {}
+
+#[derive(Clone, Component, Debug, Default)]
+pub struct RunTimerUi {}
+
+#[derive(Clone, Component, Debug, Default)]
+pub struct RunTimerDeltaUi {}
+
+#[derive(Clone, Component, Debug, Default)]
+pub struct RunTotalUi {}
@@ -0,0 +1,134 @@
+use bevy::prelude::{Component, Vec2, default};
+use bevy_ecs_ldtk::{EntityInstance, prelude::LdtkFields};
+
+// The LDtk iid of an entity that can be wired to. It's kept on whatever the
+// entity gets respawned as, so wires still find it.
+#[derive(Clone, Component, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WireId(pub String);
+
+impl WireId {
+    pub fn from_field(entity_instance: &EntityInstance) -> WireId {
+        WireId(entity_instance.iid.clone())
+    }
+}
+
+// Where a switch or gate sends its signal, from the `targets` entity refs
+#[derive(Clone, Component, Debug, Default)]
+pub struct Wires {
+    pub targets: Vec<String>,
+}
+
+impl Wires {
+    pub fn from_field(entity_instance: &EntityInstance) -> Wires {
+        let targets = match entity_instance.get_entity_refs_field("targets") {
+            Ok(targets) => targets.iter().map(|target| target.entity_iid.clone()).collect(),
+            Err(..) => Vec::new(),
+        };
+
+        Wires { targets }
+    }
+}
+
+// How many wires lead into an entity and how many of them are on. Only
+// entities something is wired to get one.
+#[derive(Clone, Component, Debug, Default, PartialEq, Eq)]
+pub struct Powered {
+    pub inputs: usize,
+    pub inputs_on: usize,
+}
+
+impl Powered {
+    pub fn is_on(&self) -> bool {
+        self.inputs_on > 0
+    }
+
+    pub fn all_on(&self) -> bool {
+        self.inputs > 0 && self.inputs_on == self.inputs
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SwitchKind {
+    // Stays on once pressed
+    #[default]
+    Button,
+    // On while the player stands on it
+    PressurePlate,
+    // Flips every time the player touches it
+    Lever,
+}
+
+#[derive(Clone, Component, Debug, Default)]
+pub struct Switch {
+    pub kind: SwitchKind,
+    pub is_on: bool,
+    pub was_touched: bool,
+}
+
+impl Switch {
+    pub fn from_field(entity_instance: &EntityInstance) -> Switch {
+        let kind = match entity_instance.identifier.as_ref() {
+            "PressurePlate" => SwitchKind::PressurePlate,
+            "Lever" => SwitchKind::Lever,
+            _ => SwitchKind::Button,
+        };
+
+        Switch {
+            kind,
+            ..default()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GateKind {
+    // On while every input is on
+    And,
+    // On while any input is on
+    #[default]
+    Or,
+    // Flips when an input turns on
+    Toggle,
+    // On for `duration` seconds after an input turns on
+    Timed,
+}
+
+#[derive(Clone, Component, Debug, Default)]
+pub struct Gate {
+    pub kind: GateKind,
+    pub duration: f32,
+    pub is_on: bool,
+    pub was_powered: bool,
+    pub time_left: f32,
+}
+
+impl Gate {
+    pub fn from_field(entity_instance: &EntityInstance) -> Gate {
+        let kind = match entity_instance.get_string_field("kind").map(|kind| kind.as_str()) {
+            Ok("And") => GateKind::And,
+            Ok("Toggle") => GateKind::Toggle,
+            Ok("Timed") => GateKind::Timed,
+            _ => GateKind::Or,
+        };
+
+        Gate {
+            kind,
+            duration: entity_instance.get_float_field("duration").copied().unwrap_or(1.0),
+            ..default()
+        }
+    }
+}
+
+// Solid until powered
+#[derive(Clone, Component, Debug, Default)]
+pub struct Door {
+    pub size: Vec2,
+}
+
+impl Door {
+    pub fn from_field(entity_instance: &EntityInstance) -> Door {
+        Door {
+            size: Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+        }
+    }
+}
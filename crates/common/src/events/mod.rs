@@ -1,4 +1,74 @@
-use bevy::prelude::Event;
+use bevy::prelude::{Event, Entity, Vec2};
 
 #[derive(Event)]
 pub struct PinUiUpdated();
+
+// The player reached the exit with every pin, `level` is 1-based
+#[derive(Event, Debug, Clone, Copy)]
+pub struct LevelCompleted {
+    pub level: isize,
+}
+
+// Sent when the player gets hit, before the respawn starts
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PlayerDied {
+    pub player: Entity,
+}
+
+// Sent when the level is restarted from the pause menu
+#[derive(Event, Debug, Clone, Copy)]
+pub struct LevelRestarted;
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PinCollected {
+    pub pin: Entity,
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CheckpointActivated {
+    pub checkpoint: Entity,
+}
+
+// What a screen transition is for, passed back in `TransitionCovered` and
+// `TransitionFinished`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionKind {
+    // Main menu -> first level
+    EnterGame,
+    // Exit reached -> next level
+    NextLevel,
+    // Hit or restart -> spawn point
+    Respawn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionStyle {
+    // Two black columns closing from the sides
+    Columns,
+    Fade,
+    // A circle closing on `RequestTransition::focus`
+    Iris,
+    // Black squares popping in one by one
+    Dissolve,
+}
+
+// Covers the screen over `duration` seconds, sends `TransitionCovered`, then
+// reveals it over the same time and sends `TransitionFinished`. Requests made
+// while another transition runs are queued, unless one of the same kind is
+// already running or queued.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct RequestTransition {
+    pub kind: TransitionKind,
+    pub style: TransitionStyle,
+    pub duration: f32,
+    // World position the iris closes on, the centre of the screen if `None`
+    pub focus: Option<Vec2>,
+}
+
+// The screen is fully covered, anything that shouldn't be seen happens now
+#[derive(Event, Debug, Clone, Copy)]
+pub struct TransitionCovered(pub TransitionKind);
+
+// The screen is fully revealed again
+#[derive(Event, Debug, Clone, Copy)]
+pub struct TransitionFinished(pub TransitionKind);
@@ -1,12 +1,13 @@
 use bevy::prelude::{Plugin, App};
 use components::{player::Player, checkpoint::Checkpoint, interaction::Interaction};
-use events::{PinUiUpdated};
+use events::{PinUiUpdated, LevelCompleted, PlayerDied, LevelRestarted, PinCollected, CheckpointActivated, RequestTransition, TransitionCovered, TransitionFinished};
 
 pub mod bundles;
 pub mod components;
 pub mod assets;
 pub mod resources;
 pub mod events;
+pub mod tile_colliders;
 
 #[derive(Debug, Default)]
 pub struct CommonPlugin {}
@@ -17,6 +18,14 @@ impl Plugin for CommonPlugin {
             .register_type::<Checkpoint>()
             .register_type::<Player>()
             .register_type::<Interaction>()
-            .add_event::<PinUiUpdated>();
+            .add_event::<PinUiUpdated>()
+            .add_event::<LevelCompleted>()
+            .add_event::<PlayerDied>()
+            .add_event::<LevelRestarted>()
+            .add_event::<PinCollected>()
+            .add_event::<CheckpointActivated>()
+            .add_event::<RequestTransition>()
+            .add_event::<TransitionCovered>()
+            .add_event::<TransitionFinished>();
     }
 }
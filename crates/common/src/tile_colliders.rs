@@ -0,0 +1,468 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::{Vec2, Vec3, IVec2};
+use bevy_rapier2d::prelude::Collider;
+use kt_util::constants::{TILE_SIZE, ONE_WAY_PLATFORM_THICKNESS};
+
+use crate::components::ldtk::SlopeKind;
+
+// Turns the tile cells of a level into as few colliders as possible. Shared by
+// the game and the movement harness, so tests run against the same layout.
+// Cells have (0, 0) at the bottom left, colliders come back as a translation
+// in pixels and a shape around it.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Point {
+    // The cell a tile entity sits in
+    pub fn from_translation(translation: Vec3) -> Point {
+        Point {
+            x: (translation.x / TILE_SIZE).floor() as i32,
+            y: (translation.y / TILE_SIZE).floor() as i32,
+        }
+    }
+}
+
+// The surface of tiles that make up one ramp, in half tiles. The collider
+// fills everything under it down to `base`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlopeRun {
+    pub start: IVec2,
+    pub end: IVec2,
+    pub base: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rectangle {
+    pub top_left: Point,
+    pub width: usize,
+    pub height: usize,
+}
+
+pub fn wall_colliders(walls: &HashSet<Point>) -> Vec<(Vec2, Collider)> {
+    find_rectangles(walls)
+        .iter()
+        .map(|rect| {
+            let size = Vec2::new(rect.width as f32, rect.height as f32) * TILE_SIZE;
+            let corner = Vec2::new(rect.top_left.x as f32, rect.top_left.y as f32) * TILE_SIZE;
+
+            (corner + size / 2.0, Collider::cuboid(size.x / 2.0, size.y / 2.0))
+        })
+        .collect()
+}
+
+// Thin strips along the top of each run
+pub fn one_way_platform_colliders(cells: &HashSet<Point>) -> Vec<(Vec2, Collider)> {
+    find_runs(cells)
+        .iter()
+        .map(|run| {
+            let width = run.width as f32 * TILE_SIZE;
+            let translation = Vec2::new(
+                run.top_left.x as f32 * TILE_SIZE + width / 2.0,
+                run.top_left.y as f32 * TILE_SIZE + TILE_SIZE - ONE_WAY_PLATFORM_THICKNESS / 2.0,
+            );
+
+            (translation, Collider::cuboid(width / 2.0, ONE_WAY_PLATFORM_THICKNESS / 2.0))
+        })
+        .collect()
+}
+
+pub fn slope_colliders(slopes: &HashMap<Point, SlopeKind>) -> Vec<(Vec2, Collider)> {
+    let half_tile = TILE_SIZE / 2.0;
+
+    find_slope_runs(slopes)
+        .iter()
+        .filter_map(|run| {
+            // Corners relative to the start of the surface, in pixels
+            let start = run.start.as_vec2() * half_tile;
+            let mut points = vec![Vec2::ZERO, run.end.as_vec2() * half_tile - start];
+
+            if run.end.y != run.base {
+                points.push(Vec2::new(run.end.x as f32, run.base as f32) * half_tile - start);
+            }
+
+            if run.start.y != run.base {
+                points.push(Vec2::new(run.start.x as f32, run.base as f32) * half_tile - start);
+            }
+
+            Collider::convex_polyline(points).map(|collider| (start, collider))
+        })
+        .collect()
+}
+
+// One-way platforms are only merged sideways, stacked ones each need a top
+pub fn find_runs(cells: &HashSet<Point>) -> Vec<Rectangle> {
+    let mut sorted_points: Vec<Point> = cells.iter().copied().collect();
+    sorted_points.sort_by(|a, b| a.y.cmp(&b.y).then(a.x.cmp(&b.x)));
+
+    let mut runs: Vec<Rectangle> = Vec::new();
+
+    for point in sorted_points {
+        if let Some(run) = runs.last_mut() {
+            if run.top_left.y == point.y && run.top_left.x + run.width as i32 == point.x {
+                run.width += 1;
+                continue;
+            }
+        }
+
+        runs.push(Rectangle {
+            top_left: point,
+            width: 1,
+            height: 1,
+        });
+    }
+
+    runs
+}
+
+// Chains slope tiles whose surfaces continue each other at the same angle
+// into single ramps, so walking along one never crosses a seam
+pub fn find_slope_runs(slopes: &HashMap<Point, SlopeKind>) -> Vec<SlopeRun> {
+    // Surface start -> (rise per tile, surface end, bottom of the tile)
+    let mut segments: HashMap<(IVec2, i32), (IVec2, i32)> = HashMap::new();
+
+    for (point, kind) in slopes.iter() {
+        let (left, right) = kind.edge_heights();
+        let start = IVec2::new(point.x * 2, point.y * 2 + left);
+        let end = IVec2::new(point.x * 2 + 2, point.y * 2 + right);
+
+        segments.insert((start, right - left), (end, point.y * 2));
+    }
+
+    let ends: HashSet<(IVec2, i32)> = segments
+        .iter()
+        .map(|((_, rise), (end, _))| (*end, *rise))
+        .collect();
+
+    // Runs start at segments nothing leads into
+    let mut heads: Vec<(IVec2, i32)> = segments
+        .keys()
+        .filter(|head| !ends.contains(head))
+        .copied()
+        .collect();
+    heads.sort_by(|a, b| a.0.y.cmp(&b.0.y).then(a.0.x.cmp(&b.0.x)));
+
+    let mut runs = Vec::new();
+
+    for (start, rise) in heads {
+        let (mut end, mut base) = segments[&(start, rise)];
+
+        while let Some((next_end, next_base)) = segments.get(&(end, rise)) {
+            end = *next_end;
+            base = base.min(*next_base);
+        }
+
+        runs.push(SlopeRun { start, end, base });
+    }
+
+    runs
+}
+
+// Cells with a wall on all four sides, nothing can ever touch them
+pub fn find_interior_points(walls: &HashSet<Point>) -> HashSet<Point> {
+    walls
+        .iter()
+        .filter(|point| {
+            [(-1, 0), (1, 0), (0, 1), (0, -1)]
+                .iter()
+                .all(|(dx, dy)| walls.contains(&Point { x: point.x + dx, y: point.y + dy }))
+        })
+        .copied()
+        .collect()
+}
+
+// Greedy decomposition, bottom row first: every rectangle starts at an edge
+// cell, grows as wide as it can and then as tall as whole rows allow. Interior
+// cells never start a rectangle but can be grown over, so a solid block ends
+// up as a single collider and an enclosed interior on its own is dropped.
+pub fn find_rectangles(walls: &HashSet<Point>) -> Vec<Rectangle> {
+    let interior = find_interior_points(walls);
+
+    let mut edge_points: Vec<Point> = walls.difference(&interior).copied().collect();
+    edge_points.sort_by(|a, b| a.y.cmp(&b.y).then(a.x.cmp(&b.x)));
+
+    let mut rectangles = Vec::new();
+    let mut covered: HashSet<Point> = HashSet::new();
+
+    for point in edge_points {
+        if covered.contains(&point) {
+            continue;
+        }
+
+        let is_free = |covered: &HashSet<Point>, x: i32, y: i32| {
+            let point = Point { x, y };
+            walls.contains(&point) && !covered.contains(&point)
+        };
+
+        let mut width = 1;
+        while is_free(&covered, point.x + width as i32, point.y) {
+            width += 1;
+        }
+
+        let mut height = 1;
+        while (0..width).all(|dx| is_free(&covered, point.x + dx as i32, point.y + height as i32)) {
+            height += 1;
+        }
+
+        for dy in 0..height {
+            for dx in 0..width {
+                covered.insert(Point { x: point.x + dx as i32, y: point.y + dy as i32 });
+            }
+        }
+
+        rectangles.push(Rectangle {
+            top_left: point,
+            width,
+            height,
+        });
+    }
+
+    rectangles
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use bevy::prelude::IVec2;
+    use crate::components::ldtk::SlopeKind;
+
+    use super::{Point, Rectangle, SlopeRun, find_rectangles, find_interior_points, find_runs, find_slope_runs};
+
+    // `#` is a wall, the first row is the top one
+    fn grid(rows: &[&str]) -> HashSet<Point> {
+        let mut walls = HashSet::new();
+
+        for (row, line) in rows.iter().enumerate() {
+            for (x, cell) in line.chars().enumerate() {
+                if cell == '#' {
+                    walls.insert(Point { x: x as i32, y: (rows.len() - 1 - row) as i32 });
+                }
+            }
+        }
+
+        walls
+    }
+
+    fn cells(rect: &Rectangle) -> Vec<Point> {
+        let mut cells = vec![];
+
+        for dy in 0..rect.height {
+            for dx in 0..rect.width {
+                cells.push(Point { x: rect.top_left.x + dx as i32, y: rect.top_left.y + dy as i32 });
+            }
+        }
+
+        cells
+    }
+
+    // Rectangles only cover walls, never overlap, and leave out nothing but
+    // interior cells
+    fn assert_valid(walls: &HashSet<Point>, rects: &[Rectangle]) {
+        let mut covered = HashSet::new();
+
+        for rect in rects {
+            for cell in cells(rect) {
+                assert!(walls.contains(&cell), "{:?} covers {:?}, which is not a wall", rect, cell);
+                assert!(covered.insert(cell), "{:?} overlaps another rectangle at {:?}", rect, cell);
+            }
+        }
+
+        let interior = find_interior_points(walls);
+
+        for wall in walls.difference(&interior) {
+            assert!(covered.contains(wall), "edge cell {:?} has no collider", wall);
+        }
+    }
+
+    #[test]
+    fn empty_level_has_no_colliders() {
+        assert!(find_rectangles(&HashSet::new()).is_empty());
+    }
+
+    #[test]
+    fn floor_is_one_collider() {
+        let walls = grid(&["#####"]);
+        let rects = find_rectangles(&walls);
+
+        assert_eq!(rects, vec![Rectangle { top_left: Point { x: 0, y: 0 }, width: 5, height: 1 }]);
+    }
+
+    #[test]
+    fn pillar_is_one_collider() {
+        let walls = grid(&["#", "#", "#", "#"]);
+        let rects = find_rectangles(&walls);
+
+        assert_eq!(rects, vec![Rectangle { top_left: Point { x: 0, y: 0 }, width: 1, height: 4 }]);
+    }
+
+    #[test]
+    fn solid_block_grows_over_its_interior() {
+        let walls = grid(&[
+            "#####",
+            "#####",
+            "#####",
+            "#####",
+        ]);
+
+        assert_eq!(find_interior_points(&walls).len(), 6);
+
+        let rects = find_rectangles(&walls);
+
+        assert_eq!(rects, vec![Rectangle { top_left: Point { x: 0, y: 0 }, width: 5, height: 4 }]);
+    }
+
+    #[test]
+    fn interior_cells_are_only_the_fully_surrounded_ones() {
+        let walls = grid(&[
+            "###",
+            "####",
+            "###",
+        ]);
+
+        let interior = find_interior_points(&walls);
+
+        assert_eq!(interior, HashSet::from([Point { x: 1, y: 1 }, Point { x: 2, y: 1 }]));
+    }
+
+    // The column on the left and the row under it grow first and stop short
+    // of the one surrounded cell, which never starts a rectangle itself
+    #[test]
+    fn fully_surrounded_interior_cells_are_dropped() {
+        let walls = grid(&[
+            " # #",
+            "### ",
+            "####",
+            "#   ",
+        ]);
+        let rects = find_rectangles(&walls);
+        let interior = find_interior_points(&walls);
+
+        assert_eq!(interior, HashSet::from([Point { x: 1, y: 2 }]));
+        assert_valid(&walls, &rects);
+
+        for rect in rects.iter() {
+            assert!(!cells(rect).contains(&Point { x: 1, y: 2 }), "{:?} covers the interior cell", rect);
+        }
+    }
+
+    #[test]
+    fn l_shape() {
+        let walls = grid(&[
+            "#   ",
+            "#   ",
+            "####",
+        ]);
+        let rects = find_rectangles(&walls);
+
+        assert_valid(&walls, &rects);
+        assert_eq!(rects.len(), 2);
+    }
+
+    #[test]
+    fn room_walls_leave_the_inside_open() {
+        let walls = grid(&[
+            "#######",
+            "#     #",
+            "#     #",
+            "#######",
+        ]);
+        let rects = find_rectangles(&walls);
+
+        assert_valid(&walls, &rects);
+        assert_eq!(rects.len(), 4);
+    }
+
+    #[test]
+    fn staircase() {
+        let walls = grid(&[
+            "   ##",
+            "  ###",
+            " ####",
+            "#####",
+        ]);
+        let rects = find_rectangles(&walls);
+
+        assert_valid(&walls, &rects);
+        assert!(rects.len() < walls.len());
+    }
+
+    #[test]
+    fn one_way_platforms_merge_sideways_only() {
+        let cells = grid(&[
+            "###  ##",
+            "###",
+        ]);
+        let runs = find_runs(&cells);
+
+        assert_eq!(runs, vec![
+            Rectangle { top_left: Point { x: 0, y: 0 }, width: 3, height: 1 },
+            Rectangle { top_left: Point { x: 0, y: 1 }, width: 3, height: 1 },
+            Rectangle { top_left: Point { x: 5, y: 1 }, width: 2, height: 1 },
+        ]);
+    }
+
+    #[test]
+    fn steep_ramp_is_one_run() {
+        let slopes = HashMap::from([
+            (Point { x: 0, y: 0 }, SlopeKind::SteepRight),
+            (Point { x: 1, y: 1 }, SlopeKind::SteepRight),
+            (Point { x: 2, y: 2 }, SlopeKind::SteepRight),
+        ]);
+
+        assert_eq!(find_slope_runs(&slopes), vec![
+            SlopeRun { start: IVec2::new(0, 0), end: IVec2::new(6, 6), base: 0 },
+        ]);
+    }
+
+    #[test]
+    fn shallow_ramp_joins_low_and_high_halves() {
+        let slopes = HashMap::from([
+            (Point { x: 3, y: 1 }, SlopeKind::ShallowLeftHigh),
+            (Point { x: 4, y: 1 }, SlopeKind::ShallowLeftLow),
+            (Point { x: 5, y: 0 }, SlopeKind::ShallowLeftHigh),
+            (Point { x: 6, y: 0 }, SlopeKind::ShallowLeftLow),
+        ]);
+
+        assert_eq!(find_slope_runs(&slopes), vec![
+            SlopeRun { start: IVec2::new(6, 4), end: IVec2::new(14, 0), base: 0 },
+        ]);
+    }
+
+    #[test]
+    fn hill_is_two_runs() {
+        // Up then down again, the peak is shared but the angle changes
+        let slopes = HashMap::from([
+            (Point { x: 0, y: 0 }, SlopeKind::SteepRight),
+            (Point { x: 1, y: 0 }, SlopeKind::SteepLeft),
+        ]);
+
+        assert_eq!(find_slope_runs(&slopes), vec![
+            SlopeRun { start: IVec2::new(0, 0), end: IVec2::new(2, 2), base: 0 },
+            SlopeRun { start: IVec2::new(2, 2), end: IVec2::new(4, 0), base: 0 },
+        ]);
+    }
+
+    #[test]
+    fn representative_level() {
+        let walls = grid(&[
+            "####################",
+            "#                  #",
+            "#        ####      #",
+            "#                ###",
+            "#   ##          ####",
+            "#   ##    #    #####",
+            "####################",
+            "####################",
+        ]);
+        let rects = find_rectangles(&walls);
+
+        assert_valid(&walls, &rects);
+        // One cuboid per cell was 100+, height 1 runs were 20
+        assert!(rects.len() <= 12, "{} rectangles", rects.len());
+    }
+}
@@ -1,68 +1,381 @@
-use bevy::{prelude::*, utils::HashMap };
+use std::collections::HashMap;
 
-#[derive(Clone, Debug)]
+use bevy::{prelude::*, asset::{AssetLoader, LoadContext, LoadedAsset, HandleId}, reflect::{TypeUuid, TypePath}, utils::BoxedFuture};
+use serde::Deserialize;
+
+// Something drawn on top of a frame, like the ruler extension on the body
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Attachment {
+    pub offset: Vec2,
+    // Frame of the attachment's own sprite
+    pub frame: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
 pub struct Animation {
     pub frames: Vec<usize>,
+    #[serde(default = "default_looping")]
     pub looping: bool,
-    pub fps: usize, 
+    pub fps: usize,
+    // One entry per frame, keyed by attachment name
+    #[serde(default)]
+    pub attachments: HashMap<String, Vec<Attachment>>,
+    // Names sent as `AnimationEvent`s when the frame at that index shows up
+    #[serde(default)]
+    pub events: HashMap<usize, Vec<String>>,
+}
+
+fn default_looping() -> bool {
+    true
+}
+
+// Every condition set on it has to hold, e.g. `{ "param": "speed", "above": 8.0 }`
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AnimationCondition {
+    pub param: String,
+    pub above: Option<f32>,
+    pub below: Option<f32>,
+    pub is: Option<bool>,
+}
+
+impl AnimationCondition {
+    fn holds(&self, params: &AnimationParams) -> bool {
+        let value = params.float(&self.param);
+
+        self.above.map_or(true, |above| value > above)
+            && self.below.map_or(true, |below| value < below)
+            && self.is.map_or(true, |is| params.bool(&self.param) == is)
+    }
+}
+
+fn all_hold(conditions: &[AnimationCondition], params: &AnimationParams) -> bool {
+    conditions.iter().all(|condition| condition.holds(params))
+}
+
+// Clips are never blended, this only decides where the next clip starts
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Crossfade {
+    // Switch right away and start from the first frame
+    #[default]
+    Restart,
+    // Switch right away and stay on the same frame index
+    KeepFrame,
+    // Let the current clip play to its end first
+    AfterClip,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AnimationTransition {
+    // Any state if not set
+    #[serde(default)]
+    pub from: Option<String>,
+    pub to: String,
+    #[serde(default)]
+    pub when: Vec<AnimationCondition>,
+    #[serde(default)]
+    pub crossfade: Crossfade,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AnimationStateDefinition {
+    // The clip stops on its current frame while these hold
+    pub hold_when: Vec<AnimationCondition>,
+}
+
+// States are named after the clips they play. Transitions are checked in
+// order and the first one that applies is taken.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AnimationController {
+    pub states: HashMap<String, AnimationStateDefinition>,
+    pub transitions: Vec<AnimationTransition>,
+}
+
+// A sprite sheet cut into a grid, with the clips of every part drawn from it,
+// e.g. `parts["Legs"]["Idle"]`. Loaded from `*.anim.json` files.
+#[derive(Debug, Deserialize, TypeUuid, TypePath)]
+#[uuid = "3c6f8a4e-2b1d-4f7a-9e0c-5d8b7a1f2e64"]
+pub struct AnimationSheet {
+    pub texture: String,
+    pub tile_size: Vec2,
+    pub columns: usize,
+    pub rows: usize,
+    pub parts: HashMap<String, HashMap<String, Animation>>,
+    #[serde(default)]
+    pub controller: Option<AnimationController>,
+}
+
+impl AnimationSheet {
+    pub fn clip(&self, part: &str, name: &str) -> Option<&Animation> {
+        self.parts.get(part)?.get(name)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AnimationSheetLoader {}
+
+impl AssetLoader for AnimationSheetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let sheet: AnimationSheet = serde_json::from_slice(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(sheet));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["anim.json"]
+    }
+}
+
+// Atlases built from the loaded sheets, rebuilt whenever a sheet changes
+#[derive(Resource, Debug, Default)]
+pub struct AnimationAtlases {
+    atlases: HashMap<HandleId, Handle<TextureAtlas>>,
+}
+
+// Inputs of the sheet's controller, set by gameplay code every frame
+#[derive(Component, Debug, Default)]
+pub struct AnimationParams {
+    floats: HashMap<String, f32>,
+    bools: HashMap<String, bool>,
+}
+
+impl AnimationParams {
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.floats.insert(name.to_string(), value);
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.bools.insert(name.to_string(), value);
+    }
+
+    pub fn float(&self, name: &str) -> f32 {
+        self.floats.get(name).copied().unwrap_or_default()
+    }
+
+    pub fn bool(&self, name: &str) -> bool {
+        self.bools.get(name).copied().unwrap_or_default()
+    }
 }
 
+#[derive(Event, Debug, Clone)]
+pub struct AnimationEvent {
+    pub entity: Entity,
+    pub animation: String,
+    pub name: String,
+}
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AnimationSet;
+
 #[derive(Component, Debug)]
 pub struct Animator {
-    pub animations: HashMap<String, Animation>,
+    pub sheet: Handle<AnimationSheet>,
+    pub part: String,
     pub current_animation: String,
     pub prev_animation: String,
+    // Waiting for the current clip to end, see `Crossfade::AfterClip`
+    pub next_animation: Option<String>,
     pub current_frame: usize,
     pub paused: bool,
     pub counter: f32,
 }
 
+impl Animator {
+    pub fn new(sheet: Handle<AnimationSheet>, part: &str, animation: &str) -> Self {
+        Animator {
+            sheet,
+            part: part.to_string(),
+            current_animation: animation.to_string(),
+            prev_animation: animation.to_string(),
+            next_animation: None,
+            current_frame: 0,
+            paused: false,
+            counter: 0.0,
+        }
+    }
+
+    pub fn play(&mut self, animation: &str, crossfade: Crossfade) {
+        match crossfade {
+            Crossfade::Restart => {
+                self.current_animation = animation.to_string();
+                self.next_animation = None;
+            },
+            Crossfade::KeepFrame => {
+                self.current_animation = animation.to_string();
+                self.prev_animation = animation.to_string();
+                self.next_animation = None;
+            },
+            Crossfade::AfterClip => {
+                self.next_animation = Some(animation.to_string());
+            },
+        }
+    }
+
+    // The attachment for the frame being shown, if the clip has one
+    pub fn attachment<'a>(&self, sheets: &'a Assets<AnimationSheet>, name: &str) -> Option<&'a Attachment> {
+        sheets
+            .get(&self.sheet)?
+            .clip(&self.part, &self.current_animation)?
+            .attachments
+            .get(name)?
+            .get(self.current_frame)
+    }
+}
+
+fn build_animation_atlases(
+    mut ev_sheets: EventReader<AssetEvent<AnimationSheet>>,
+    sheets: Res<Assets<AnimationSheet>>,
+    mut atlases: ResMut<AnimationAtlases>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    asset_server: Res<AssetServer>,
+) {
+    for event in ev_sheets.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                let sheet = match sheets.get(handle) {
+                    Some(sheet) => sheet,
+                    None => continue,
+                };
+
+                let texture_atlas = TextureAtlas::from_grid(
+                    asset_server.load(sheet.texture.as_str()),
+                    sheet.tile_size,
+                    sheet.columns,
+                    sheet.rows,
+                    None,
+                    None,
+                );
+
+                atlases.atlases.insert(handle.id(), texture_atlases.add(texture_atlas));
+            },
+            AssetEvent::Removed { handle } => {
+                atlases.atlases.remove(&handle.id());
+            },
+        }
+    }
+}
+
+fn sync_animation_atlases(
+    atlases: Res<AnimationAtlases>,
+    mut query: Query<(&Animator, &mut Handle<TextureAtlas>)>,
+) {
+    for (animator, mut texture_atlas) in query.iter_mut() {
+        let atlas = match atlases.atlases.get(&animator.sheet.id()) {
+            Some(atlas) => atlas,
+            None => continue,
+        };
+
+        if *texture_atlas != *atlas {
+            *texture_atlas = atlas.clone();
+        }
+    }
+}
+
+fn update_animation_states(
+    mut query: Query<(&mut Animator, &AnimationParams)>,
+    sheets: Res<Assets<AnimationSheet>>,
+) {
+    for (mut animator, params) in query.iter_mut() {
+        let controller = match sheets.get(&animator.sheet).and_then(|sheet| sheet.controller.as_ref()) {
+            Some(controller) => controller,
+            None => continue,
+        };
+
+        let state = animator.next_animation.clone().unwrap_or_else(|| animator.current_animation.clone());
+
+        let transition = controller.transitions
+            .iter()
+            .filter(|transition| transition.to != state)
+            .filter(|transition| transition.from.as_ref().map_or(true, |from| *from == state))
+            .find(|transition| all_hold(&transition.when, params));
+
+        if let Some(transition) = transition {
+            animator.play(&transition.to, transition.crossfade);
+        }
+
+        let paused = controller.states
+            .get(&animator.current_animation)
+            .map_or(false, |state| !state.hold_when.is_empty() && all_hold(&state.hold_when, params));
+
+        if animator.paused != paused {
+            animator.paused = paused;
+        }
+    }
+}
+
 pub fn animator_sys (
-    mut query: Query<(&mut Animator, &mut TextureAtlasSprite)>,
+    mut query: Query<(Entity, &mut Animator, &mut TextureAtlasSprite)>,
+    sheets: Res<Assets<AnimationSheet>>,
     time: Res<Time>,
+    mut ev_animation: EventWriter<AnimationEvent>,
 ) {
-    for (mut animator, mut sprite) in query.iter_mut() {
+    for (entity, mut animator, mut sprite) in query.iter_mut() {
         let mut new_counter = animator.counter + time.delta_seconds();
 
         let current_animation = animator.current_animation.clone();
-        let prev_animation = animator.prev_animation.as_str();
 
-        let animation = animator.animations.get(&current_animation).unwrap().clone();
+        // Not loaded yet, or the clip was removed from the file
+        let animation = match sheets.get(&animator.sheet).and_then(|sheet| sheet.clip(&animator.part, &current_animation)) {
+            Some(animation) if !animation.frames.is_empty() => animation,
+            _ => continue,
+        };
 
         let fraction: f32 = 1.0 / (animation.fps as f32);
+        let mut entered_frame = None;
 
-        if current_animation != prev_animation {
+        if current_animation != animator.prev_animation {
             new_counter = 0.0;
             animator.current_frame = 0;
-            sprite.index = animation.frames[0];
+            entered_frame = Some(0);
+        } else {
+            // The clip can get shorter when the file is reloaded
+            if animator.current_frame >= animation.frames.len() {
+                animator.current_frame = 0;
+            }
 
-            animator.counter = new_counter;
-            animator.prev_animation = current_animation.to_string();
+            if !animator.paused && new_counter > fraction {
+                let new_frame = (animator.current_frame + 1) % animation.frames.len();
+                let clip_ended = new_frame <= animator.current_frame;
 
-            continue;
-        }
+                if let (true, Some(next_animation)) = (clip_ended, animator.next_animation.take()) {
+                    // Picked up as a clip change on the next update
+                    animator.current_animation = next_animation;
+                } else if animation.looping || !clip_ended {
+                    animator.current_frame = new_frame;
+                    entered_frame = Some(new_frame);
+                } else {
+                    // If not looping and at the end, hold the last frame
+                    animator.current_frame = animation.frames.len() - 1;
+                }
 
-        if animator.paused {
-            continue;
+                new_counter = 0.0;
+            }
         }
 
-        if new_counter > fraction {
-            let new_frame = (animator.current_frame + 1) % animation.frames.len();
+        sprite.index = animation.frames[animator.current_frame];
 
-            if animation.looping || new_frame > animator.current_frame {
-                animator.current_frame = new_frame;
-            } else {
-                // If not looping and at the end, hold the last frame
-                animator.current_frame = animation.frames.len() - 1;
-            }
+        animator.counter = new_counter;
+        animator.prev_animation = current_animation.to_string();
 
-            sprite.index = animation.frames[animator.current_frame];
+        let names = entered_frame.and_then(|frame| animation.events.get(&frame));
 
-            new_counter = 0.0;
+        for name in names.into_iter().flatten() {
+            ev_animation.send(AnimationEvent {
+                entity,
+                animation: current_animation.clone(),
+                name: name.clone(),
+            });
         }
-
-        animator.counter = new_counter;
-        animator.prev_animation = current_animation.to_string();
     }
 }
 
@@ -71,6 +384,16 @@ pub struct AnimationPlugin {}
 
 impl Plugin for AnimationPlugin {
     fn build(&self, app: &mut App) {
-        // app.add_systems(Update, animator_sys);
+        app
+            .add_asset::<AnimationSheet>()
+            .init_asset_loader::<AnimationSheetLoader>()
+            .init_resource::<AnimationAtlases>()
+            .add_event::<AnimationEvent>()
+            .add_systems(Update, (
+                build_animation_atlases,
+                sync_animation_atlases,
+                update_animation_states,
+                animator_sys,
+            ).chain().in_set(AnimationSet));
     }
 }
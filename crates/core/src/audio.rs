@@ -0,0 +1,233 @@
+use bevy::{prelude::{Plugin, App, Resource, Component, Res, ResMut, Query, Handle, AudioSource, AudioBundle, PlaybackSettings, AudioSink, AudioSinkPlayback, Update, Startup, Event, EventReader, Commands, AssetServer, DetectChanges}, audio::{PlaybackMode, Volume, VolumeLevel}, utils::HashMap};
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AudioBus {
+    Master,
+    Music,
+    Sfx,
+    Ui,
+}
+
+impl AudioBus {
+    pub const ALL: [AudioBus; 4] = [
+        AudioBus::Master,
+        AudioBus::Music,
+        AudioBus::Sfx,
+        AudioBus::Ui,
+    ];
+}
+
+// Volume of every bus from 0 to 1. Everything that plays goes through music,
+// SFX or UI, and all of them through master.
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioBuses {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+    pub ui: f32,
+    pub muted: bool,
+}
+
+impl Default for AudioBuses {
+    fn default() -> Self {
+        AudioBuses {
+            master: 1.0,
+            music: 1.0,
+            sfx: 1.0,
+            ui: 1.0,
+            muted: false,
+        }
+    }
+}
+
+impl AudioBuses {
+    pub fn get(&self, bus: AudioBus) -> f32 {
+        match bus {
+            AudioBus::Master => self.master,
+            AudioBus::Music => self.music,
+            AudioBus::Sfx => self.sfx,
+            AudioBus::Ui => self.ui,
+        }
+    }
+
+    pub fn set(&mut self, bus: AudioBus, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+
+        match bus {
+            AudioBus::Master => self.master = volume,
+            AudioBus::Music => self.music = volume,
+            AudioBus::Sfx => self.sfx = volume,
+            AudioBus::Ui => self.ui = volume,
+        }
+    }
+
+    // What a sound on `bus` actually plays at
+    pub fn volume(&self, bus: AudioBus) -> f32 {
+        if self.muted {
+            return 0.0;
+        }
+
+        match bus {
+            AudioBus::Master => self.master,
+            _ => self.master * self.get(bus),
+        }
+    }
+}
+
+// Volume of a playing sound before the buses are applied, kept so it can be
+// adjusted when a bus changes.
+#[derive(Component, Debug, Clone)]
+pub struct BusVolume {
+    pub bus: AudioBus,
+    pub volume: f32,
+}
+
+pub fn bus_audio(source: Handle<AudioSource>, buses: &AudioBuses, bus: AudioBus, volume: f32, mode: PlaybackMode) -> (AudioBundle, BusVolume) {
+    (
+        AudioBundle {
+            source,
+            settings: PlaybackSettings {
+                mode,
+                volume: Volume::Relative(VolumeLevel::new(volume * buses.volume(bus))),
+                ..PlaybackSettings::default()
+            },
+        },
+        BusVolume { bus, volume },
+    )
+}
+
+// Every sound effect in the game. Gameplay code sends `PlaySfx` with one of
+// these instead of loading and spawning audio itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sfx {
+    Jump,
+    PinPickup,
+    Hurt,
+    ButtonClick,
+    LevelStart,
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PlaySfx(pub Sfx);
+
+#[derive(Debug, Clone)]
+pub struct SfxDefinition {
+    pub path: &'static str,
+    pub bus: AudioBus,
+    pub volume: f32,
+    // Playback speed is picked from 1 - variation..1 + variation
+    pub pitch_variation: f32,
+    // Copies of the sound allowed to play at once, more are dropped
+    pub max_voices: usize,
+}
+
+impl Sfx {
+    pub fn definition(&self) -> SfxDefinition {
+        match self {
+            Sfx::Jump => SfxDefinition { path: "audio/SFX_Jump_11.ogg", bus: AudioBus::Sfx, volume: 0.2, pitch_variation: 0.08, max_voices: 2 },
+            Sfx::PinPickup => SfxDefinition { path: "audio/SFX_powerUp10.ogg", bus: AudioBus::Sfx, volume: 0.2, pitch_variation: 0.05, max_voices: 3 },
+            Sfx::Hurt => SfxDefinition { path: "audio/SFX_fall2.ogg", bus: AudioBus::Sfx, volume: 0.2, pitch_variation: 0.0, max_voices: 1 },
+            Sfx::ButtonClick => SfxDefinition { path: "audio/btn_click.ogg", bus: AudioBus::Ui, volume: 1.0, pitch_variation: 0.0, max_voices: 2 },
+            Sfx::LevelStart => SfxDefinition { path: "audio/SFX_powerUp10.ogg", bus: AudioBus::Ui, volume: 1.0, pitch_variation: 0.0, max_voices: 1 },
+        }
+    }
+
+    pub const ALL: [Sfx; 5] = [
+        Sfx::Jump,
+        Sfx::PinPickup,
+        Sfx::Hurt,
+        Sfx::ButtonClick,
+        Sfx::LevelStart,
+    ];
+}
+
+// Handles are loaded once at startup so the first play of a sound isn't late
+#[derive(Resource, Debug, Default)]
+pub struct SfxRegistry {
+    handles: HashMap<Sfx, Handle<AudioSource>>,
+}
+
+#[derive(Component, Debug)]
+pub struct SfxVoice(pub Sfx);
+
+fn load_sfx(
+    mut registry: ResMut<SfxRegistry>,
+    asset_server: Res<AssetServer>,
+) {
+    for sfx in Sfx::ALL {
+        registry.handles.insert(sfx, asset_server.load(sfx.definition().path));
+    }
+}
+
+fn play_sfx(
+    mut ev_play_sfx: EventReader<PlaySfx>,
+    registry: Res<SfxRegistry>,
+    buses: Res<AudioBuses>,
+    q_voices: Query<&SfxVoice>,
+    mut commands: Commands,
+) {
+    let mut voices: HashMap<Sfx, usize> = HashMap::default();
+
+    for voice in q_voices.iter() {
+        *voices.entry(voice.0).or_default() += 1;
+    }
+
+    let mut rng = rand::thread_rng();
+
+    for PlaySfx(sfx) in ev_play_sfx.iter() {
+        let definition = sfx.definition();
+        let playing = voices.entry(*sfx).or_default();
+
+        if *playing >= definition.max_voices {
+            continue;
+        }
+
+        let handle = match registry.handles.get(sfx) {
+            Some(handle) => handle.clone(),
+            None => continue,
+        };
+
+        *playing += 1;
+
+        let speed = if definition.pitch_variation > 0.0 {
+            rng.gen_range(1.0 - definition.pitch_variation..1.0 + definition.pitch_variation)
+        } else {
+            1.0
+        };
+
+        let (mut audio, bus_volume) = bus_audio(handle, &buses, definition.bus, definition.volume, PlaybackMode::Despawn);
+        audio.settings.speed = speed;
+
+        commands.spawn((audio, bus_volume, SfxVoice(*sfx)));
+    }
+}
+
+fn update_bus_volumes(
+    buses: Res<AudioBuses>,
+    q_sinks: Query<(&AudioSink, &BusVolume)>,
+) {
+    if !buses.is_changed() {
+        return;
+    }
+
+    for (sink, bus_volume) in q_sinks.iter() {
+        sink.set_volume(bus_volume.volume * buses.volume(bus_volume.bus));
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AudioPlugin {}
+
+impl Plugin for AudioPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<AudioBuses>()
+            .init_resource::<SfxRegistry>()
+            .add_event::<PlaySfx>()
+            .add_systems(Startup, load_sfx)
+            .add_systems(Update, (play_sfx, update_bus_volumes));
+    }
+}
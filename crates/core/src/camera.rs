@@ -1,10 +1,86 @@
 use std::time::Duration;
 use bevy_parallax::*;
 
-use bevy::{prelude::*, window::PrimaryWindow, core_pipeline::bloom::BloomSettings};
+use bevy::{prelude::*, window::PrimaryWindow, core_pipeline::bloom::BloomSettings, transform::TransformSystem};
 use bevy_tweening::{Animator, Tween, EaseFunction, lens::TransformPositionLens};
 use kt_util::constants::{ASPECT_RATIO_X, ASPECT_RATIO_Y};
 
+use crate::physics::InterpolationSet;
+
+// Target moves further than this in one frame -> it was teleported, the
+// camera jumps instead of panning across the level
+const CAMERA_SNAP_DISTANCE: f32 = 64.0;
+// How quickly the look-ahead reacts to changes in the target's velocity
+const CAMERA_VELOCITY_SMOOTHING: f32 = 8.0;
+
+// What the camera follows, the player
+#[derive(Clone, Component, Debug, Default)]
+pub struct CameraTarget {
+    // Set by the game, the camera only moves vertically to the height the
+    // target stands at, unless it leaves the vertical dead zone
+    pub grounded: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CameraZoneMode {
+    // The camera stays centred on the zone
+    Lock,
+    // The camera follows but never shows anything outside the zone
+    #[default]
+    Clamp,
+}
+
+// Takes over the camera while the target is inside it, centred on the
+// entity's position
+#[derive(Clone, Component, Debug, Default)]
+pub struct CameraZone {
+    pub size: Vec2,
+    pub mode: CameraZoneMode,
+}
+
+// The camera never shows anything outside the level
+#[derive(Resource, Clone, Debug, Default)]
+pub struct CameraBounds {
+    pub level: Option<Rect>,
+}
+
+#[derive(Clone, Component, Debug)]
+pub struct CameraController {
+    // Roughly the time it takes to catch up with the target
+    pub smooth_time: f32,
+    // The camera leads the target by its velocity over this many seconds
+    pub look_ahead_time: f32,
+    pub look_ahead_max: f32,
+    // Half height of the band the target can move in without the camera
+    // leaving the ground it last stood on
+    pub vertical_dead_zone: f32,
+    // Shows a bit more above the ground than below it
+    pub vertical_offset: f32,
+    // Trimmed off the level bounds, the outer half tiles are never shown
+    pub bounds_inset: Vec2,
+    velocity: Vec2,
+    target_velocity: Vec2,
+    last_target: Option<Vec2>,
+    ground_y: f32,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        CameraController {
+            smooth_time: 0.15,
+            look_ahead_time: 0.25,
+            look_ahead_max: 48.0,
+            vertical_dead_zone: ASPECT_RATIO_Y * 0.2,
+            vertical_offset: 16.0,
+            bounds_inset: Vec2::new(0.0, 8.0),
+            velocity: Vec2::ZERO,
+            target_velocity: Vec2::ZERO,
+            last_target: None,
+            ground_y: 0.0,
+        }
+    }
+}
+
 fn spawn_camera(
     mut commands: Commands,
     // mut create_parallax: EventWriter<CreateParallaxEvent>,
@@ -28,6 +104,7 @@ fn spawn_camera(
             ..default()
         },
         ParallaxCameraComponent::default(),
+        CameraController::default(),
         BloomSettings {
             intensity: 0.1,
             ..default()
@@ -65,6 +142,124 @@ pub fn auto_scale_sys(
     }
 }
 
+// Critically damped spring towards `target`, never overshoots
+fn smooth_damp(current: f32, target: f32, velocity: &mut f32, smooth_time: f32, delta: f32) -> f32 {
+    let omega = 2.0 / smooth_time.max(0.0001);
+    let x = omega * delta;
+    let decay = 1.0 / (1.0 + x + 0.48 * x * x + 0.235 * x * x * x);
+    let change = current - target;
+    let temp = (*velocity + omega * change) * delta;
+
+    *velocity = (*velocity - omega * temp) * decay;
+
+    target + (change + temp) * decay
+}
+
+// Keeps a view of `half_view` around `point` inside `rect`, centred on the
+// rect on axes where it is smaller than the view
+fn clamp_view(rect: Rect, point: Vec2, half_view: Vec2) -> Vec2 {
+    let clamp_axis = |point: f32, min: f32, max: f32, half: f32| {
+        if max - min <= half * 2.0 {
+            (min + max) / 2.0
+        } else {
+            point.clamp(min + half, max - half)
+        }
+    };
+
+    Vec2::new(
+        clamp_axis(point.x, rect.min.x, rect.max.x, half_view.x),
+        clamp_axis(point.y, rect.min.y, rect.max.y, half_view.y),
+    )
+}
+
+pub fn follow_target_sys(
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_target: Query<(&CameraTarget, &Transform), Without<CameraController>>,
+    q_zones: Query<(&CameraZone, &GlobalTransform)>,
+    mut q_camera: Query<(&mut CameraController, &mut Transform)>,
+    bounds: Res<CameraBounds>,
+    time: Res<Time>,
+) {
+    let window = match q_window.get_single() {
+        Ok(window) => window,
+        Err(..) => return,
+    };
+
+    let (target, target_transform) = match q_target.get_single() {
+        Ok(target) => target,
+        Err(..) => return,
+    };
+
+    let (mut controller, mut transform) = match q_camera.get_single_mut() {
+        Ok(camera) => camera,
+        Err(..) => return,
+    };
+
+    let delta = time.delta_seconds();
+    let position = target_transform.translation.truncate();
+    let half_view = Vec2::new(window.width(), window.height()) * transform.scale.truncate() / 2.0;
+
+    let teleported = controller.last_target.map_or(true, |last| last.distance(position) > CAMERA_SNAP_DISTANCE);
+
+    match controller.last_target {
+        _ if teleported => controller.target_velocity = Vec2::ZERO,
+        Some(last) if delta > 0.0 => {
+            let velocity = (position - last) / delta;
+            let smoothing = 1.0 - (-CAMERA_VELOCITY_SMOOTHING * delta).exp();
+            controller.target_velocity = controller.target_velocity.lerp(velocity, smoothing);
+        },
+        _ => {},
+    }
+
+    controller.last_target = Some(position);
+
+    if target.grounded || teleported {
+        controller.ground_y = position.y;
+    }
+
+    let dead_zone = controller.vertical_dead_zone;
+    controller.ground_y = controller.ground_y.clamp(position.y - dead_zone, position.y + dead_zone);
+
+    let look_ahead = (controller.target_velocity.x * controller.look_ahead_time)
+        .clamp(-controller.look_ahead_max, controller.look_ahead_max);
+
+    let mut goal = Vec2::new(position.x + look_ahead, controller.ground_y + controller.vertical_offset);
+
+    let zone = q_zones
+        .iter()
+        .map(|(zone, zone_transform)| (zone.mode, Rect::from_center_size(zone_transform.translation().truncate(), zone.size)))
+        .find(|(_, rect)| rect.contains(position));
+
+    match zone {
+        Some((CameraZoneMode::Lock, rect)) => goal = rect.center(),
+        Some((CameraZoneMode::Clamp, rect)) => goal = clamp_view(rect, goal, half_view),
+        None => {},
+    }
+
+    if let Some(level) = bounds.level {
+        let level = Rect {
+            min: level.min + controller.bounds_inset,
+            max: level.max - controller.bounds_inset,
+        };
+
+        goal = clamp_view(level, goal, half_view);
+    }
+
+    if teleported {
+        controller.velocity = Vec2::ZERO;
+        transform.translation = goal.extend(transform.translation.z);
+        return;
+    }
+
+    let smooth_time = controller.smooth_time;
+    let mut velocity = controller.velocity;
+
+    transform.translation.x = smooth_damp(transform.translation.x, goal.x, &mut velocity.x, smooth_time, delta);
+    transform.translation.y = smooth_damp(transform.translation.y, goal.y, &mut velocity.y, smooth_time, delta);
+
+    controller.velocity = velocity;
+}
+
 #[derive(Debug, Default)]
 pub struct CameraPlugin {}
 
@@ -72,7 +267,10 @@ impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
         app
             .insert_resource(ClearColor(Color::rgb(0.1, 0.1, 0.1)))
+            .init_resource::<CameraBounds>()
             .add_systems(Startup, spawn_camera)
-            .add_systems(Update, auto_scale_sys);
+            .add_systems(Update, auto_scale_sys)
+            // After gameplay and interpolation moved the target this frame
+            .add_systems(PostUpdate, follow_target_sys.after(InterpolationSet::Apply).before(TransformSystem::TransformPropagate));
     }
 }
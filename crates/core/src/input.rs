@@ -0,0 +1,264 @@
+use std::collections::{BTreeMap, HashSet};
+
+use bevy::{prelude::{Plugin, App, Resource, Res, ResMut, Input, KeyCode, PreUpdate, FixedUpdate, IntoSystemConfigs, SystemSet, Gamepads, GamepadButton, GamepadButtonType, GamepadAxis, GamepadAxisType, Axis, Vec2}, input::InputSystem};
+use serde::{Serialize, Deserialize};
+
+use crate::replay::not_playing_replay;
+
+const STICK_DEADZONE: f32 = 0.35;
+const TRIGGER_DEADZONE: f32 = 0.05;
+
+// Logical actions the game reacts to. Systems should read `Input<InputAction>`
+// instead of raw key codes so bindings can be changed without touching them.
+// Replays store actions by position, new ones go at the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum InputAction {
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Stretch,
+    LetGo,
+    Pause,
+    MenuUp,
+    MenuDown,
+    MenuLeft,
+    MenuRight,
+    Confirm,
+    MoveDown,
+}
+
+impl InputAction {
+    pub const ALL: [InputAction; 12] = [
+        InputAction::MoveLeft,
+        InputAction::MoveRight,
+        InputAction::Jump,
+        InputAction::Stretch,
+        InputAction::LetGo,
+        InputAction::Pause,
+        InputAction::MenuUp,
+        InputAction::MenuDown,
+        InputAction::MenuLeft,
+        InputAction::MenuRight,
+        InputAction::Confirm,
+        InputAction::MoveDown,
+    ];
+}
+
+// Analog values that don't fit into a pressed/released action. The stick only
+// presses the move actions, `stretch` is normalized to 0..1.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct InputAxes {
+    pub stretch: f32,
+}
+
+// Input as seen by a single fixed simulation tick. Frames and ticks don't line
+// up one to one, so presses that happen between ticks are buffered here and
+// handed to the next tick instead of being lost.
+#[derive(Resource, Debug, Default)]
+pub struct TickInput {
+    pub actions: Input<InputAction>,
+    pub axes: InputAxes,
+    pending: HashSet<InputAction>,
+}
+
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputBindings {
+    pub keyboard: BTreeMap<InputAction, Vec<KeyCode>>,
+    pub gamepad: BTreeMap<InputAction, Vec<GamepadButtonType>>,
+    pub stretch_trigger: GamepadButtonType,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        let mut keyboard = BTreeMap::new();
+
+        keyboard.insert(InputAction::MoveLeft, vec![KeyCode::Left]);
+        keyboard.insert(InputAction::MoveRight, vec![KeyCode::Right]);
+        keyboard.insert(InputAction::Jump, vec![KeyCode::Space]);
+        keyboard.insert(InputAction::Stretch, vec![KeyCode::X]);
+        keyboard.insert(InputAction::LetGo, vec![KeyCode::Space]);
+        keyboard.insert(InputAction::Pause, vec![KeyCode::Escape]);
+        keyboard.insert(InputAction::MenuUp, vec![KeyCode::Up]);
+        keyboard.insert(InputAction::MenuDown, vec![KeyCode::Down]);
+        keyboard.insert(InputAction::MenuLeft, vec![KeyCode::Left]);
+        keyboard.insert(InputAction::MenuRight, vec![KeyCode::Right]);
+        keyboard.insert(InputAction::Confirm, vec![KeyCode::Return]);
+        keyboard.insert(InputAction::MoveDown, vec![KeyCode::Down]);
+
+        let mut gamepad = BTreeMap::new();
+
+        gamepad.insert(InputAction::MoveLeft, vec![GamepadButtonType::DPadLeft]);
+        gamepad.insert(InputAction::MoveRight, vec![GamepadButtonType::DPadRight]);
+        gamepad.insert(InputAction::Jump, vec![GamepadButtonType::South]);
+        gamepad.insert(InputAction::Stretch, vec![GamepadButtonType::RightTrigger2, GamepadButtonType::West]);
+        gamepad.insert(InputAction::LetGo, vec![GamepadButtonType::South]);
+        gamepad.insert(InputAction::Pause, vec![GamepadButtonType::Start]);
+        gamepad.insert(InputAction::MenuUp, vec![GamepadButtonType::DPadUp]);
+        gamepad.insert(InputAction::MenuDown, vec![GamepadButtonType::DPadDown]);
+        gamepad.insert(InputAction::MenuLeft, vec![GamepadButtonType::DPadLeft]);
+        gamepad.insert(InputAction::MenuRight, vec![GamepadButtonType::DPadRight]);
+        gamepad.insert(InputAction::Confirm, vec![GamepadButtonType::South]);
+        gamepad.insert(InputAction::MoveDown, vec![GamepadButtonType::DPadDown]);
+
+        InputBindings {
+            keyboard,
+            gamepad,
+            stretch_trigger: GamepadButtonType::RightTrigger2,
+        }
+    }
+}
+
+impl InputBindings {
+    // Settings files written by older builds won't know about newly added
+    // actions, so fall back to the default keys for anything missing.
+    pub fn fill_missing_from(&mut self, defaults: &InputBindings) {
+        for (action, keys) in defaults.keyboard.iter() {
+            self.keyboard.entry(*action).or_insert_with(|| keys.clone());
+        }
+
+        for (action, buttons) in defaults.gamepad.iter() {
+            self.gamepad.entry(*action).or_insert_with(|| buttons.clone());
+        }
+    }
+}
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InputActionSet;
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TickInputSet;
+
+pub fn update_input_actions(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_input: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepad_button_axes: Res<Axis<GamepadButton>>,
+    bindings: Res<InputBindings>,
+    mut actions: ResMut<Input<InputAction>>,
+    mut axes: ResMut<InputAxes>,
+) {
+    actions.clear();
+
+    let mut stick = Vec2::ZERO;
+    let mut trigger: f32 = 0.0;
+
+    for gamepad in gamepads.iter() {
+        let x = gamepad_axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX)).unwrap_or(0.0);
+        let y = gamepad_axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY)).unwrap_or(0.0);
+
+        if x.abs() > stick.x.abs() {
+            stick.x = x;
+        }
+
+        if y.abs() > stick.y.abs() {
+            stick.y = y;
+        }
+
+        let value = gamepad_button_axes
+            .get(GamepadButton::new(gamepad, bindings.stretch_trigger))
+            .unwrap_or(0.0);
+
+        trigger = trigger.max(value);
+    }
+
+    if stick.x.abs() < STICK_DEADZONE {
+        stick.x = 0.0;
+    }
+
+    if stick.y.abs() < STICK_DEADZONE {
+        stick.y = 0.0;
+    }
+
+    if trigger < TRIGGER_DEADZONE {
+        trigger = 0.0;
+    }
+
+    for action in InputAction::ALL {
+        let keyboard_pressed = bindings.keyboard
+            .get(&action)
+            .map_or(false, |keys| keyboard_input.any_pressed(keys.iter().copied()));
+
+        let gamepad_pressed = bindings.gamepad
+            .get(&action)
+            .map_or(false, |buttons| {
+                gamepads.iter().any(|gamepad| {
+                    gamepad_input.any_pressed(buttons.iter().map(|button| GamepadButton::new(gamepad, *button)))
+                })
+            });
+
+        let analog_pressed = match action {
+            InputAction::MoveLeft | InputAction::MenuLeft => stick.x < 0.0,
+            InputAction::MoveRight | InputAction::MenuRight => stick.x > 0.0,
+            InputAction::MenuUp => stick.y > 0.0,
+            InputAction::MenuDown | InputAction::MoveDown => stick.y < 0.0,
+            InputAction::Stretch => trigger > 0.0,
+            _ => false,
+        };
+
+        if action == InputAction::Stretch {
+            axes.stretch = if keyboard_pressed {
+                1.0
+            } else if trigger > 0.0 {
+                trigger
+            } else if gamepad_pressed {
+                1.0
+            } else {
+                0.0
+            };
+        }
+
+        if keyboard_pressed || gamepad_pressed || analog_pressed {
+            actions.press(action);
+        } else {
+            actions.release(action);
+        }
+    }
+}
+
+pub fn buffer_tick_input(
+    actions: Res<Input<InputAction>>,
+    mut tick_input: ResMut<TickInput>,
+) {
+    for action in actions.get_just_pressed() {
+        tick_input.pending.insert(*action);
+    }
+}
+
+pub fn update_tick_input(
+    actions: Res<Input<InputAction>>,
+    axes: Res<InputAxes>,
+    mut tick_input: ResMut<TickInput>,
+) {
+    tick_input.actions.clear();
+
+    for action in InputAction::ALL {
+        // A tap that was released before this tick still counts for one tick.
+        // Always taken out, so a held key doesn't leave it for the next tick.
+        let buffered = tick_input.pending.remove(&action);
+
+        if actions.pressed(action) || buffered {
+            tick_input.actions.press(action);
+        } else {
+            tick_input.actions.release(action);
+        }
+    }
+
+    tick_input.axes = axes.clone();
+}
+
+#[derive(Debug, Default)]
+pub struct InputPlugin {}
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Input<InputAction>>()
+            .init_resource::<InputAxes>()
+            .init_resource::<InputBindings>()
+            .init_resource::<TickInput>()
+            .add_systems(PreUpdate, (update_input_actions, buffer_tick_input).chain().in_set(InputActionSet).after(InputSystem))
+            .add_systems(FixedUpdate, update_tick_input.in_set(TickInputSet).run_if(not_playing_replay));
+    }
+}
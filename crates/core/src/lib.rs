@@ -5,6 +5,9 @@ use bevy_tweening::TweeningPlugin;
 use particle::ParticlePlugin;
 use camera::CameraPlugin;
 use animation::AnimationPlugin;
+use input::InputPlugin;
+use replay::ReplayPlugin;
+use audio::AudioPlugin;
 
 use self::{physics::PhysicsPlugin, render::RenderPlugin, mouse::MousePlugin};
 
@@ -14,6 +17,9 @@ pub mod camera;
 pub mod physics;
 pub mod render;
 pub mod mouse;
+pub mod input;
+pub mod replay;
+pub mod audio;
 
 #[derive(Debug, Default)]
 pub struct CorePlugin {}
@@ -31,6 +37,9 @@ impl Plugin for CorePlugin {
             .add_plugins(CameraPlugin {})
             .add_plugins(RenderPlugin {})
             .add_plugins(MousePlugin {})
+            .add_plugins(InputPlugin {})
+            .add_plugins(ReplayPlugin {})
+            .add_plugins(AudioPlugin {})
             .add_plugins(PhysicsPlugin {});
     }
 }
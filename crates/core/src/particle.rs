@@ -1,78 +1,310 @@
-use std::time::Duration;
+use std::{time::Duration, sync::Arc, f32::consts::TAU};
 use bevy::prelude::*;
+use rand::Rng;
+
+// Particles alive at once, further spawns are dropped
+const MAX_PARTICLES: usize = 512;
+
+pub trait CurveValue: Copy {
+    fn lerp(from: Self, to: Self, t: f32) -> Self;
+}
+
+impl CurveValue for f32 {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        from + (to - from) * t
+    }
+}
+
+impl CurveValue for Color {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        let from = Vec4::from(from.as_rgba_f32());
+        let to = Vec4::from(to.as_rgba_f32());
+
+        Color::from(from.lerp(to, t))
+    }
+}
+
+// Values over a particle's life, keys are `(time from 0 to 1, value)` in order
+#[derive(Clone, Debug)]
+pub struct Curve<T: CurveValue> {
+    pub keys: Vec<(f32, T)>,
+}
+
+impl<T: CurveValue> Curve<T> {
+    pub fn constant(value: T) -> Self {
+        Curve { keys: vec![(0.0, value)] }
+    }
+
+    pub fn linear(from: T, to: T) -> Self {
+        Curve { keys: vec![(0.0, from), (1.0, to)] }
+    }
+
+    // `None` for a curve without keys
+    pub fn sample(&self, t: f32) -> Option<T> {
+        let (first, last) = match (self.keys.first(), self.keys.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return None,
+        };
+
+        if t <= first.0 {
+            return Some(first.1);
+        }
+
+        for pair in self.keys.windows(2) {
+            let ((from_t, from), (to_t, to)) = (pair[0], pair[1]);
+
+            if t <= to_t {
+                let span = (to_t - from_t).max(f32::EPSILON);
+                return Some(T::lerp(from, to, (t - from_t) / span));
+            }
+        }
+
+        Some(last.1)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SpawnShape {
+    Point,
+    // Anywhere inside the circle
+    Circle { radius: f32 },
+    // Anywhere inside the rectangle centred on the emitter
+    Rect { half_size: Vec2 },
+}
+
+impl SpawnShape {
+    fn sample(&self, rng: &mut impl Rng) -> Vec2 {
+        match self {
+            SpawnShape::Point => Vec2::ZERO,
+            SpawnShape::Circle { radius } => {
+                // sqrt keeps the points spread evenly instead of bunched in the middle
+                let distance = radius * rng.gen::<f32>().sqrt();
+                Vec2::from_angle(rng.gen_range(0.0..TAU)) * distance
+            },
+            SpawnShape::Rect { half_size } => Vec2::new(
+                rng.gen_range(-half_size.x..=half_size.x),
+                rng.gen_range(-half_size.y..=half_size.y),
+            ),
+        }
+    }
+}
+
+// Everything about how particles of one kind look and move. Shared between
+// the emitter and all its particles.
+#[derive(Clone, Debug)]
+pub struct ParticleEffect {
+    pub atlas: Handle<TextureAtlas>,
+    // Played once over the particle's life
+    pub frames: Vec<usize>,
+    pub lifetime: f32,
+    // Every particle lives `lifetime` plus or minus up to this
+    pub lifetime_jitter: f32,
+    pub shape: SpawnShape,
+    pub velocity_min: Vec2,
+    pub velocity_max: Vec2,
+    pub gravity: Vec2,
+    // Fraction of the velocity lost every second
+    pub drag: f32,
+    pub color: Curve<Color>,
+    // Multiplies the alpha of `color`
+    pub alpha: Curve<f32>,
+    pub scale: Curve<f32>,
+}
+
+impl ParticleEffect {
+    // A still particle playing `frames` once, what the emitter used to do
+    pub fn new(atlas: Handle<TextureAtlas>, frames: Vec<usize>, lifetime: f32) -> Self {
+        ParticleEffect {
+            atlas,
+            frames,
+            lifetime,
+            lifetime_jitter: 0.0,
+            shape: SpawnShape::Point,
+            velocity_min: Vec2::ZERO,
+            velocity_max: Vec2::ZERO,
+            gravity: Vec2::ZERO,
+            drag: 0.0,
+            color: Curve::constant(Color::WHITE),
+            alpha: Curve::constant(1.0),
+            scale: Curve::constant(1.0),
+        }
+    }
+
+    // Atlas index a new particle starts on, an effect without frames shows
+    // the first sprite of the atlas
+    pub fn first_frame(&self) -> usize {
+        self.frames.first().copied().unwrap_or(0)
+    }
+}
 
 #[derive(Component)]
 pub struct Particle {
     pub lifetime: Timer,
-    frames: Vec<usize>,
+    pub velocity: Vec2,
+    pub alive: bool,
+    effect: Arc<ParticleEffect>,
 }
 
 #[derive(Component)]
 pub struct ParticleEmitter {
-    pub frames: Vec<usize>,
-    pub particle_lifetime: f32,
-    pub handle: Handle<TextureAtlas>,
+    pub effect: Arc<ParticleEffect>,
+    // Particles spawned every time the timer finishes
+    pub burst: usize,
     pub spawning: bool,
-    pub spawn_timer: Timer, 
+    pub spawn_timer: Timer,
 }
 
-fn spawn_test_emitter (
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+impl ParticleEmitter {
+    // Spawns `burst` particles every time a repeating timer finishes, or
+    // waits for `trigger` with a once timer
+    pub fn new(effect: Arc<ParticleEffect>, burst: usize, spawn_timer: Timer) -> Self {
+        ParticleEmitter {
+            effect,
+            burst,
+            spawning: spawn_timer.mode() == TimerMode::Repeating,
+            spawn_timer,
+        }
+    }
+
+    // One burst on the next update
+    pub fn trigger(&mut self) {
+        self.spawning = true;
+        self.spawn_timer = Timer::from_seconds(0.01, TimerMode::Once);
+    }
+}
+
+// A one-off burst without an emitter entity, e.g. when something gets destroyed
+#[derive(Event, Clone)]
+pub struct EmitParticles {
+    pub effect: Arc<ParticleEffect>,
+    pub position: Vec3,
+    pub count: usize,
+}
+
+// Dead particles are hidden and handed out again instead of being despawned
+#[derive(Resource, Debug, Default)]
+pub struct ParticlePool {
+    free: Vec<Entity>,
+}
+
+type ParticleItem<'a> = (
+    &'a mut Particle,
+    &'a mut Transform,
+    &'a mut TextureAtlasSprite,
+    &'a mut Handle<TextureAtlas>,
+    &'a mut Visibility,
+);
+
+fn spawn_particle(
+    effect: &Arc<ParticleEffect>,
+    origin: Vec3,
+    rng: &mut impl Rng,
+    pool: &mut ParticlePool,
+    q_particles: &mut Query<ParticleItem, Without<ParticleEmitter>>,
+    commands: &mut Commands,
 ) {
-    let texture_handle = asset_server.load("sprite.png");
-
-    let texture_atlas = TextureAtlas::from_grid(
-        texture_handle,
-        Vec2::new(4.0, 4.0),
-        12,
-        5,
-        None,
-        None,
+    let lifetime = effect.lifetime + rng.gen_range(-1.0..=1.0) * effect.lifetime_jitter;
+    let velocity = Vec2::new(
+        rng.gen_range(effect.velocity_min.x.min(effect.velocity_max.x)..=effect.velocity_max.x.max(effect.velocity_min.x)),
+        rng.gen_range(effect.velocity_min.y.min(effect.velocity_max.y)..=effect.velocity_max.y.max(effect.velocity_min.y)),
     );
+    let translation = origin + effect.shape.sample(rng).extend(0.0);
+    let lifetime = Timer::from_seconds(lifetime.max(0.01), TimerMode::Once);
+
+    // Entities in the pool can be gone when a level was cleaned up
+    while let Some(entity) = pool.free.pop() {
+        let (mut particle, mut transform, mut sprite, mut atlas, mut visibility) = match q_particles.get_mut(entity) {
+            Ok(particle) => particle,
+            Err(..) => continue,
+        };
+
+        particle.lifetime = lifetime;
+        particle.velocity = velocity;
+        particle.alive = true;
+        particle.effect = effect.clone();
 
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
+        *transform = Transform::from_translation(translation);
+        *sprite = TextureAtlasSprite::new(effect.first_frame());
+        *atlas = effect.atlas.clone();
+        *visibility = Visibility::Inherited;
+
+        return;
+    }
 
     commands.spawn((
-        Transform::from_xyz(200.0, 200.0, 0.0),
-        ParticleEmitter {
-            frames: vec![48, 49, 50, 51, 52],
-            particle_lifetime: 1.0,
-            handle: texture_atlas_handle,
-            spawning: true,
-            spawn_timer: Timer::from_seconds(1.0, TimerMode::Repeating),
-        }
+        SpriteSheetBundle {
+            texture_atlas: effect.atlas.clone(),
+            sprite: TextureAtlasSprite::new(effect.first_frame()),
+            transform: Transform::from_translation(translation),
+            ..default()
+        },
+        Particle {
+            lifetime,
+            velocity,
+            alive: true,
+            effect: effect.clone(),
+        },
     ));
 }
 
 fn particle_update_sys (
-    mut query: Query<(Entity, &mut Particle, &mut TextureAtlasSprite)>,
-    mut commands: Commands,
+    mut query: Query<(Entity, &mut Particle, &mut Transform, &mut TextureAtlasSprite, &mut Visibility)>,
+    mut pool: ResMut<ParticlePool>,
     time: Res<Time>,
 ) {
-    for (entity, mut particle, mut texture_atlas_sprite) in query.iter_mut() {
-        particle.lifetime.tick(time.delta());
+    let delta = time.delta_seconds();
 
-        if particle.lifetime.finished() {
-            commands.entity(entity).despawn();
+    for (entity, mut particle, mut transform, mut texture_atlas_sprite, mut visibility) in query.iter_mut() {
+        if !particle.alive {
             continue;
         }
 
-        let frame = particle.lifetime.percent() * particle.frames.len() as f32;
-        if frame.floor() as usize >= particle.frames.len() {
+        particle.lifetime.tick(time.delta());
+
+        if particle.lifetime.finished() {
+            particle.alive = false;
+            *visibility = Visibility::Hidden;
+            pool.free.push(entity);
             continue;
         }
 
-        texture_atlas_sprite.index = particle.frames[frame.floor() as usize];
-    } 
+        let effect = particle.effect.clone();
+        let t = particle.lifetime.percent();
+
+        particle.velocity += effect.gravity * delta;
+        particle.velocity *= (1.0 - effect.drag * delta).max(0.0);
+        transform.translation += (particle.velocity * delta).extend(0.0);
+        // Empty curves fall back to what `ParticleEffect::new` uses
+        transform.scale = Vec3::splat(effect.scale.sample(t).unwrap_or(1.0));
+
+        let color = effect.color.sample(t).unwrap_or(Color::WHITE);
+        texture_atlas_sprite.color = color.with_a(color.a() * effect.alpha.sample(t).unwrap_or(1.0));
+
+        let frame = (t * effect.frames.len() as f32).floor() as usize;
+        if frame < effect.frames.len() {
+            texture_atlas_sprite.index = effect.frames[frame];
+        }
+    }
 }
 
 fn emitter_sys (
     mut commands: Commands,
-    mut query: Query<(&Transform, &mut ParticleEmitter)>,
+    mut query: Query<(&Transform, &mut ParticleEmitter), Without<Particle>>,
+    mut q_particles: Query<ParticleItem, Without<ParticleEmitter>>,
+    mut ev_emit_particles: EventReader<EmitParticles>,
+    mut pool: ResMut<ParticlePool>,
     timer: Res<Time>,
 ) {
+    let mut rng = rand::thread_rng();
+    let mut budget = MAX_PARTICLES.saturating_sub(q_particles.iter().filter(|(particle, ..)| particle.alive).count());
+
+    for event in ev_emit_particles.iter() {
+        for _ in 0..event.count.min(budget) {
+            spawn_particle(&event.effect, event.position, &mut rng, &mut pool, &mut q_particles, &mut commands);
+            budget -= 1;
+        }
+    }
+
     for (transform, mut emitter) in query.iter_mut() {
         if !emitter.spawning {
             emitter.spawn_timer.set_elapsed(Duration::from_secs(0));
@@ -89,18 +321,10 @@ fn emitter_sys (
             emitter.spawning = false;
         }
 
-        commands.spawn((
-            SpriteSheetBundle {
-                texture_atlas: emitter.handle.clone(),
-                sprite: TextureAtlasSprite::new(emitter.frames[0]),
-                transform: Transform::from_xyz(transform.translation.x, transform.translation.y, transform.translation.z),
-                ..default()
-            },
-            Particle {
-                lifetime: Timer::from_seconds(emitter.particle_lifetime, TimerMode::Once),
-                frames: emitter.frames.to_vec(),
-            }
-        ));
+        for _ in 0..emitter.burst.min(budget) {
+            spawn_particle(&emitter.effect, transform.translation, &mut rng, &mut pool, &mut q_particles, &mut commands);
+            budget -= 1;
+        }
     }
 }
 
@@ -110,7 +334,8 @@ pub struct ParticlePlugin {}
 impl Plugin for ParticlePlugin {
     fn build(&self, app: &mut App) {
         app
-            // .add_systems(Startup, spawn_test_emitter)
+            .init_resource::<ParticlePool>()
+            .add_event::<EmitParticles>()
             .add_systems(Update, emitter_sys)
             .add_systems(Update, particle_update_sys);
     }
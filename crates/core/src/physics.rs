@@ -1,8 +1,100 @@
-use bevy::prelude::{Plugin, App};
-use bevy_rapier2d::{prelude::{RapierPhysicsPlugin, NoUserData}, render::RapierDebugRenderPlugin};
+use std::time::Duration;
+
+use bevy::{prelude::{Plugin, App, Component, Vec3, Query, Transform, Res, FixedTime, FixedUpdate, PostUpdate, IntoSystemConfigs, SystemSet}, transform::TransformSystem};
+use bevy_rapier2d::{prelude::{RapierPhysicsPlugin, NoUserData, RapierConfiguration, TimestepMode, PhysicsSet}, render::RapierDebugRenderPlugin};
+use kt_util::constants::FIXED_TIMESTEP;
 
 const PPM: f32 = 1.0;
 
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InterpolationSet {
+    Restore,
+    Record,
+    // Rendered positions for this frame, in `PostUpdate`
+    Apply,
+}
+
+// Smooths out entities simulated in `FixedUpdate` when the display refresh rate
+// doesn't match the simulation rate. The real simulated position is put back
+// before every tick, everything else sees the interpolated one.
+#[derive(Component, Debug)]
+pub struct TransformInterpolation {
+    previous: Vec3,
+    current: Vec3,
+    rendered: Option<Vec3>,
+}
+
+impl TransformInterpolation {
+    pub fn new(translation: Vec3) -> TransformInterpolation {
+        TransformInterpolation {
+            previous: translation,
+            current: translation,
+            rendered: None,
+        }
+    }
+
+    // For moves made during a tick that shouldn't be smeared over the frames
+    // after it
+    pub fn teleport(&mut self, translation: Vec3) {
+        self.previous = translation;
+        self.current = translation;
+    }
+
+    // Anything other than the interpolation that moved the entity (respawns,
+    // spawn points) wins and resets the history, so it doesn't get smeared.
+    fn was_teleported(&mut self, translation: Vec3) -> bool {
+        match self.rendered {
+            Some(rendered) if rendered != translation => {
+                self.previous = translation;
+                self.current = translation;
+                self.rendered = None;
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+fn restore_simulated_transforms(
+    mut q_interpolated: Query<(&mut Transform, &mut TransformInterpolation)>,
+) {
+    for (mut transform, mut interpolation) in q_interpolated.iter_mut() {
+        if interpolation.was_teleported(transform.translation) {
+            continue;
+        }
+
+        if interpolation.rendered.take().is_some() {
+            transform.translation = interpolation.current;
+        }
+    }
+}
+
+fn record_simulated_transforms(
+    mut q_interpolated: Query<(&Transform, &mut TransformInterpolation)>,
+) {
+    for (transform, mut interpolation) in q_interpolated.iter_mut() {
+        interpolation.previous = interpolation.current;
+        interpolation.current = transform.translation;
+    }
+}
+
+fn interpolate_transforms(
+    mut q_interpolated: Query<(&mut Transform, &mut TransformInterpolation)>,
+    fixed_time: Res<FixedTime>,
+) {
+    let alpha = (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).clamp(0.0, 1.0);
+
+    for (mut transform, mut interpolation) in q_interpolated.iter_mut() {
+        if interpolation.was_teleported(transform.translation) {
+            continue;
+        }
+
+        let translation = interpolation.previous.lerp(interpolation.current, alpha);
+        transform.translation = translation;
+        interpolation.rendered = Some(translation);
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct PhysicsPlugin {}
 
@@ -10,6 +102,17 @@ impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
         app
         // .add_plugins(RapierDebugRenderPlugin::default())
-        .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(PPM));
+        .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(PPM).in_fixed_schedule())
+        .insert_resource(FixedTime::new(Duration::from_secs_f32(FIXED_TIMESTEP)))
+        .insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: FIXED_TIMESTEP,
+                substeps: 1,
+            },
+            ..RapierConfiguration::default()
+        })
+        .add_systems(FixedUpdate, restore_simulated_transforms.in_set(InterpolationSet::Restore).before(PhysicsSet::SyncBackend))
+        .add_systems(FixedUpdate, record_simulated_transforms.in_set(InterpolationSet::Record).after(PhysicsSet::Writeback))
+        .add_systems(PostUpdate, interpolate_transforms.in_set(InterpolationSet::Apply).before(TransformSystem::TransformPropagate));
     }
 }
@@ -0,0 +1,403 @@
+use std::{fmt, fs, io, path::Path};
+
+use bevy::prelude::{Plugin, App, Resource, Res, ResMut, FixedUpdate, IntoSystemConfigs, Event, EventWriter, Vec2};
+
+use crate::input::{InputAction, TickInput, TickInputSet, InputAxes, update_tick_input};
+
+const REPLAY_MAGIC: &[u8; 4] = b"KTRP";
+pub const REPLAY_VERSION: u16 = 3;
+
+// Header: magic, version, level, level checksum, run count
+const HEADER_LEN: usize = 4 + 2 + 4 + 8 + 4;
+// Versions before 3 had a seed after the level that nothing used
+const LEGACY_SEED_LEN: usize = 8;
+// Run: action bits, stretch, tick count
+const RUN_LEN: usize = 2 + 1 + 2;
+// Frame: x, y, horizontal velocity, stretch, flags
+const FRAME_LEN: usize = 4 + 4 + 4 + 4 + 1;
+
+const FRAME_GRABBED_CEILING: u8 = 1;
+const FRAME_RESPAWNING: u8 = 1 << 1;
+
+impl InputAction {
+    pub fn bit(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+// Everything the simulation needs to know about one fixed tick of input.
+// The stretch trigger is quantized to a byte to keep files small.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplayTick {
+    pub actions: u16,
+    pub stretch: u8,
+}
+
+impl ReplayTick {
+    // Digital input only, stretching goes at full speed
+    pub fn from_actions(actions: &[InputAction]) -> ReplayTick {
+        let actions = actions.iter().fold(0, |bits, action| bits | action.bit());
+
+        ReplayTick {
+            actions,
+            stretch: if actions & InputAction::Stretch.bit() != 0 { u8::MAX } else { 0 },
+        }
+    }
+
+    pub fn from_input(tick_input: &TickInput) -> ReplayTick {
+        let actions = InputAction::ALL
+            .iter()
+            .filter(|action| tick_input.actions.pressed(**action))
+            .fold(0, |bits, action| bits | action.bit());
+
+        ReplayTick {
+            actions,
+            stretch: (tick_input.axes.stretch.clamp(0.0, 1.0) * 255.0).round() as u8,
+        }
+    }
+
+    pub fn apply_to(&self, tick_input: &mut TickInput) {
+        tick_input.actions.clear();
+
+        for action in InputAction::ALL {
+            if self.actions & action.bit() != 0 {
+                tick_input.actions.press(action);
+            } else {
+                tick_input.actions.release(action);
+            }
+        }
+
+        tick_input.axes = InputAxes {
+            stretch: self.stretch as f32 / 255.0,
+        };
+    }
+}
+
+// Where the player ended up after a tick. Not needed to replay the input, but
+// lets a ghost be drawn without simulating it. Added in version 2.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReplayFrame {
+    pub position: Vec2,
+    pub velocity_x: f32,
+    pub stretch: f32,
+    pub grabbed_ceiling: bool,
+    pub respawning: bool,
+}
+
+impl ReplayFrame {
+    fn write(&self, bytes: &mut Vec<u8>) {
+        let mut flags = 0;
+
+        if self.grabbed_ceiling {
+            flags |= FRAME_GRABBED_CEILING;
+        }
+
+        if self.respawning {
+            flags |= FRAME_RESPAWNING;
+        }
+
+        bytes.extend_from_slice(&self.position.x.to_le_bytes());
+        bytes.extend_from_slice(&self.position.y.to_le_bytes());
+        bytes.extend_from_slice(&self.velocity_x.to_le_bytes());
+        bytes.extend_from_slice(&self.stretch.to_le_bytes());
+        bytes.push(flags);
+    }
+
+    fn read(bytes: &[u8]) -> ReplayFrame {
+        let float = |offset: usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let flags = bytes[16];
+
+        ReplayFrame {
+            position: Vec2::new(float(0), float(4)),
+            velocity_x: float(8),
+            stretch: float(12),
+            grabbed_ceiling: flags & FRAME_GRABBED_CEILING != 0,
+            respawning: flags & FRAME_RESPAWNING != 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    InvalidHeader,
+    UnsupportedVersion(u16),
+    Truncated,
+    LevelMismatch { expected: u64, found: u64 },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(error) => write!(f, "could not access replay file: {}", error),
+            ReplayError::InvalidHeader => write!(f, "not a replay file"),
+            ReplayError::UnsupportedVersion(version) => write!(f, "unsupported replay version {}", version),
+            ReplayError::Truncated => write!(f, "replay file is truncated"),
+            ReplayError::LevelMismatch { expected, found } => {
+                write!(f, "replay was recorded on a different level (checksum {:016x}, level is {:016x})", expected, found)
+            },
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(error: io::Error) -> Self {
+        ReplayError::Io(error)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Replay {
+    pub level: u32,
+    pub level_checksum: u64,
+    pub ticks: Vec<ReplayTick>,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn new(level: u32, level_checksum: u64) -> Replay {
+        Replay {
+            level,
+            level_checksum,
+            ticks: vec![],
+            frames: vec![],
+        }
+    }
+
+    pub fn check_level(&self, level_checksum: u64) -> Result<(), ReplayError> {
+        if self.level_checksum != level_checksum {
+            return Err(ReplayError::LevelMismatch { expected: self.level_checksum, found: level_checksum });
+        }
+
+        Ok(())
+    }
+
+    // Input rarely changes from one tick to the next, so ticks are stored as
+    // runs of identical input.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut runs: Vec<(ReplayTick, u16)> = vec![];
+
+        for tick in self.ticks.iter() {
+            match runs.last_mut() {
+                Some((last, count)) if last == tick && *count < u16::MAX => *count += 1,
+                _ => runs.push((*tick, 1)),
+            }
+        }
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + runs.len() * RUN_LEN + 4 + self.frames.len() * FRAME_LEN);
+
+        bytes.extend_from_slice(REPLAY_MAGIC);
+        bytes.extend_from_slice(&REPLAY_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.level.to_le_bytes());
+        bytes.extend_from_slice(&self.level_checksum.to_le_bytes());
+        bytes.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+
+        for (tick, count) in runs {
+            bytes.extend_from_slice(&tick.actions.to_le_bytes());
+            bytes.push(tick.stretch);
+            bytes.extend_from_slice(&count.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+
+        for frame in self.frames.iter() {
+            frame.write(&mut bytes);
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Replay, ReplayError> {
+        if bytes.len() < HEADER_LEN || &bytes[0..4] != REPLAY_MAGIC {
+            return Err(ReplayError::InvalidHeader);
+        }
+
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version == 0 || version > REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+
+        let seed_len = if version < 3 { LEGACY_SEED_LEN } else { 0 };
+        if bytes.len() < HEADER_LEN + seed_len {
+            return Err(ReplayError::InvalidHeader);
+        }
+
+        let level = u32::from_le_bytes(bytes[6..10].try_into().unwrap());
+        let header = &bytes[10 + seed_len..];
+        let level_checksum = u64::from_le_bytes(header[0..8].try_into().unwrap());
+        let run_count = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+
+        let runs = &header[12..];
+        if runs.len() < run_count * RUN_LEN {
+            return Err(ReplayError::Truncated);
+        }
+
+        let mut replay = Replay::new(level, level_checksum);
+
+        for run in runs.chunks_exact(RUN_LEN).take(run_count) {
+            let tick = ReplayTick {
+                actions: u16::from_le_bytes([run[0], run[1]]),
+                stretch: run[2],
+            };
+            let count = u16::from_le_bytes([run[3], run[4]]);
+
+            replay.ticks.extend(std::iter::repeat(tick).take(count as usize));
+        }
+
+        // Version 1 files only have input, they play back fine without frames
+        if version < 2 {
+            return Ok(replay);
+        }
+
+        let frames = &runs[run_count * RUN_LEN..];
+        if frames.len() < 4 {
+            return Err(ReplayError::Truncated);
+        }
+
+        let frame_count = u32::from_le_bytes(frames[0..4].try_into().unwrap()) as usize;
+        let frames = &frames[4..];
+        if frames.len() < frame_count * FRAME_LEN {
+            return Err(ReplayError::Truncated);
+        }
+
+        replay.frames = frames
+            .chunks_exact(FRAME_LEN)
+            .take(frame_count)
+            .map(ReplayFrame::read)
+            .collect();
+
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, self.to_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, ReplayError> {
+        Replay::from_bytes(&fs::read(path)?)
+    }
+}
+
+// FNV-1a, used to fingerprint level data. It has to give the same result on
+// every build, which rules out std's randomly seeded hashers.
+#[derive(Debug, Clone, Copy)]
+pub struct Checksum(u64);
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Checksum(0xcbf29ce484222325)
+    }
+}
+
+impl Checksum {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub fn write_i32(&mut self, value: i32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_str(&mut self, value: &str) {
+        self.write(value.as_bytes());
+        self.write(&[0]);
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub enum ReplayState {
+    #[default]
+    Idle,
+    Recording(Replay),
+    Playing {
+        replay: Replay,
+        cursor: usize,
+    },
+}
+
+impl ReplayState {
+    pub fn is_playing(&self) -> bool {
+        matches!(self, ReplayState::Playing { .. })
+    }
+
+    // Stops recording and hands over what was recorded so far
+    pub fn take_recording(&mut self) -> Option<Replay> {
+        match std::mem::take(self) {
+            ReplayState::Recording(replay) => Some(replay),
+            other => {
+                *self = other;
+                None
+            },
+        }
+    }
+}
+
+#[derive(Event, Debug)]
+pub struct ReplayFinished();
+
+pub fn not_playing_replay(replay_state: Option<Res<ReplayState>>) -> bool {
+    !replay_state.map_or(false, |replay_state| replay_state.is_playing())
+}
+
+fn play_replay_tick(
+    mut replay_state: ResMut<ReplayState>,
+    mut tick_input: ResMut<TickInput>,
+    mut ev_replay_finished: EventWriter<ReplayFinished>,
+) {
+    let finished = match replay_state.as_mut() {
+        ReplayState::Playing { replay, cursor } => {
+            match replay.ticks.get(*cursor) {
+                Some(tick) => {
+                    tick.apply_to(&mut tick_input);
+                    *cursor += 1;
+                    false
+                },
+                None => true,
+            }
+        },
+        _ => return,
+    };
+
+    if finished {
+        ReplayTick::default().apply_to(&mut tick_input);
+        *replay_state = ReplayState::Idle;
+        ev_replay_finished.send(ReplayFinished());
+    }
+}
+
+fn record_replay_tick(
+    mut replay_state: ResMut<ReplayState>,
+    tick_input: Res<TickInput>,
+) {
+    if let ReplayState::Recording(replay) = replay_state.as_mut() {
+        replay.ticks.push(ReplayTick::from_input(&tick_input));
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ReplayPlugin {}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ReplayState>()
+            .add_event::<ReplayFinished>()
+            .add_systems(FixedUpdate, play_replay_tick.in_set(TickInputSet).after(update_tick_input))
+            .add_systems(FixedUpdate, record_replay_tick.after(TickInputSet));
+    }
+}
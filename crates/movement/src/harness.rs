@@ -0,0 +1,276 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::{App, Entity, Component, Transform, SpatialBundle, FixedUpdate, PostUpdate, MinimalPlugins, AssetPlugin, AddAsset, Mesh, TransformPlugin, HierarchyPlugin, Vec2, IVec2};
+use bevy_rapier2d::prelude::{RigidBody, Collider, CollisionGroups, Group};
+use kt_common::{bundles::player::PlayerBundle, components::{player::Player, velocity::Velocity, ldtk::SlopeKind, one_way_platform::{OneWayPlatform, ONE_WAY_PLATFORM_GROUP}}, tile_colliders::{Point, wall_colliders, one_way_platform_colliders, slope_colliders}};
+use kt_core::{audio::PlaySfx, input::{InputAction, TickInput}, physics::PhysicsPlugin, replay::ReplayTick};
+use kt_util::constants::TILE_SIZE;
+
+use crate::MovementPlugin;
+
+// Runs the movement systems without a window or renderer, one fixed tick at a
+// time, so physics changes can be checked against hand made levels.
+//
+// Levels are given as rows of text, top row first: `#` is a wall tile, `=` a
+// one-way platform, `/` and `\` are 45° slopes, `P` is where the player
+// starts and anything else is empty space.
+pub struct MovementHarness {
+    pub app: App,
+    pub player: Entity,
+}
+
+impl MovementHarness {
+    pub fn new(level: &[&str]) -> MovementHarness {
+        let mut app = App::new();
+
+        app
+            .add_plugins((MinimalPlugins, AssetPlugin::default(), TransformPlugin, HierarchyPlugin))
+            .add_asset::<Mesh>()
+            .add_plugins(PhysicsPlugin {})
+            .add_plugins(MovementPlugin {})
+            .init_resource::<TickInput>()
+            .add_event::<PlaySfx>();
+
+        let mut walls: HashSet<Point> = HashSet::new();
+        let mut one_way_platforms: HashSet<Point> = HashSet::new();
+        let mut slopes: HashMap<Point, SlopeKind> = HashMap::new();
+        let mut player_start = None;
+
+        for (row, line) in level.iter().enumerate() {
+            let y = (level.len() - 1 - row) as i32;
+
+            for (x, tile) in line.chars().enumerate() {
+                let point = Point { x: x as i32, y };
+
+                match tile {
+                    '#' => { walls.insert(point); },
+                    '=' => { one_way_platforms.insert(point); },
+                    '/' => { slopes.insert(point, SlopeKind::SteepRight); },
+                    '\\' => { slopes.insert(point, SlopeKind::SteepLeft); },
+                    'P' => player_start = Some(point),
+                    _ => {},
+                }
+            }
+        }
+
+        // Same merging as the game, so the character controller meets the
+        // same seams it would in a real level
+        for (translation, collider) in wall_colliders(&walls).into_iter().chain(slope_colliders(&slopes)) {
+            app.world.spawn((
+                SpatialBundle::from_transform(Transform::from_xyz(translation.x, translation.y, 0.0)),
+                RigidBody::Fixed,
+                collider,
+            ));
+        }
+
+        for (translation, collider) in one_way_platform_colliders(&one_way_platforms) {
+            app.world.spawn((
+                SpatialBundle::from_transform(Transform::from_xyz(translation.x, translation.y, 0.0)),
+                RigidBody::Fixed,
+                collider,
+                CollisionGroups::new(ONE_WAY_PLATFORM_GROUP, Group::ALL),
+                OneWayPlatform {},
+            ));
+        }
+
+        let start = tile_center(player_start.expect("level has no player start"));
+
+        let player = app.world.spawn((
+            SpatialBundle::from_transform(Transform::from_xyz(start.x, start.y, 0.0)),
+            PlayerBundle::default(),
+        )).id();
+
+        app.finish();
+        app.cleanup();
+
+        // The first update doesn't advance time, it only runs startup systems
+        // and propagates transforms so colliders are created in place.
+        app.update();
+
+        MovementHarness {
+            app,
+            player,
+        }
+    }
+
+    pub fn step(&mut self, tick: ReplayTick) {
+        tick.apply_to(&mut self.app.world.resource_mut::<TickInput>());
+
+        self.app.world.run_schedule(FixedUpdate);
+        self.app.world.run_schedule(PostUpdate);
+    }
+
+    // Holds the given actions for a number of ticks
+    pub fn hold(&mut self, actions: &[InputAction], ticks: usize) {
+        let tick = ReplayTick::from_actions(actions);
+
+        for _ in 0..ticks {
+            self.step(tick);
+        }
+    }
+
+    pub fn idle(&mut self, ticks: usize) {
+        self.hold(&[], ticks);
+    }
+
+    pub fn get<T: Component>(&self) -> &T {
+        self.app.world
+            .get::<T>(self.player)
+            .expect("player is missing component")
+    }
+
+    pub fn transform(&self) -> &Transform {
+        self.get::<Transform>()
+    }
+
+    pub fn velocity(&self) -> &Velocity {
+        self.get::<Velocity>()
+    }
+
+    pub fn player(&self) -> &Player {
+        self.get::<Player>()
+    }
+
+    // Tile the center of the player is in, same coordinates as the level rows
+    pub fn player_tile(&self) -> IVec2 {
+        let point = Point::from_translation(self.transform().translation);
+
+        IVec2::new(point.x, point.y)
+    }
+}
+
+fn tile_center(point: Point) -> Vec2 {
+    Vec2::new(
+        point.x as f32 * TILE_SIZE + TILE_SIZE / 2.0,
+        point.y as f32 * TILE_SIZE + TILE_SIZE / 2.0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::IVec2;
+    use kt_core::input::InputAction;
+    use kt_util::constants::TILE_SIZE;
+
+    use super::MovementHarness;
+
+    // Lets the player land on the floor it starts above
+    fn settle(harness: &mut MovementHarness) {
+        harness.idle(30);
+    }
+
+    #[test]
+    fn max_jump_clears_one_tile() {
+        let mut harness = MovementHarness::new(&[
+            "     ",
+            "     ",
+            "     ",
+            " P## ",
+            "#####",
+        ]);
+        settle(&mut harness);
+
+        harness.hold(&[InputAction::Jump, InputAction::MoveRight], 60);
+        harness.idle(30);
+
+        assert_eq!(harness.player_tile().y, 2);
+        assert!(harness.player_tile().x >= 2);
+    }
+
+    #[test]
+    fn max_jump_does_not_clear_two_tiles() {
+        let mut harness = MovementHarness::new(&[
+            "     ",
+            "     ",
+            "  ## ",
+            " P## ",
+            "#####",
+        ]);
+        settle(&mut harness);
+
+        harness.hold(&[InputAction::Jump, InputAction::MoveRight], 60);
+        harness.idle(30);
+
+        assert_eq!(harness.player_tile(), IVec2::new(1, 1));
+    }
+
+    #[test]
+    fn stretching_into_ceiling_grabs_it() {
+        let mut harness = MovementHarness::new(&[
+            "####",
+            "    ",
+            " P  ",
+            "####",
+        ]);
+        settle(&mut harness);
+        assert!(!harness.player().grabbed_ceiling);
+
+        harness.hold(&[InputAction::Stretch], 20);
+
+        assert!(harness.player().grabbed_ceiling);
+    }
+
+    #[test]
+    fn walking_into_wall_stops_horizontal_velocity() {
+        let mut harness = MovementHarness::new(&[
+            "P #",
+            "###",
+        ]);
+        settle(&mut harness);
+
+        harness.hold(&[InputAction::MoveRight], 30);
+
+        assert_eq!(harness.velocity().current.x, 0.0);
+        assert!(harness.transform().translation.x < 2.0 * TILE_SIZE);
+    }
+
+    #[test]
+    fn holding_down_drops_through_one_way_platform() {
+        let mut harness = MovementHarness::new(&[
+            "    ",
+            " P  ",
+            " =  ",
+            "    ",
+            "####",
+        ]);
+        settle(&mut harness);
+        assert_eq!(harness.player_tile().y, 3);
+
+        harness.hold(&[InputAction::MoveDown], 40);
+
+        assert_eq!(harness.player_tile().y, 1);
+    }
+
+    #[test]
+    fn hanging_from_ceiling_passes_one_way_platform() {
+        let mut harness = MovementHarness::new(&[
+            "########",
+            "   =    ",
+            " P      ",
+            "########",
+        ]);
+        settle(&mut harness);
+
+        harness.hold(&[InputAction::Stretch], 20);
+        harness.hold(&[InputAction::MoveRight], 50);
+
+        assert!(harness.player().grabbed_ceiling);
+        assert!(harness.player_tile().x >= 4);
+    }
+
+    #[test]
+    fn walks_up_45_degree_ramp() {
+        let mut harness = MovementHarness::new(&[
+            "        ",
+            "        ",
+            "P  /####",
+            "########",
+        ]);
+        settle(&mut harness);
+
+        harness.hold(&[InputAction::MoveRight], 120);
+
+        assert_eq!(harness.player_tile().y, 2);
+        assert!(harness.player_tile().x >= 5);
+    }
+}
@@ -1,12 +1,51 @@
-use bevy::prelude::{Query, Transform, Res, Input, KeyCode, Vec2, default};
+use bevy::prelude::{Query, Transform, Res, Vec2, default};
 use bevy_rapier2d::prelude::{RapierContext, Collider, QueryFilter, QueryFilterFlags};
-use kt_common::components::{velocity::Velocity, player::Player};
-use kt_util::constants::PLAYER_HORIZONTAL_MOVE_SPEED;
+use kt_common::components::{velocity::Velocity, player::Player, one_way_platform::solid_groups};
+use kt_core::input::{InputAction, TickInput};
+use kt_util::constants::{PLAYER_HORIZONTAL_MOVE_SPEED, PLAYER_MAX_SLOPE_ANGLE, PLAYER_HALF_WIDTH, PLAYER_HALF_HEIGHT};
+
+// The wall probe starts this far above the feet, so the slope the player is
+// standing on doesn't count as something in the way
+const WALL_PROBE_LIFT: f32 = 1.0;
+
+// Whether a wall is right next to the player in `direction`. Slopes that can
+// be walked up aren't walls.
+fn is_blocked(rapier_context: &RapierContext, player: &Player, transform: &Transform, direction: f32) -> bool {
+    let shape = Collider::cuboid(PLAYER_HALF_WIDTH, PLAYER_HALF_HEIGHT + player.stretch / 2.0);
+    let shape_pos = transform.translation.truncate() + Vec2::new(0.2 * direction, player.stretch / 2.0 + WALL_PROBE_LIFT);
+    let shape_vel = Vec2::new(direction, 0.0);
+    let shape_rot = 0.0;
+    let max_toi = 1.0;
+
+    let filter = QueryFilter {
+        flags: QueryFilterFlags::ONLY_FIXED | QueryFilterFlags::EXCLUDE_SENSORS, 
+        groups: Some(solid_groups()),
+        ..default()
+    };
+
+    let hit = match rapier_context.cast_shape(
+        shape_pos, shape_rot, shape_vel, &shape, max_toi, filter
+    ) {
+        Some((_entity, hit)) => hit,
+        None => return false,
+    };
+
+    // Already touching, there's no normal to go by
+    let details = match hit.details {
+        Some(details) if hit.toi > 0.0 => details,
+        _ => return true,
+    };
+
+    // Outward normal of whatever was hit, it faces the probe
+    let normal = if details.normal1.dot(shape_vel) < 0.0 { details.normal1 } else { -details.normal1 };
+
+    normal.y < PLAYER_MAX_SLOPE_ANGLE.to_radians().cos()
+}
 
 pub fn horizontal_controls (
     mut q_player: Query<(&mut Velocity, &Player, &Transform)>,
     rapier_context: Res<RapierContext>,
-    keyboard_input: Res<Input<KeyCode>>,
+    input: Res<TickInput>,
 ) {
     for (mut velocity, player, transform) in q_player.iter_mut() {
         if player.grabbed_ceiling {
@@ -17,41 +56,15 @@ pub fn horizontal_controls (
             continue;
         }
 
-        if keyboard_input.pressed(KeyCode::Left) {
-            let shape = Collider::cuboid(6.0, 9.0 + player.stretch / 2.0);
-            let shape_pos = transform.translation.truncate() + Vec2::new(-0.2, player.stretch / 2.0 + 0.1);
-            let shape_vel = Vec2::new(-1.0, 0.0);
-            let shape_rot = 0.0;
-            let max_toi = 1.0;
-
-            let filter = QueryFilter {
-                flags: QueryFilterFlags::ONLY_FIXED | QueryFilterFlags::EXCLUDE_SENSORS, 
-                ..default()
-            };
-
-            if let Some(_entity) = rapier_context.cast_shape(
-                shape_pos, shape_rot, shape_vel, &shape, max_toi, filter
-            ) {
+        if input.actions.pressed(InputAction::MoveLeft) {
+            if is_blocked(&rapier_context, player, transform, -1.0) {
                 velocity.current.x = 0.0;
                 continue
             }
 
             velocity.current.x = -PLAYER_HORIZONTAL_MOVE_SPEED;
-        } else if keyboard_input.pressed(KeyCode::Right) {
-            let shape = Collider::cuboid(6.0, 9.0 + player.stretch / 2.0);
-            let shape_pos = transform.translation.truncate() + Vec2::new(0.2, player.stretch / 2.0 + 0.1);
-            let shape_vel = Vec2::new(1.0, 0.0);
-            let shape_rot = 0.0;
-            let max_toi = 1.0;
-
-            let filter = QueryFilter {
-                flags: QueryFilterFlags::ONLY_FIXED | QueryFilterFlags::EXCLUDE_SENSORS, 
-                ..default()
-            };
-
-            if let Some(_entity) = rapier_context.cast_shape(
-                shape_pos, shape_rot, shape_vel, &shape, max_toi, filter
-            ) {
+        } else if input.actions.pressed(InputAction::MoveRight) {
+            if is_blocked(&rapier_context, player, transform, 1.0) {
                 velocity.current.x = 0.0;
                 continue
             }
@@ -65,7 +78,7 @@ pub fn horizontal_controls (
 pub fn horizontal_controls_on_ceiling (
     mut q_player: Query<(&mut Velocity, &mut Player, &Transform)>,
     rapier_context: Res<RapierContext>,
-    keyboard_input: Res<Input<KeyCode>>,
+    input: Res<TickInput>,
 ) {
     let player = q_player.get_single_mut();
     let (mut velocity, mut player, transform) = match player {
@@ -79,24 +92,25 @@ pub fn horizontal_controls_on_ceiling (
 
     velocity.current.x = 0.0;
 
-    let shape = Collider::cuboid(6.0, 9.0);
+    let shape = Collider::cuboid(PLAYER_HALF_WIDTH, PLAYER_HALF_HEIGHT);
     let shape_pos = transform.translation.truncate();
     let shape_vel = Vec2::new(0.0, player.stretch + 2.0);
     let shape_rot = 0.0;
     let max_toi = 1.0;
     let filter = QueryFilter {
         flags: QueryFilterFlags::ONLY_FIXED | QueryFilterFlags::EXCLUDE_SENSORS, 
+        groups: Some(solid_groups()),
         ..default()
     };
 
     if let Some(_entity) = rapier_context.cast_shape(
         shape_pos, shape_rot, shape_vel, &shape, max_toi, filter
     ) {
-        if keyboard_input.pressed(KeyCode::Left) {
+        if input.actions.pressed(InputAction::MoveLeft) {
             velocity.current.x = -100.0;
         } 
 
-        if keyboard_input.pressed(KeyCode::Right) {
+        if input.actions.pressed(InputAction::MoveRight) {
             velocity.current.x = 100.0;
         } 
     } else {
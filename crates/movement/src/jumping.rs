@@ -1,23 +1,22 @@
-use bevy::{prelude::{Query, Res, Input, KeyCode, Transform, Vec2, default, With, AudioBundle, PlaybackSettings, Commands, AssetServer}, time::{Time, Timer}, audio::{PlaybackMode, VolumeLevel}};
+use bevy::{prelude::{Query, Res, Transform, Vec2, default, With, EventWriter, FixedTime}, time::Timer};
 use bevy_rapier2d::prelude::{KinematicCharacterControllerOutput, RapierContext, Collider, QueryFilter, QueryFilterFlags, KinematicCharacterController};
-use kt_common::components::{velocity::Velocity, jump::Jump, player::Player};
-use kt_util::constants::{PLAYER_JUMP_SPEED, JUMP_HOLD_FORCE, JUMP_HOLD_TIMER};
+use kt_common::components::{velocity::Velocity, jump::Jump, player::Player, one_way_platform::solid_groups};
+use kt_core::{audio::{PlaySfx, Sfx}, input::{InputAction, TickInput}};
+use kt_util::constants::{PLAYER_JUMP_SPEED, JUMP_HOLD_FORCE, JUMP_HOLD_TIMER, PLAYER_HALF_WIDTH, PLAYER_HALF_HEIGHT};
 
 pub fn jumping_controls (
     mut q_player: Query<(&mut Velocity, &mut Jump, &Player)>,
-    keyboard_input: Res<Input<KeyCode>>,
-    time: Res<Time>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-
+    input: Res<TickInput>,
+    fixed_time: Res<FixedTime>,
+    mut ev_play_sfx: EventWriter<PlaySfx>,
 ) {
     for (mut velocity, mut jump, player) in q_player.iter_mut() {
-        if keyboard_input.pressed(KeyCode::Space) && jump.is_jumping && !jump.jump_timer.finished() {
-            jump.jump_timer.tick(time.delta());
-            velocity.current.y += JUMP_HOLD_FORCE * jump.jump_timer.percent_left();
+        if input.actions.pressed(InputAction::Jump) && jump.is_jumping && !jump.jump_timer.finished() {
+            jump.jump_timer.tick(fixed_time.period);
+            velocity.current.y += JUMP_HOLD_FORCE * jump.jump_timer.percent_left() * fixed_time.period.as_secs_f32();
         }
 
-        if keyboard_input.just_pressed(KeyCode::Space) {
+        if input.actions.just_pressed(InputAction::Jump) {
             if !jump.can_jump {
                 continue;
             }
@@ -26,14 +25,7 @@ pub fn jumping_controls (
                 continue;
             }
 
-            commands.spawn(AudioBundle {
-                source: asset_server.load("audio/SFX_Jump_11.ogg"),
-                settings: PlaybackSettings {
-                    volume: bevy::audio::Volume::Relative(VolumeLevel::new(0.2)),
-                    mode: PlaybackMode::Remove,
-                    ..default()
-                },
-            });
+            ev_play_sfx.send(PlaySfx(Sfx::Jump));
 
             velocity.current.y = PLAYER_JUMP_SPEED;
             jump.is_jumping = true;
@@ -41,7 +33,7 @@ pub fn jumping_controls (
         }
 
 
-        if keyboard_input.just_released(KeyCode::Space) || jump.jump_timer.finished() {
+        if input.actions.just_released(InputAction::Jump) || jump.jump_timer.finished() {
             jump.is_jumping = false;
         }
     }
@@ -64,13 +56,14 @@ pub fn bounce_off_ceiling(
             continue;
         }
 
-        let shape = Collider::cuboid(6.0, 9.0);
+        let shape = Collider::cuboid(PLAYER_HALF_WIDTH, PLAYER_HALF_HEIGHT);
         let shape_pos = transform.translation.truncate();
         let shape_vel = Vec2::new(0.0, 2.0);
         let shape_rot = 0.0;
         let max_toi = 1.0;
         let filter = QueryFilter {
             flags: QueryFilterFlags::ONLY_FIXED | QueryFilterFlags::EXCLUDE_SENSORS, 
+            groups: Some(solid_groups()),
             ..default()
         };
 
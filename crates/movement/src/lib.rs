@@ -1,7 +1,9 @@
-use bevy::prelude::{Plugin, App, Update, IntoSystemConfigs};
+use bevy::prelude::{Plugin, App, FixedUpdate, IntoSystemConfigs, SystemSet};
+use bevy_rapier2d::prelude::PhysicsSet;
+use kt_core::{input::TickInputSet, physics::InterpolationSet};
 use horizontal::{horizontal_controls, horizontal_controls_on_ceiling};
 use jumping::{jumping_controls, update_can_jump_flag, bounce_off_ceiling};
-use physics::{apply_velocity_to_kinematic_controller, clear_velocity_if_kinematic_on_ground, hit_ground, activate_platforms, handle_platform_dropping, handle_platform_off_screen, sync_emitter_position};
+use physics::{apply_velocity_to_kinematic_controller, clear_velocity_if_kinematic_on_ground, hit_ground, activate_platforms, handle_platform_dropping, handle_platform_off_screen, sync_emitter_position, drop_through_platforms, update_one_way_platforms, move_movers, carry_riders};
 use stretching::{stretching_controls, grab_ceiling, ungrab_ceiling};
 
 mod jumping;
@@ -9,12 +11,18 @@ mod horizontal;
 mod stretching;
 mod physics;
 
+#[cfg(test)]
+mod harness;
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MovementSet;
+
 #[derive(Debug, Default)]
 pub struct MovementPlugin {}
 
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (
+        app.add_systems(FixedUpdate, (
             horizontal_controls,
             horizontal_controls_on_ceiling,
             jumping_controls,
@@ -23,13 +31,22 @@ impl Plugin for MovementPlugin {
             stretching_controls,
             grab_ceiling,
             ungrab_ceiling,
+            drop_through_platforms,
+            update_one_way_platforms,
             apply_velocity_to_kinematic_controller,
+            move_movers,
+            carry_riders,
             clear_velocity_if_kinematic_on_ground,
             sync_emitter_position,
             hit_ground,
             activate_platforms,
             handle_platform_dropping,
             handle_platform_off_screen,
-        ).chain());
+        )
+            .chain()
+            .in_set(MovementSet)
+            .after(TickInputSet)
+            .after(InterpolationSet::Restore)
+            .before(PhysicsSet::SyncBackend));
     }
 }
@@ -1,21 +1,27 @@
 use std::time::Duration;
 
-use bevy::{prelude::{Query, Res, Vec2, With, Transform, Vec3, Without}, time::{Time, TimerMode, Timer}};
-use bevy_rapier2d::prelude::{KinematicCharacterController, KinematicCharacterControllerOutput};
+use bevy::{prelude::{Query, Res, Vec2, With, Transform, Vec3, Without, FixedTime}, time::{TimerMode, Timer}};
+use bevy_rapier2d::prelude::{KinematicCharacterController, KinematicCharacterControllerOutput, CollisionGroups, Group};
 use bevy_tweening::{EaseFunction, lens::TransformPositionLens, Tween};
-use kt_common::components::{velocity::Velocity, acceleration::Acceleration, gravity::GravityDir, jump::Jump, ground_detector::{GroundDetector}, dust_particle_emitter::DustParticleEmitter, platform::Platform, player::Player};
-use kt_core::particle::ParticleEmitter;
+use kt_common::components::{velocity::Velocity, acceleration::Acceleration, gravity::GravityDir, jump::Jump, ground_detector::{GroundDetector}, dust_particle_emitter::DustParticleEmitter, platform::Platform, mover::Mover, player::Player, one_way_platform::{OneWayPlatform, ONE_WAY_PLATFORM_GROUP}};
+use kt_core::{particle::ParticleEmitter, input::{InputAction, TickInput}};
+use kt_util::constants::{GRAVITY, PLATFORM_DROP_SPEED, ONE_WAY_PLATFORM_THICKNESS, PLAYER_DROP_THROUGH_TIME, PLAYER_HALF_HEIGHT};
+
+// Feet this far into a one-way platform still count as standing on it
+const ONE_WAY_PLATFORM_TOLERANCE: f32 = 1.0;
 
 pub fn apply_velocity_to_kinematic_controller(
     mut q_kinematic_controller: Query<(&mut KinematicCharacterController, &mut Velocity, &mut Acceleration, &GravityDir)>,
-    time: Res<Time>,
+    fixed_time: Res<FixedTime>,
 ) {
+    let delta = fixed_time.period.as_secs_f32();
+
     for (mut kcc, mut velocity, mut acceleration, gravity_dir) in q_kinematic_controller.iter_mut() {
         // Apply gravity
         if velocity.current.y < 0.0 {
-            velocity.current += Vec2::new(0.0, -14.0 * gravity_dir.dir * gravity_dir.slow_down);
+            velocity.current += Vec2::new(0.0, -GRAVITY * delta * gravity_dir.dir * gravity_dir.slow_down);
         } else {
-            velocity.current += Vec2::new(0.0, -14.0 * gravity_dir.dir);
+            velocity.current += Vec2::new(0.0, -GRAVITY * delta * gravity_dir.dir);
         }
 
         // Movement
@@ -28,14 +34,14 @@ pub fn apply_velocity_to_kinematic_controller(
         if kcc.translation.is_none() {
             kcc.translation = Some(
                 Vec2::new(
-                    velocity.current.x * time.delta_seconds(),
-                    velocity.current.y * time.delta_seconds(),
+                    velocity.current.x * delta,
+                    velocity.current.y * delta,
                 )
             );
         }
 
 
-        // Damp velocity
+        // Damp velocity, damping is per simulation tick
         velocity.current.x *= 1.0 - velocity.damping;
 
         if velocity.current.x.abs() < 0.1 {
@@ -47,6 +53,51 @@ pub fn apply_velocity_to_kinematic_controller(
     }
 }
 
+pub fn move_movers(
+    mut q_movers: Query<(&mut Mover, &mut Transform)>,
+    fixed_time: Res<FixedTime>,
+) {
+    for (mut mover, mut transform) in q_movers.iter_mut() {
+        mover.advance(fixed_time.period.as_secs_f32());
+
+        let position = mover.position();
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
+
+// The character controller doesn't follow kinematic bodies on its own, so
+// whoever stands on a mover gets its displacement added to their own
+pub fn carry_riders(
+    mut q_kinematic: Query<(&mut KinematicCharacterController, &KinematicCharacterControllerOutput, &Transform), Without<Mover>>,
+    mut q_movers: Query<(&mut Mover, &Transform)>,
+) {
+    for (mut kcc, kcco, transform) in q_kinematic.iter_mut() {
+        if !kcco.grounded {
+            continue;
+        }
+
+        for collision in kcco.collisions.iter() {
+            let (mut mover, mover_transform) = match q_movers.get_mut(collision.entity) {
+                Ok(mover) => mover,
+                Err(..) => continue,
+            };
+
+            // Bumping into its side doesn't count as riding it
+            if mover_transform.translation.y > transform.translation.y {
+                continue;
+            }
+
+            if mover.start_on_touch {
+                mover.is_active = true;
+            }
+
+            kcc.translation = Some(kcc.translation.unwrap_or(Vec2::ZERO) + mover.displacement);
+            break;
+        }
+    }
+}
+
 pub fn clear_velocity_if_kinematic_on_ground(
     mut q_kinematic: Query<(&KinematicCharacterControllerOutput, &Jump, &mut Velocity, &mut GroundDetector, &Player)>,
 ) {
@@ -100,20 +151,20 @@ pub fn activate_platforms(
 
 pub fn handle_platform_dropping(
     mut q_platforms: Query<(&mut Platform, &mut Transform)>,
-    time: Res<Time>,
+    fixed_time: Res<FixedTime>,
 ) {
     for (mut platform, mut transform) in q_platforms.iter_mut() {
-        platform.drop_timer.tick(time.delta());
+        platform.drop_timer.tick(fixed_time.period);
 
         if platform.drop_timer.finished() && platform.is_stepped_on {
-            transform.translation.y -= 3.0;
+            transform.translation.y -= PLATFORM_DROP_SPEED * fixed_time.period.as_secs_f32();
         }
     }
 }
 
 pub fn handle_platform_off_screen(
     mut q_platforms: Query<(&mut Platform, &Transform, &mut bevy_tweening::Animator<Transform>)>,
-    time: Res<Time>,
+    fixed_time: Res<FixedTime>,
 ) {
     for (mut platform, transform, mut animator) in q_platforms.iter_mut() {
         if transform.translation.y > -30.0 {
@@ -125,7 +176,7 @@ pub fn handle_platform_off_screen(
         }
 
         platform.is_stepped_on = false;
-        platform.restart_timer.tick(time.delta());
+        platform.restart_timer.tick(fixed_time.period);
 
         if platform.restart_timer.just_finished() {
             let tween = Tween::new(
@@ -142,6 +193,55 @@ pub fn handle_platform_off_screen(
     }
 }
 
+// Holding down while standing on a one-way platform falls through it
+pub fn drop_through_platforms(
+    mut q_player: Query<(&mut Player, &KinematicCharacterControllerOutput)>,
+    q_one_way_platforms: Query<(), With<OneWayPlatform>>,
+    input: Res<TickInput>,
+    fixed_time: Res<FixedTime>,
+) {
+    for (mut player, kcco) in q_player.iter_mut() {
+        player.drop_through_time = (player.drop_through_time - fixed_time.period.as_secs_f32()).max(0.0);
+
+        if !input.actions.pressed(InputAction::MoveDown) || !kcco.grounded || player.grabbed_ceiling || player.is_respawning {
+            continue;
+        }
+
+        let on_one_way_platform = kcco.collisions
+            .iter()
+            .any(|collision| q_one_way_platforms.contains(collision.entity));
+
+        if on_one_way_platform {
+            player.drop_through_time = PLAYER_DROP_THROUGH_TIME;
+        }
+    }
+}
+
+// One-way platforms are solid only for a player falling onto them from above,
+// everything else passes through
+pub fn update_one_way_platforms(
+    q_player: Query<(&Transform, &Velocity, &Player), Without<OneWayPlatform>>,
+    mut q_one_way_platforms: Query<(&Transform, &mut CollisionGroups), With<OneWayPlatform>>,
+) {
+    for (player_transform, velocity, player) in q_player.iter() {
+        // Bottom of the player's collider
+        let feet = player_transform.translation.y - PLAYER_HALF_HEIGHT;
+
+        for (transform, mut groups) in q_one_way_platforms.iter_mut() {
+            let top = transform.translation.y + ONE_WAY_PLATFORM_THICKNESS / 2.0;
+            let solid = player.drop_through_time <= 0.0
+                && velocity.current.y <= 0.0
+                && feet >= top - ONE_WAY_PLATFORM_TOLERANCE;
+
+            let memberships = if solid { ONE_WAY_PLATFORM_GROUP } else { Group::NONE };
+
+            if groups.memberships != memberships {
+                groups.memberships = memberships;
+            }
+        }
+    }
+}
+
 pub fn hit_ground(
     q_ground_detector: Query<&GroundDetector>,
     mut q_dust_emitter: Query<&mut ParticleEmitter, With<DustParticleEmitter>>
@@ -153,9 +253,8 @@ pub fn hit_ground(
                     continue;
                 }
 
-                emitter.spawning = true;
-                emitter.spawn_timer = Timer::from_seconds(0.01, TimerMode::Once);
-            } 
+                emitter.trigger();
+            }
         }
     }
 }
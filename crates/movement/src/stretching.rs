@@ -1,20 +1,24 @@
-use bevy::prelude::{Query, Transform, Res, Input, KeyCode, Vec2, default};
+use bevy::prelude::{Query, Transform, Res, Vec2, default, FixedTime};
 use bevy_rapier2d::prelude::{RapierContext, QueryFilter, QueryFilterFlags, Collider, KinematicCharacterController};
-use kt_common::components::{player::Player, jump::Jump, gravity::GravityDir, velocity::Velocity};
-use kt_util::constants::{PLAYER_MAXIMUM_STRETCH, PLAYER_STRETCH_SPEED};
+use kt_common::components::{player::Player, jump::Jump, gravity::GravityDir, velocity::Velocity, one_way_platform::solid_groups};
+use kt_core::input::{InputAction, TickInput};
+use kt_util::constants::{PLAYER_MAXIMUM_STRETCH, PLAYER_STRETCH_SPEED, PLAYER_HALF_HEIGHT};
 
 pub fn stretching_controls(
     mut q_player: Query<(&Transform, &mut Player, &mut Jump, &mut GravityDir)>,
     rapier_context: Res<RapierContext>,
-    keyboard_input: Res<Input<KeyCode>>,
+    input: Res<TickInput>,
+    fixed_time: Res<FixedTime>,
 ) {
+    let stretch_step = PLAYER_STRETCH_SPEED * fixed_time.period.as_secs_f32();
+
     for (transform, mut player, mut jump, mut gravity_dir) in q_player.iter_mut() {
         if player.grabbed_ceiling {
             continue;
         }
 
         if player.is_respawning {
-            player.stretch -= PLAYER_STRETCH_SPEED;
+            player.stretch -= stretch_step;
 
             if player.stretch < 0.0 {
                 player.stretch = 0.0;
@@ -24,13 +28,13 @@ pub fn stretching_controls(
         }
 
         gravity_dir.slow_down = 1.0;
-        if keyboard_input.pressed(KeyCode::X) {
+        if input.actions.pressed(InputAction::Stretch) {
             if player.stretch >= PLAYER_MAXIMUM_STRETCH {
                 player.stretch = PLAYER_MAXIMUM_STRETCH;
                 continue;
             }
 
-            let shape = Collider::cuboid(4.0, 9.0);
+            let shape = Collider::cuboid(4.0, PLAYER_HALF_HEIGHT);
             let mut shape_pos = transform.translation.truncate();
             shape_pos.y += player.stretch;
             let shape_vel = Vec2::new(
@@ -41,6 +45,7 @@ pub fn stretching_controls(
             let max_toi = 1.0;
             let filter = QueryFilter {
                 flags: QueryFilterFlags::ONLY_FIXED | QueryFilterFlags::EXCLUDE_SENSORS, 
+                groups: Some(solid_groups()),
                 ..default()
             };
 
@@ -54,12 +59,13 @@ pub fn stretching_controls(
                 continue;
             }
 
-            player.stretch += PLAYER_STRETCH_SPEED;
+            // Analog triggers grow the ruler slower when only partially pulled
+            player.stretch += stretch_step * input.axes.stretch;
             gravity_dir.slow_down = 0.2;
             continue;
         }
 
-        player.stretch -= PLAYER_STRETCH_SPEED;
+        player.stretch -= stretch_step;
 
         if player.stretch < 0.0 {
             player.stretch = 0.0;
@@ -69,9 +75,9 @@ pub fn stretching_controls(
 
 pub fn ungrab_ceiling(
     mut q_player: Query<&mut Player>,
-    keyboard_input: Res<Input<KeyCode>>,
+    input: Res<TickInput>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::Space) {
+    if input.actions.just_pressed(InputAction::LetGo) {
         for mut player in q_player.iter_mut() {
             player.grabbed_ceiling = false;
         }
@@ -80,19 +86,23 @@ pub fn ungrab_ceiling(
 
 pub fn grab_ceiling(
     mut q_player: Query<(&mut Player, &mut GravityDir, &mut Velocity, &mut KinematicCharacterController)>,
+    fixed_time: Res<FixedTime>,
 ) {
+    let stretch_step = PLAYER_STRETCH_SPEED * fixed_time.period.as_secs_f32();
+
     for (mut player, mut gravity_dir, mut velocity, mut kcc) in q_player.iter_mut() {
         if player.grabbed_ceiling {
             gravity_dir.dir = 0.0;
             if player.stretch > 0.0 {
+                // Pull the body up towards the ceiling while the ruler shrinks
                 kcc.translation = Some(
                     Vec2::new(
                         0.0,
-                        PLAYER_STRETCH_SPEED * 1.0,
+                        stretch_step,
                     )
                 );
 
-                player.stretch -= PLAYER_STRETCH_SPEED * 1.0;
+                player.stretch -= stretch_step;
                 velocity.current.y = 0.0;
             }
         } else {
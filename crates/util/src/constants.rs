@@ -5,18 +5,45 @@ pub const INITIAL_WINDOW_HEIGHT: f32 = 768.0;
 pub const ASPECT_RATIO_X: f32 = 512.0 / 1.5;
 pub const ASPECT_RATIO_Y: f32 = 364.0 / 1.5;
 
+// Movement is simulated at a fixed rate, speeds are in px/s and forces in px/s²
+pub const FIXED_TIMESTEP: f32 = 1.0 / 60.0;
+
+pub const GRAVITY: f32 = 840.0;
+
+// Half extents of the player's collider, unstretched
+pub const PLAYER_HALF_WIDTH: f32 = 6.0;
+pub const PLAYER_HALF_HEIGHT: f32 = 9.0;
+
 pub const PLAYER_MAXIMUM_STRETCH: f32 = 88.0;
-pub const PLAYER_STRETCH_SPEED: f32 = 5.0;
+pub const PLAYER_STRETCH_SPEED: f32 = 300.0;
 pub const PLAYER_HORIZONTAL_MOVE_SPEED: f32 = 400.0;
 
+// Steepest slope in degrees the player walks up instead of treating as a wall
+pub const PLAYER_MAX_SLOPE_ANGLE: f32 = 50.0;
+
 pub const PLAYER_JUMP_SPEED: f32 = 140.0;
-pub const JUMP_HOLD_FORCE: f32 = 20.0;
+pub const JUMP_HOLD_FORCE: f32 = 1200.0;
 pub const JUMP_HOLD_TIMER: f32 = 0.30;
 
-pub const PLAYER_HIT_RESPAWN_TIME: f32 = 1.0;
+pub const PLATFORM_DROP_SPEED: f32 = 180.0;
+// Elevators only go up and down between their start and `level` tiles away
+pub const ELEVATOR_SPEED: f32 = 18.0;
 
-pub const PLAYER_CAMERA_MARGIN_X: usize = 10; // 10% of the width from the center
-pub const PLAYER_CAMERA_MARGIN_Y: usize = 10; // 10% of the height from the center
+// One-way platforms are a thin strip along the top of their tile
+pub const ONE_WAY_PLATFORM_THICKNESS: f32 = 4.0;
+// How long pressing down lets the player fall through one-way platforms
+pub const PLAYER_DROP_THROUGH_TIME: f32 = 0.25;
+
+pub const LEVEL_COUNT: isize = 12;
+
+pub const TILE_SIZE: f32 = 24.0;
+
+pub const PLAYER_HIT_RESPAWN_TIME: f32 = 1.0;
 
 pub const Z_INDEX_PENCIL_BOX: f32 = 4.0;
 pub const Z_INDEX_TILE_MAP_BOX: f32 = 5.0;
+
+// Staplers stay open, then snap shut and hurt for a moment
+pub const STAPLER_OPEN_TIME: f32 = 1.6;
+pub const STAPLER_CLOSED_TIME: f32 = 0.6;
+pub const STAPLER_OPEN_ANGLE: f32 = 40.0;
@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+
+use bevy::prelude::{Component, Query, Res, ResMut, Commands, Entity, With, Without, EventReader, Transform, SpatialBundle, Color, AssetServer, DespawnRecursiveExt, BuildChildren};
+use bevy_ecs_ldtk::{LevelEvent, LevelSelection};
+use kt_common::{components::{player::Player, velocity::Velocity, despawnable::Despawnable}, events::LevelRestarted};
+use kt_core::{physics::TransformInterpolation, replay::{Replay, ReplayState, ReplayFrame}};
+
+use crate::{spawn_ruler_limbs, replays::LevelChecksum, save_game::config_dir};
+
+const GHOST_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.35);
+
+// What the limb animations need to know about a ruler, so the same systems
+// can draw both the player and a ghost.
+#[derive(Component, Debug, Default, Clone)]
+pub struct RulerPose {
+    pub velocity_x: f32,
+    pub stretch: f32,
+    pub grabbed_ceiling: bool,
+    pub respawning: bool,
+}
+
+// Plays back the frames of the best replay of the level. It only has a
+// transform and limbs, no collider, so it can't touch anything.
+#[derive(Component, Debug)]
+pub struct Ghost {
+    frames: Vec<ReplayFrame>,
+    cursor: usize,
+}
+
+pub fn best_replay_path(level: u32) -> PathBuf {
+    config_dir().join("replays").join(format!("level-{:02}-best.ktr", level))
+}
+
+pub fn sync_player_pose(
+    mut q_player: Query<(&Player, &Velocity, &mut RulerPose)>,
+) {
+    for (player, velocity, mut pose) in q_player.iter_mut() {
+        pose.velocity_x = velocity.current.x;
+        pose.stretch = player.stretch;
+        pose.grabbed_ceiling = player.grabbed_ceiling;
+        pose.respawning = player.is_respawning;
+    }
+}
+
+pub fn record_ghost_frame(
+    mut replay_state: ResMut<ReplayState>,
+    q_player: Query<(&Transform, &Player, &Velocity)>,
+) {
+    let replay = match replay_state.as_mut() {
+        ReplayState::Recording(replay) => replay,
+        _ => return,
+    };
+
+    let (transform, player, velocity) = match q_player.get_single() {
+        Ok(player) => player,
+        Err(..) => return,
+    };
+
+    replay.frames.push(ReplayFrame {
+        position: transform.translation.truncate(),
+        velocity_x: velocity.current.x,
+        stretch: player.stretch,
+        grabbed_ceiling: player.grabbed_ceiling,
+        respawning: player.is_respawning,
+    });
+}
+
+// Starts the ghost over whenever the level is spawned or restarted
+pub fn spawn_best_ghost(
+    mut ev_level: EventReader<LevelEvent>,
+    mut ev_level_restarted: EventReader<LevelRestarted>,
+    level_selection: Res<LevelSelection>,
+    level_checksum: Res<LevelChecksum>,
+    q_ghosts: Query<Entity, With<Ghost>>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let spawned = ev_level
+        .iter()
+        .filter(|event| matches!(event, LevelEvent::Spawned(_)))
+        .count() > 0;
+    let restarted = ev_level_restarted.iter().count() > 0;
+
+    if !spawned && !restarted {
+        return;
+    }
+
+    for ghost in q_ghosts.iter() {
+        commands.entity(ghost).despawn_recursive();
+    }
+
+    let level = match *level_selection {
+        LevelSelection::Index(index) => index as u32,
+        _ => return,
+    };
+
+    // No best run yet, or the level changed since it was recorded
+    let replay = match Replay::load(&best_replay_path(level)) {
+        Ok(replay) if replay.check_level(level_checksum.0).is_ok() => replay,
+        _ => return,
+    };
+
+    let start = match replay.frames.first() {
+        Some(frame) => frame.position.extend(-0.5),
+        None => return,
+    };
+
+    let ghost = commands.spawn((
+        SpatialBundle::from_transform(Transform::from_translation(start)),
+        TransformInterpolation::new(start),
+        RulerPose::default(),
+        Ghost {
+            frames: replay.frames,
+            cursor: 0,
+        },
+        Despawnable {},
+    )).id();
+
+    let ghost_limbs = spawn_ruler_limbs(&mut commands, &asset_server, GHOST_COLOR, ());
+
+    commands.entity(ghost)
+        .add_child(ghost_limbs);
+}
+
+pub fn play_ghost(
+    mut q_ghosts: Query<(Entity, &mut Ghost, &mut Transform, &mut RulerPose), Without<Player>>,
+    mut commands: Commands,
+) {
+    for (entity, mut ghost, mut transform, mut pose) in q_ghosts.iter_mut() {
+        let frame = match ghost.frames.get(ghost.cursor) {
+            Some(frame) => *frame,
+            None => {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            },
+        };
+
+        ghost.cursor += 1;
+
+        transform.translation = frame.position.extend(transform.translation.z);
+        pose.velocity_x = frame.velocity_x;
+        pose.stretch = frame.stretch;
+        pose.grabbed_ceiling = frame.grabbed_ceiling;
+        pose.respawning = frame.respawning;
+    }
+}
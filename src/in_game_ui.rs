@@ -1,38 +1,71 @@
-use bevy::{prelude::{Commands, Res, AssetServer, NodeBundle, default, BuildChildren, ChildBuilder, ImageBundle, EventReader, Query, Entity, With, Without}, ui::{Style, Val, FlexDirection, JustifyContent, Display, UiImage, AlignItems, UiRect}};
-use bevy_persistent::Persistent;
-use kt_common::{events::PinUiUpdated, components::ui::{PinsContainerUI, PinUI}};
+use bevy::{prelude::{Commands, Res, AssetServer, NodeBundle, default, BuildChildren, ChildBuilder, ImageBundle, EventReader, Query, Entity, With, Without, TextBundle, Color}, ui::{Style, Val, FlexDirection, JustifyContent, Display, UiImage, AlignItems, UiRect}, text::{Text, TextStyle}};
+use kt_common::{events::PinUiUpdated, components::ui::{InGameUi, PinsContainerUI, PinUI, RunTimerUi, RunTimerDeltaUi, RunTotalUi}};
 
-use crate::save_game::GameState;
+use crate::{save_game::GameSave, run_timer::{RunTimer, format_time, format_delta}};
+
+const AHEAD_COLOR: Color = Color::rgb(0.35, 0.85, 0.35);
+const BEHIND_COLOR: Color = Color::rgb(0.9, 0.3, 0.25);
 
 pub fn setup_in_game_ui(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    game_state: Res<Persistent<GameState>>,
+    game_state: Res<GameSave>,
 ) {
-    let in_game_ui_container = NodeBundle {
+    let in_game_ui_container = (
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::SpaceBetween,
+                ..default()
+            },
+            ..default()
+        },
+        InGameUi {},
+    );
+
+    let top_container = NodeBundle {
         style: Style {
             width: Val::Percent(100.0),
-            height: Val::Percent(100.0),
-            flex_direction: FlexDirection::Column,
+            height: Val::Percent(10.0),
+            flex_direction: FlexDirection::Row,
             justify_content: JustifyContent::SpaceBetween,
+            align_items: AlignItems::Center,
+            padding: UiRect::horizontal(Val::Px(16.0)),
             ..default()
         },
         ..default()
     };
 
-    let top_container = NodeBundle {
+    let timer_column = NodeBundle {
         style: Style {
-            width: Val::Percent(100.0),
-            height: Val::Percent(10.0),
             flex_direction: FlexDirection::Column,
-            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        ..default()
+    };
+
+    let timer_row = NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Row,
             align_items: AlignItems::FlexEnd,
-            padding: UiRect::right(Val::Px(16.0)),
             ..default()
         },
         ..default()
     };
 
+    let timer_style = TextStyle {
+        font: asset_server.load("fonts/ThaleahFat.ttf"),
+        font_size: 32.0,
+        color: Color::WHITE,
+    };
+
+    let small_style = TextStyle {
+        font_size: 20.0,
+        ..timer_style.clone()
+    };
+
     let pins_grid = (NodeBundle {
         style: Style {
             display: Display::Flex,
@@ -47,6 +80,25 @@ pub fn setup_in_game_ui(
             parent
                 .spawn(top_container)
                 .with_children(|top| {
+                    top
+                        .spawn(timer_column)
+                        .with_children(|timer| {
+                            timer
+                                .spawn(timer_row)
+                                .with_children(|row| {
+                                    row.spawn((TextBundle::from_section(format_time(0.0), timer_style), RunTimerUi {}));
+                                    row.spawn((
+                                        TextBundle::from_section("", small_style.clone()).with_style(Style {
+                                            margin: UiRect::left(Val::Px(8.0)),
+                                            ..default()
+                                        }),
+                                        RunTimerDeltaUi {},
+                                    ));
+                                });
+
+                            timer.spawn((TextBundle::from_section("", small_style), RunTotalUi {}));
+                        });
+
                     top.spawn(pins_grid);
                 });
         });
@@ -91,7 +143,7 @@ pub fn consume_pin_ui_update_events(
     mut q_pins_container: Query<Entity, (With<PinsContainerUI>, Without<PinUI>)>,
     mut q_pins: Query<Entity, (With<PinUI>, Without<PinsContainerUI>)>,
     mut commands: Commands,
-    game_state: Res<Persistent<GameState>>,
+    game_state: Res<GameSave>,
     asset_server: Res<AssetServer>,
 ) {
     for _ev in ev_pin_ui_update.iter() {
@@ -117,3 +169,36 @@ pub fn consume_pin_ui_update_events(
         }
     }
 }
+
+pub fn update_run_timer_ui(
+    run_timer: Res<RunTimer>,
+    game_state: Res<GameSave>,
+    mut q_timer: Query<&mut Text, (With<RunTimerUi>, Without<RunTimerDeltaUi>, Without<RunTotalUi>)>,
+    mut q_delta: Query<&mut Text, (With<RunTimerDeltaUi>, Without<RunTimerUi>, Without<RunTotalUi>)>,
+    mut q_total: Query<&mut Text, (With<RunTotalUi>, Without<RunTimerUi>, Without<RunTimerDeltaUi>)>,
+) {
+    let elapsed = run_timer.elapsed();
+
+    for mut text in q_timer.iter_mut() {
+        text.sections[0].value = format_time(elapsed);
+    }
+
+    for mut text in q_delta.iter_mut() {
+        match game_state.stats(run_timer.level).and_then(|stats| stats.best_time) {
+            Some(best_time) => {
+                let delta = elapsed - best_time;
+
+                text.sections[0].value = format_delta(delta);
+                text.sections[0].style.color = if delta > 0.0 { BEHIND_COLOR } else { AHEAD_COLOR };
+            },
+            None => text.sections[0].value.clear(),
+        }
+    }
+
+    for mut text in q_total.iter_mut() {
+        text.sections[0].value = match run_timer.run_elapsed() {
+            Some(total) => format!("run {}", format_time(total)),
+            None => String::new(),
+        };
+    }
+}
@@ -0,0 +1,63 @@
+use bevy::prelude::{Res, ResMut, EventReader};
+use bevy_ecs_ldtk::LevelEvent;
+use kt_common::events::{PlayerDied, PinCollected};
+use kt_core::replay::ReplayState;
+
+use crate::save_game::GameSave;
+
+// Completion and best time are recorded in `record_level_time`, everything
+// else is counted from gameplay events here. Replays never touch the stats.
+// Counts are saved as soon as they change, quitting mid level keeps them.
+pub fn count_level_attempt(
+    mut ev_level: EventReader<LevelEvent>,
+    replay_state: Res<ReplayState>,
+    mut game_state: ResMut<GameSave>,
+) {
+    for event in ev_level.iter() {
+        if !matches!(event, LevelEvent::Spawned(_)) || replay_state.is_playing() {
+            continue;
+        }
+
+        let level = game_state.current_level;
+        game_state.stats_mut(level).attempts += 1;
+        game_state.persist().ok();
+    }
+}
+
+pub fn count_level_death(
+    mut ev_player_died: EventReader<PlayerDied>,
+    replay_state: Res<ReplayState>,
+    mut game_state: ResMut<GameSave>,
+) {
+    for _event in ev_player_died.iter() {
+        if replay_state.is_playing() {
+            continue;
+        }
+
+        let level = game_state.current_level;
+        game_state.stats_mut(level).deaths += 1;
+        game_state.persist().ok();
+    }
+}
+
+pub fn record_best_pins(
+    mut ev_pin_collected: EventReader<PinCollected>,
+    replay_state: Res<ReplayState>,
+    mut game_state: ResMut<GameSave>,
+) {
+    for _event in ev_pin_collected.iter() {
+        if replay_state.is_playing() {
+            continue;
+        }
+
+        let (level, picked_keys) = (game_state.current_level, game_state.picked_keys);
+        let stats = game_state.stats_mut(level);
+
+        if picked_keys <= stats.best_pins {
+            continue;
+        }
+
+        stats.best_pins = picked_keys;
+        game_state.persist().ok();
+    }
+}
@@ -1,24 +1,34 @@
-use std::{time::Duration, path::Path};
+use std::time::Duration;
 
-use bevy::{prelude::{App, default, Commands, ResMut, Assets, Res, AssetServer, Vec2, SpatialBundle, Vec3, Transform, BuildChildren, Startup, Query, Children, With, Update, IntoSystemConfigs, KeyCode, Input, Rect, Without, Entity, Camera, ImagePlugin, Color, in_state, OnEnter, States, Component, Resource, EventWriter, AudioBundle, PlaybackSettings, AudioSink, AudioSinkPlayback, GlobalVolume}, DefaultPlugins, window::{WindowPlugin, Window, WindowResolution, PresentMode}, sprite::{TextureAtlas, SpriteSheetBundle, TextureAtlasSprite, SpriteBundle, Sprite}, utils::{HashMap}, time::{Time, Timer, TimerMode}, ecs::{schedule::ExecutorKind }, diagnostic::{FrameTimeDiagnosticsPlugin}, ui::{Style, Val, UiRect}, audio::{PlaybackMode, VolumeLevel}, };
+use bevy::{prelude::{App, default, Commands, ResMut, Assets, Res, AssetServer, Vec2, SpatialBundle, Vec3, Transform, BuildChildren, Startup, FixedUpdate, Query, Children, Parent, With, Update, IntoSystemConfigs, KeyCode, Input, Rect, Without, Entity, GlobalTransform, ImagePlugin, AssetPlugin, Color, in_state, OnEnter, OnExit, Condition, States, Component, Bundle, EventWriter, EventReader, AudioSink, AudioSinkPlayback, GlobalVolume, FixedTime}, DefaultPlugins, window::{WindowPlugin, Window, WindowResolution, PresentMode}, sprite::{SpriteSheetBundle, TextureAtlasSprite, SpriteBundle, Sprite}, time::{Timer, TimerMode}, ecs::{schedule::ExecutorKind }, diagnostic::{FrameTimeDiagnosticsPlugin}, audio::PlaybackMode, asset::ChangeWatcher, };
 use bevy::prelude::PluginGroup;
 
 use bevy_ecs_ldtk::{LdtkPlugin, LdtkWorldBundle, LevelSelection, prelude::{LdtkIntCellAppExt, LdtkEntityAppExt}, LdtkSettings, LevelBackground, LayerMetadata};
-use bevy_framepace::{FramepacePlugin, FramepaceSettings, Limiter};
-use bevy_persistent::{Persistent, StorageFormat};
-use bevy_rapier2d::prelude::{RigidBody, Collider, KinematicCharacterController, QueryFilterFlags, RapierContext, QueryFilter};
-use bevy_tweening::{Tween, EaseFunction, lens::{TransformScaleLens, TransformPositionLens, SpriteColorLens, UiPositionLens}, RepeatCount};
-use in_game_ui::{setup_in_game_ui, consume_pin_ui_update_events};
-use kt_common::{CommonPlugin, components::{limb::{Limb, LimbType}, player::Player, jump::Jump, gravity::GravityDir, velocity::Velocity, acceleration::Acceleration, checkpoint::Checkpoint, ground_detector::GroundDetector, dust_particle_emitter::DustParticleEmitter, pin::{Pin, PinState}, ui::{TransitionColumnLeftUi, TransitionColumnRightUi}, ldtk::{WallBundle, SpikesBundle, SpawnPointBundle, CheckpointBundle, ElevatorBundle, PlatformBundle, PinBundle, SharpenerBundle, SpawnPoint, Level, Elevator, HitComponent, ExitBundle, RequiredKeys, Exit, TextBundle}, interaction::Interaction}, events::{PinUiUpdated}};
-use kt_core::{CorePlugin, animation::{Animation, Animator, animator_sys}, particle::ParticleEmitter};
-use kt_movement::MovementPlugin;
-use kt_util::constants::{WINDOW_TITLE, INITIAL_WINDOW_WIDTH, INITIAL_WINDOW_HEIGHT, PLAYER_HIT_RESPAWN_TIME, PLAYER_CAMERA_MARGIN_X, ASPECT_RATIO_X, ASPECT_RATIO_Y, PLAYER_CAMERA_MARGIN_Y, PLAYER_JUMP_SPEED, JUMP_HOLD_FORCE, Z_INDEX_PENCIL_BOX};
+use bevy_framepace::FramepacePlugin;
+use bevy_rapier2d::prelude::{Collider, QueryFilterFlags, RapierContext, QueryFilter, PhysicsSet};
+use bevy_tweening::{Tween, EaseFunction, lens::{TransformScaleLens, TransformPositionLens, TransformRotateZLens, SpriteColorLens}, RepeatCount};
+use in_game_ui::{setup_in_game_ui, consume_pin_ui_update_events, update_run_timer_ui};
+use kt_common::{CommonPlugin, bundles::player::PlayerBundle, components::{limb::{Limb, LimbType, PlayerLimb}, player::Player, jump::Jump, velocity::Velocity, checkpoint::Checkpoint, ground_detector::GroundDetector, dust_particle_emitter::DustParticleEmitter, pin::{Pin, PinState}, stapler::{Stapler, StaplerJaw}, ldtk::{WallBundle, OneWayPlatformBundle, SlopeBundle, SpikesBundle, SpawnPointBundle, CheckpointBundle, ElevatorBundle, PlatformBundle, PinBundle, SharpenerBundle, StaplerBundle, MoverBundle, SwitchBundle, GateBundle, DoorBundle, SpawnPoint, HitComponent, ExitBundle, RequiredKeys, Exit, TextBundle, CameraZoneBundle}, interaction::Interaction}, events::{PinUiUpdated, LevelCompleted, PlayerDied, PinCollected, CheckpointActivated, LevelRestarted, TransitionCovered, TransitionKind}};
+use kt_core::{CorePlugin, camera::{CameraTarget, CameraBounds}, animation::{Animator, AnimationSheet, AnimationParams, AnimationEvent, AnimationSet}, particle::ParticleEmitter, physics::{TransformInterpolation, InterpolationSet}, replay::ReplayState, audio::{AudioBuses, AudioBus, PlaySfx, Sfx, bus_audio}};
+use kt_movement::{MovementPlugin, MovementSet};
+use kt_util::constants::{WINDOW_TITLE, INITIAL_WINDOW_WIDTH, INITIAL_WINDOW_HEIGHT, PLAYER_JUMP_SPEED, JUMP_HOLD_FORCE, Z_INDEX_PENCIL_BOX, STAPLER_OPEN_TIME, STAPLER_CLOSED_TIME, STAPLER_OPEN_ANGLE, PLAYER_HALF_WIDTH, PLAYER_HALF_HEIGHT};
 // use bevy_save::{prelude::*, WorldSaveableExt};
 use main_menu_ui::{setup_menu, handle_play_button_interactions, handle_level_button_interactions, handle_back_button_interactions};
-use process_ldtk_world::{process_spawn_point, process_elevator, process_platform, process_pin, process_sharpener, setup_walls, process_exit, process_text};
-use save_game::{GameState, load};
-use screen_transitions::{complete_transition_event_handler, setup_transition_ui, switch_levels_transition_event_handler, save_game_after_transition};
-use setup_sound_ui::{sound_ui, handle_sound_button_interactions};
+use process_ldtk_world::{process_spawn_point, process_elevator, process_mover, process_platform, process_pin, process_sharpener, process_stapler, setup_walls, process_exit, process_text, process_camera_zone, setup_one_way_platforms, process_switch, process_door};
+use save_game::{GameSave, ActiveSlot, SLOT_COUNT, load};
+use screen_transitions::{complete_transition_event_handler, setup_transition_ui, switch_levels_transition_event_handler, save_game_after_transition, start_level_exit_transition, start_respawn_transition, queue_transitions, advance_transition, draw_transition, ScreenTransition};
+use settings::{load_settings, apply_input_bindings, apply_audio_settings};
+use settings_menu::{handle_settings_button_interactions, handle_volume_step_interactions, handle_volume_slider_interactions, update_volume_fills};
+use save_slots::{handle_slot_button_interactions, handle_copy_slot_button_interactions, handle_delete_slot_button_interactions, track_play_time};
+use menu_navigation::{MenuFocus, navigate_menu_focus, reset_menu_focus};
+use level_stats::{count_level_attempt, count_level_death, record_best_pins};
+use pause_menu::{PauseState, toggle_pause, pause_game, resume_game, handle_pause_button_interactions, cleanup_in_game};
+use run_timer::{RunTimer, start_run_timer, tick_run_timer, record_level_time};
+use ghost::{RulerPose, sync_player_pose, record_ghost_frame, spawn_best_ghost, play_ghost};
+use replays::{LevelChecksum, load_replay_from_args, skip_menu_for_replay, start_level_replay, save_level_replay, log_replay_finished};
+use setup_sound_ui::{sound_ui, handle_sound_button_interactions, update_mute_button};
+use particle_effects::{ParticleEffects, sparkle_on_pin_collected, burst_on_player_died};
+use wiring::{update_switches, update_gates, propagate_signals, update_switch_sprites, power_doors, power_movers, power_sharpeners, power_spikes, reset_wiring_on_restart};
 
 pub mod save_game;
 pub mod main_menu_ui;
@@ -26,6 +36,17 @@ pub mod screen_transitions;
 pub mod process_ldtk_world;
 pub mod in_game_ui;
 pub mod setup_sound_ui;
+pub mod settings;
+pub mod menu_navigation;
+pub mod replays;
+pub mod ghost;
+pub mod run_timer;
+pub mod save_slots;
+pub mod level_stats;
+pub mod pause_menu;
+pub mod settings_menu;
+pub mod particle_effects;
+pub mod wiring;
 
 #[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
 pub enum AppState {
@@ -46,7 +67,11 @@ fn main() {
               ..default()
             }),
             ..default()
-        }).set(ImagePlugin::default_nearest()))
+        }).set(ImagePlugin::default_nearest()).set(AssetPlugin {
+            // Lets animation files and sprites be edited while the game runs
+            watch_for_changes: if cfg!(debug_assertions) { ChangeWatcher::with_delay(Duration::from_millis(200)) } else { None },
+            ..default()
+        }))
         .add_plugins(FrameTimeDiagnosticsPlugin)
         .add_plugins(FramepacePlugin)
         .add_plugins(LdtkPlugin)
@@ -55,61 +80,90 @@ fn main() {
         .add_plugins(CorePlugin {})
         .add_plugins(MovementPlugin {})
         // .add_plugins(ParallaxPlugin {})
-        .insert_resource(FramepaceSettings {
-            limiter: Limiter::from_framerate(60.0)
-        })
         .edit_schedule(Update, |schedule| {
             schedule.set_executor_kind(ExecutorKind::SingleThreaded);
         });
 
     app
-        .add_state::<AppState>();
+        .add_state::<AppState>()
+        .add_state::<PauseState>();
 
-    let config_dir = dirs::config_dir()
-        .map(|native_config_dir| native_config_dir.join("pixel-arena"))
-        .unwrap_or(Path::new("local").join("configuration"));
+    let settings = load_settings();
+    let active_slot = ActiveSlot(settings.last_slot.min(SLOT_COUNT - 1));
+    let (game_state, save_notice) = load(active_slot.0);
 
     app
-        .insert_resource(
-                    Persistent::<GameState>::builder()
-            .name("game state")
-            .format(StorageFormat::Json)
-            .path(config_dir.join("game-state.json"))
-            .default(GameState { unlocked_levels: 1, current_level: 0, picked_keys: 0, required_keys: 0 })
-            .build()
-            .expect("failed to initialize game state")
-                        )
+        .insert_resource(game_state)
+        .insert_resource(save_notice)
+        .insert_resource(active_slot)
+        .insert_resource(settings.bindings.clone())
+        .insert_resource(settings.audio.clone())
+        .insert_resource(settings)
+        .insert_resource(load_replay_from_args())
+        .init_resource::<LevelChecksum>()
+        .init_resource::<RunTimer>()
+        .init_resource::<ParticleEffects>()
         .insert_resource(LevelSelection::Index(0))
-        .insert_resource(LevelDimensions::default())
         .insert_resource(LdtkSettings {
             level_background: LevelBackground::Nonexistent,
             ..default()
         })
         .register_ldtk_int_cell::<WallBundle>(1)
+        .register_ldtk_int_cell::<OneWayPlatformBundle>(2)
+        .register_ldtk_int_cell::<SlopeBundle>(3)
+        .register_ldtk_int_cell::<SlopeBundle>(4)
+        .register_ldtk_int_cell::<SlopeBundle>(5)
+        .register_ldtk_int_cell::<SlopeBundle>(6)
+        .register_ldtk_int_cell::<SlopeBundle>(7)
+        .register_ldtk_int_cell::<SlopeBundle>(8)
         .register_ldtk_entity::<SpikesBundle>("Spikes")
         .register_ldtk_entity::<SpawnPointBundle>("SpawnPoint")
         .register_ldtk_entity::<CheckpointBundle>("Checkpoint")
+        .register_ldtk_entity::<StaplerBundle>("Stapler")
         .register_ldtk_entity::<ElevatorBundle>("Elevator")
+        .register_ldtk_entity::<MoverBundle>("Mover")
         .register_ldtk_entity::<PlatformBundle>("Platform")
         .register_ldtk_entity::<PinBundle>("Pin")
         .register_ldtk_entity::<SharpenerBundle>("Sharpener")
         .register_ldtk_entity::<ExitBundle>("Exit")
-        .register_ldtk_entity::<TextBundle>("Text");
+        .register_ldtk_entity::<TextBundle>("Text")
+        .register_ldtk_entity::<CameraZoneBundle>("CameraZone")
+        .register_ldtk_entity::<SwitchBundle>("Button")
+        .register_ldtk_entity::<SwitchBundle>("PressurePlate")
+        .register_ldtk_entity::<SwitchBundle>("Lever")
+        .register_ldtk_entity::<GateBundle>("Gate")
+        .register_ldtk_entity::<DoorBundle>("Door");
 
 /*
     GLOBAL
 */
     app.add_systems(Startup, (background_music, setup_transition_ui, sound_ui).chain());
     app.add_systems(Update, handle_sound_button_interactions);
+    app.add_systems(Update, apply_input_bindings);
+    app.add_systems(Update, (apply_audio_settings, update_mute_button, update_volume_fills));
+    app.init_resource::<ScreenTransition>();
+    app.add_systems(Update, (queue_transitions, draw_transition).chain());
+    // Between the hit that requests a respawn and the systems reacting to the
+    // screen being covered, all on the same tick
+    app.add_systems(FixedUpdate, advance_transition.after(start_respawn_transition).before(respawn_player));
+    app.add_systems(Update, (handle_volume_step_interactions, handle_volume_slider_interactions).run_if(in_state(AppState::MainMenu).or_else(in_state(PauseState::Paused))));
+    app.add_systems(Update, log_replay_finished);
 
 /*
    MENU STATE
 */
     app
-        .add_systems(OnEnter(AppState::MainMenu), setup_menu)
+        .init_resource::<MenuFocus>()
+        .add_systems(OnEnter(AppState::MainMenu), (reset_menu_focus, setup_menu))
+        .add_systems(Update, navigate_menu_focus.run_if(in_state(AppState::MainMenu).or_else(in_state(PauseState::Paused))))
+        .add_systems(Update, skip_menu_for_replay.run_if(in_state(AppState::MainMenu)))
         .add_systems(Update, handle_play_button_interactions.run_if(in_state(AppState::MainMenu)))
+        .add_systems(Update, handle_settings_button_interactions.run_if(in_state(AppState::MainMenu)))
         .add_systems(Update, handle_level_button_interactions.run_if(in_state(AppState::MainMenu)))
-        .add_systems(Update, handle_back_button_interactions.run_if(in_state(AppState::MainMenu)))
+        .add_systems(Update, handle_back_button_interactions.run_if(in_state(AppState::MainMenu).or_else(in_state(PauseState::Paused))))
+        .add_systems(Update, handle_slot_button_interactions.run_if(in_state(AppState::MainMenu)))
+        .add_systems(Update, handle_copy_slot_button_interactions.run_if(in_state(AppState::MainMenu)))
+        .add_systems(Update, handle_delete_slot_button_interactions.run_if(in_state(AppState::MainMenu)))
         .add_systems(Update, complete_transition_event_handler.run_if(in_state(AppState::MainMenu)));
 
 /*
@@ -119,42 +173,107 @@ fn main() {
         .add_systems(OnEnter(AppState::InGame), setup)
         .add_systems(OnEnter(AppState::InGame), spawn_player)
         .add_systems(OnEnter(AppState::InGame), setup_in_game_ui)
+        .add_systems(OnExit(AppState::InGame), cleanup_in_game)
         .add_systems(Update, setup_walls.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, setup_one_way_platforms.run_if(in_state(AppState::InGame)))
         .add_systems(Update, process_spawn_point.run_if(in_state(AppState::InGame)))
         .add_systems(Update, process_text.run_if(in_state(AppState::InGame)))
         .add_systems(Update, process_elevator.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, process_mover.run_if(in_state(AppState::InGame)))
         .add_systems(Update, process_platform.run_if(in_state(AppState::InGame)))
         .add_systems(Update, process_pin.run_if(in_state(AppState::InGame)))
         .add_systems(Update, process_sharpener.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, process_stapler.run_if(in_state(AppState::InGame)))
         .add_systems(Update, process_exit.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, process_camera_zone.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, process_switch.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, process_door.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, start_level_replay.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, start_run_timer.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, count_level_attempt.after(start_level_replay).run_if(in_state(AppState::InGame)))
+        .add_systems(Update, spawn_best_ghost.after(start_level_replay).run_if(in_state(AppState::InGame)))
+        .add_systems(FixedUpdate, play_ghost.after(InterpolationSet::Restore).before(InterpolationSet::Record).run_if(in_state(AppState::InGame)))
+        .add_systems(FixedUpdate, record_ghost_frame.after(InterpolationSet::Record).run_if(in_state(AppState::InGame)))
+        .add_systems(FixedUpdate, tick_run_timer.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, update_run_timer_ui.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, track_play_time.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, count_level_death.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, record_best_pins.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, start_level_exit_transition.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, save_level_replay.before(switch_levels_transition_event_handler).run_if(in_state(AppState::InGame)))
         .add_systems(Update, switch_levels_transition_event_handler.run_if(in_state(AppState::InGame)))
         .add_systems(Update, save_game_after_transition.run_if(in_state(AppState::InGame)))
         .add_systems(Update, consume_pin_ui_update_events.run_if(in_state(AppState::InGame)))
         .add_systems(Update, change_exit_sprite.run_if(in_state(AppState::InGame)))
         .add_systems(Update, open_exit.run_if(in_state(AppState::InGame)))
-        .add_systems(Update, reset_level_after_restart.run_if(in_state(AppState::InGame)))
         .add_systems(Update, restart_pin.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, reset_checkpoints_on_restart.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, reset_wiring_on_restart.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, puff_dust_on_steps.after(AnimationSet).run_if(in_state(AppState::InGame)))
+        .add_systems(Update, sparkle_on_pin_collected.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, burst_on_player_died.run_if(in_state(AppState::InGame)))
         .add_systems(Update, (
-    reset_overlaps,
-    handle_player_interaction,
-    respawn_animation,
-    handle_animation,
-    animator_sys,
-    handle_extension_stretch,
+    sync_player_pose,
+    handle_animation.before(AnimationSet),
+    handle_extension_stretch.after(AnimationSet),
     handle_stretching,
     flip_depend_on_velocity,
+    checkpoint_sprites_handle,
+    update_camera_bounds,
+    sync_camera_target,
+).chain()
+    .run_if(in_state(AppState::InGame))
+    .run_if(in_state(PauseState::Running)));
+
+    // What happens to the player is decided on the simulation tick too, once
+    // the movement of the tick was written back, so it doesn't depend on the
+    // frame rate and replays play out the same
+    app.add_systems(FixedUpdate, (
+    respawn_animation,
+    cycle_staplers,
     handle_player_hurt_collision,
+    start_respawn_transition,
     handle_activate_checkpoint,
-    checkpoint_sprites_handle,
     respawn_player,
-    update_level_dimensions,
-    follow_player_with_camera,
-    elevator_handle,
+    reset_level_after_restart,
     pickup_pin,
     handle_pin,
     handle_exit_door,
 ).chain()
-    .run_if(in_state(AppState::InGame)));
+    .after(PhysicsSet::Writeback)
+    .before(InterpolationSet::Record)
+    .run_if(in_state(AppState::InGame))
+    .run_if(in_state(PauseState::Running)));
+
+    // On the simulation tick, so timed gates and powered movers stay in step
+    // with the movement they affect. Switches see the overlaps of this tick,
+    // found from where the last one left the player.
+    app.add_systems(FixedUpdate, (
+    reset_overlaps,
+    handle_player_interaction,
+    update_switches,
+    update_gates,
+    propagate_signals,
+    update_switch_sprites,
+    power_doors,
+    power_movers,
+    power_sharpeners,
+    power_spikes,
+).chain()
+    .after(InterpolationSet::Restore)
+    .before(MovementSet)
+    .run_if(in_state(AppState::InGame))
+    .run_if(in_state(PauseState::Running)));
+
+/*
+    PAUSED
+*/
+    app
+        .configure_set(FixedUpdate, MovementSet.run_if(in_state(PauseState::Running)))
+        .add_systems(OnEnter(PauseState::Paused), pause_game)
+        .add_systems(OnExit(PauseState::Paused), resume_game)
+        .add_systems(Update, toggle_pause.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, handle_pause_button_interactions.run_if(in_state(PauseState::Paused)));
     
     app.run();
 }
@@ -162,51 +281,9 @@ fn main() {
 fn background_music (
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    audio_buses: Res<AudioBuses>,
 ) {
-    commands.spawn(AudioBundle {
-        source: asset_server.load("audio/music_2.ogg"),
-        settings: PlaybackSettings {
-            volume: bevy::audio::Volume::Relative(VolumeLevel::new(0.1)),
-            mode: PlaybackMode::Loop,
-            ..default()
-        },
-    });
-}
-
-fn elevator_handle(
-    mut q_elevator: Query<(&mut Transform, &mut Elevator, &Level)>
-) {
-    for (mut transform, mut elevator, level) in q_elevator.iter_mut() {
-        if level.0 == 0 {
-            continue;
-        }
-
-        transform.translation.x += elevator.direction.x;
-        transform.translation.y += elevator.direction.y;
-
-        if level.0 < 0 {
-            if transform.translation.y > elevator.initial_position.y {
-                elevator.direction.y = -elevator.direction.y
-            }
-
-            if transform.translation.y < elevator.initial_position.y + level.0 as f32 * 24.0 {
-                elevator.direction.y = -elevator.direction.y
-            }
-
-            continue;
-        }
-
-        if level.0 > 0 {
-            if transform.translation.y > elevator.initial_position.y + level.0 as f32 * 24.0 {
-                elevator.direction.y = -elevator.direction.y
-            }
-
-            if transform.translation.y < elevator.initial_position.y {
-                elevator.direction.y = -elevator.direction.y
-            }
-        }
-
-    }
+    commands.spawn(bus_audio(asset_server.load("audio/music_2.ogg"), &audio_buses, AudioBus::Music, 0.1, PlaybackMode::Loop));
 }
 
 fn handle_pin(
@@ -216,8 +293,7 @@ fn handle_pin(
         &mut bevy_tweening::Animator<Transform>,
         &mut bevy_tweening::Animator<Sprite>,
     )>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    mut ev_play_sfx: EventWriter<PlaySfx>,
 ) {
     for (mut pin, transform, mut transform_animator, mut sprite_animator) in q_pin.iter_mut() {
         // dbg!(&pin);
@@ -246,147 +322,65 @@ fn handle_pin(
 
             pin.picked = true;
 
-            commands.spawn(AudioBundle {
-                source: asset_server.load("audio/SFX_powerUp10.ogg"),
-                settings: PlaybackSettings {
-                    volume: bevy::audio::Volume::Relative(VolumeLevel::new(0.2)),
-                    mode: PlaybackMode::Remove,
-                    ..default()
-                },
-            });
+            ev_play_sfx.send(PlaySfx(Sfx::PinPickup));
         }
     }
 }
 
 fn pickup_pin(
-    mut q_pins: Query<(&mut Pin, &Interaction)>,
-    mut game_state: ResMut<Persistent<GameState>>,
-    mut ev_pin_pickup: EventWriter<PinUiUpdated>
+    mut q_pins: Query<(&mut Pin, &Interaction, Entity)>,
+    mut game_state: ResMut<GameSave>,
+    mut ev_pin_pickup: EventWriter<PinUiUpdated>,
+    mut ev_pin_collected: EventWriter<PinCollected>,
 ) {
-    for (mut pin, interaction) in q_pins.iter_mut() {
+    for (mut pin, interaction, entity) in q_pins.iter_mut() {
         if interaction.is_overlapping && !pin.picked {
             pin.state.update_value(PinState::Picked);
             game_state.picked_keys += 1;
             ev_pin_pickup.send(PinUiUpdated());
+            ev_pin_collected.send(PinCollected { pin: entity });
         }
     }
 }
 
-#[derive(Resource, Default, Debug, Clone)]
-pub struct LevelDimensions {
-    pub width: f32,
-    pub height: f32,
-}
-
-fn update_level_dimensions (
-    q_layers: Query<&LayerMetadata>,
-    mut level_dimensions: ResMut<LevelDimensions>,
+// The camera stays inside the level's tiles layer
+fn update_camera_bounds (
+    q_layers: Query<(&LayerMetadata, &GlobalTransform)>,
+    mut camera_bounds: ResMut<CameraBounds>,
 ) {
-    for layer in q_layers.iter() {
+    for (layer, transform) in q_layers.iter() {
         if layer.identifier != "Tiles" {
             continue;
         }
 
         let width = layer.c_wid * layer.grid_size;
         let height = layer.c_hei * layer.grid_size;
+        let origin = transform.translation().truncate();
 
-        level_dimensions.width = width as f32;
-        level_dimensions.height = height as f32;
+        camera_bounds.level = Some(Rect::from_corners(origin, origin + Vec2::new(width as f32, height as f32)));
     }
 }
 
-pub fn follow_player_with_camera(
-    q_player: Query<&Transform, (With<Player>, Without<Camera>)>,
-    mut q_camera: Query<(&mut Transform, Entity), With<Camera>>,
-    level_dimensions: Res<LevelDimensions>,
+fn sync_camera_target(
+    mut q_player: Query<(&GroundDetector, &mut CameraTarget), With<Player>>,
 ) {
-    let player = if let Ok(player) = q_player.get_single() {
-        player
-    } else {
-        return
-    };
-
-    let (mut camera, entity) = if let Ok(camera) = q_camera.get_single_mut() {
-        camera
-    } else {
-        return
-    };
-
-    let x_margin = ASPECT_RATIO_X * ((PLAYER_CAMERA_MARGIN_X as f32 / 2.0) / 100.0);
-    let y_margin = ASPECT_RATIO_Y * ((PLAYER_CAMERA_MARGIN_Y as f32 / 2.0) / 100.0);
-
-    let left_edge = camera.translation.x - x_margin;
-    let right_edge = camera.translation.x + x_margin;
-
-    let top_edge = camera.translation.y + y_margin;
-    let bottom_edge = camera.translation.y - y_margin;
-
-    let mut speed = Vec2::ZERO;
-    let mut new_pos_x = camera.translation.x;
-
-    if player.translation.x < left_edge {
-        new_pos_x = camera.translation.x - (left_edge - player.translation.x);
-        speed.x = -1.0;
+    for (ground_detector, mut camera_target) in q_player.iter_mut() {
+        camera_target.grounded = ground_detector.is_on_ground.current;
     }
-
-    if player.translation.x > right_edge {
-        new_pos_x = camera.translation.x + player.translation.x - right_edge;
-        speed.x = 1.0;
-    }
-
-    if player.translation.x - ASPECT_RATIO_X / 2.0 + x_margin <= 0.0 {
-        new_pos_x = ASPECT_RATIO_X / 2.0;
-        speed.x = 0.0;
-    }
-
-    if player.translation.x + ASPECT_RATIO_X / 2.0 - x_margin >= level_dimensions.width {
-        new_pos_x = level_dimensions.width - ASPECT_RATIO_X / 2.0;
-        speed.x = 0.0;
-    }
-
-    let mut new_pos_y = camera.translation.y;
-
-    if player.translation.y > top_edge {
-        new_pos_y = camera.translation.y + (player.translation.y - top_edge);
-        speed.y = 1.0;
-    }
-
-    if player.translation.y < bottom_edge {
-        new_pos_y = camera.translation.y - (bottom_edge - player.translation.y);
-        speed.y = -1.0;
-    }
-
-    if player.translation.y - ASPECT_RATIO_Y / 2.0 + y_margin <= 8.0 {
-        new_pos_y = ASPECT_RATIO_Y / 2.0 + 8.0;
-        speed.y = 0.0;
-    }
-
-    if player.translation.y + ASPECT_RATIO_Y / 2.0 - y_margin >= level_dimensions.height - 8.0 {
-        new_pos_y = level_dimensions.height - ASPECT_RATIO_Y / 2.0 - 8.0;
-        speed.y = 0.0;
-    }
-
-    camera.translation.x = new_pos_x;
-    camera.translation.y = new_pos_y;
-    // speed.x = new_pos_x - camera.translation.x;
-    // speed.y = new_pos_y - camera.translation.y;
-
-    // move_event_writer.send(ParallaxMoveEvent {
-        // camera_move_speed: speed,
-        // camera: entity,
-    // });
 }
 
 fn handle_activate_checkpoint(
     q_player: Query<(&Transform, &Velocity), With<Player>>,
     mut q_checkpoints: Query<&mut Checkpoint>,
+    mut q_pins: Query<&mut Pin>,
     rapier_context: Res<RapierContext>,
-    time: Res<Time>,
+    fixed_time: Res<FixedTime>,
+    mut ev_checkpoint_activated: EventWriter<CheckpointActivated>,
 ) {
     for (transform, velocity) in q_player.iter() {
-        let shape = Collider::cuboid(6.0, 9.0);
+        let shape = Collider::cuboid(PLAYER_HALF_WIDTH, PLAYER_HALF_HEIGHT);
         let shape_pos = transform.translation.truncate();
-        let shape_vel = Vec2::new(velocity.current.x * time.delta_seconds(), velocity.current.y * time.delta_seconds());
+        let shape_vel = Vec2::new(velocity.current.x * fixed_time.period.as_secs_f32(), velocity.current.y * fixed_time.period.as_secs_f32());
         let shape_rot = 0.0;
         let max_toi = 1.0;
         let filter = QueryFilter {
@@ -417,6 +411,85 @@ fn handle_activate_checkpoint(
                 Ok(mut checkpoint_to_activate) => checkpoint_to_activate.is_active = true,
                 Err(..) => continue,
             }
+
+            for mut pin in q_pins.iter_mut() {
+                pin.banked = matches!(pin.state.current, PinState::Picked);
+            }
+
+            ev_checkpoint_activated.send(CheckpointActivated { checkpoint: entity });
+        }
+    }
+}
+
+// A restarted run starts from the spawn point again, with nothing banked
+fn reset_checkpoints_on_restart(
+    mut ev_level_restarted: EventReader<LevelRestarted>,
+    mut q_checkpoints: Query<&mut Checkpoint>,
+    mut q_pins: Query<&mut Pin>,
+) {
+    if ev_level_restarted.iter().count() == 0 {
+        return;
+    }
+
+    for mut checkpoint in q_checkpoints.iter_mut() {
+        checkpoint.is_active = false;
+    }
+
+    for mut pin in q_pins.iter_mut() {
+        pin.banked = false;
+    }
+}
+
+fn cycle_staplers(
+    mut q_staplers: Query<(&mut Stapler, &Children, Entity)>,
+    mut q_jaws: Query<&mut bevy_tweening::Animator<Transform>, With<StaplerJaw>>,
+    fixed_time: Res<FixedTime>,
+    mut commands: Commands,
+) {
+    for (mut stapler, children, entity) in q_staplers.iter_mut() {
+        stapler.timer.tick(fixed_time.period);
+
+        if !stapler.timer.just_finished() {
+            continue;
+        }
+
+        stapler.is_closed = !stapler.is_closed;
+
+        let open_angle = STAPLER_OPEN_ANGLE.to_radians();
+        let tween = if stapler.is_closed {
+            stapler.timer = Timer::from_seconds(STAPLER_CLOSED_TIME, TimerMode::Once);
+            commands.entity(entity).insert(HitComponent {});
+
+            // Snaps shut, then takes its time coming back up
+            Tween::new(
+                EaseFunction::QuadraticIn,
+                Duration::from_secs_f32(0.06),
+                TransformRotateZLens {
+                    start: open_angle,
+                    end: 0.0,
+                }
+            )
+        } else {
+            stapler.timer = Timer::from_seconds(STAPLER_OPEN_TIME, TimerMode::Once);
+            commands.entity(entity).remove::<HitComponent>();
+
+            Tween::new(
+                EaseFunction::SineOut,
+                Duration::from_secs_f32(0.4),
+                TransformRotateZLens {
+                    start: 0.0,
+                    end: open_angle,
+                }
+            )
+        };
+
+        let jaw = match children.iter().find(|child| q_jaws.contains(**child)) {
+            Some(jaw) => *jaw,
+            None => continue,
+        };
+
+        if let Ok(mut animator) = q_jaws.get_mut(jaw) {
+            animator.set_tweenable(tween);
         }
     }
 }
@@ -425,59 +498,87 @@ fn checkpoint_sprites_handle(
     mut q_checkpoints: Query<(&mut TextureAtlasSprite, &Checkpoint)>,
 ) {
     for (mut sprite, checkpoint) in q_checkpoints.iter_mut() {
+        // Grey and red flags on the bottom row of objects.png
         if checkpoint.is_active {
-            sprite.index = 2;
+            sprite.index = 5;
         } else {
-            sprite.index = 1;
+            sprite.index = 4;
         }
     }
 }
 
+// Hits and restarts move the player back once the respawn transition covers
+// the screen, a new level once its pencil box had time to show up
 fn respawn_player(
-    mut q_player: Query<(&mut Transform, &mut Player, &mut Velocity, &mut Jump, Entity)>,
-    mut q_checkpoint: Query<&mut Transform, (With<SpawnPoint>, Without<Player>)>,
-    time: Res<Time>,
+    mut q_player: Query<(&mut Transform, &mut TransformInterpolation, &mut Player, &mut Velocity, &mut Jump, Entity)>,
+    mut q_spawn_point: Query<&mut Transform, (With<SpawnPoint>, Without<Player>)>,
+    q_checkpoints: Query<(&Transform, &Checkpoint), (Without<SpawnPoint>, Without<Player>)>,
+    mut ev_transition_covered: EventReader<TransitionCovered>,
+    fixed_time: Res<FixedTime>,
     mut commands: Commands,
 ) {
-    for (mut transform, mut player, mut velocity, mut jump, entity) in q_player.iter_mut() {
-        player.respawn_timer.tick(time.delta());
+    let covered = ev_transition_covered
+        .iter()
+        .any(|TransitionCovered(kind)| *kind == TransitionKind::Respawn);
 
-        if player.respawn_timer.just_finished() {
-            for mut spawnpoint_transform in q_checkpoint.iter_mut() {
-                spawnpoint_transform.translation.z = Z_INDEX_PENCIL_BOX;
-                transform.translation.x = spawnpoint_transform.translation.x;
-                transform.translation.y = spawnpoint_transform.translation.y + 10.0;
-                velocity.current.y = PLAYER_JUMP_SPEED;
-                player.respawning_animation_timer = Timer::from_seconds(0.3, TimerMode::Once);
-                jump.is_jumping = true;
-
-                commands.entity(entity).remove::<Collider>();
-                commands.entity(entity).insert({
-                    Collider::cuboid(6.0, 9.0)
-                });
-            }
+    for (mut transform, mut interpolation, mut player, mut velocity, mut jump, entity) in q_player.iter_mut() {
+        player.respawn_timer.tick(fixed_time.period);
+
+        if !covered && !player.respawn_timer.just_finished() {
+            continue;
         }
+
+        let active_checkpoint = q_checkpoints
+            .iter()
+            .find(|(_transform, checkpoint)| checkpoint.is_active)
+            .map(|(checkpoint_transform, _checkpoint)| checkpoint_transform.translation);
+
+        let respawn_position = match active_checkpoint {
+            Some(position) => position,
+            None => {
+                let mut spawnpoint_transform = match q_spawn_point.get_single_mut() {
+                    Ok(spawnpoint_transform) => spawnpoint_transform,
+                    Err(..) => continue,
+                };
+
+                // Only the pencil box pops the player out of itself
+                spawnpoint_transform.translation.z = Z_INDEX_PENCIL_BOX;
+                spawnpoint_transform.translation
+            },
+        };
+
+        transform.translation.x = respawn_position.x;
+        transform.translation.y = respawn_position.y + 10.0;
+        interpolation.teleport(transform.translation);
+        velocity.current.y = PLAYER_JUMP_SPEED;
+        player.respawning_animation_timer = Timer::from_seconds(0.3, TimerMode::Once);
+        jump.is_jumping = true;
+
+        commands.entity(entity).remove::<Collider>();
+        commands.entity(entity).insert({
+            Collider::cuboid(PLAYER_HALF_WIDTH, PLAYER_HALF_HEIGHT)
+        });
     }
 }
 
 fn respawn_animation(
     mut q_player: Query<(&mut Player, &mut Velocity, Entity)>,
     mut q_spawn_points: Query<&mut Transform, With<SpawnPoint>>,
-    time: Res<Time>,
+    fixed_time: Res<FixedTime>,
     mut commands: Commands,
 ) {
     for (mut player, mut velocity, entity) in q_player.iter_mut() {
-        player.respawning_animation_timer.tick(time.delta());
+        player.respawning_animation_timer.tick(fixed_time.period);
 
         if !player.respawning_animation_timer.finished() {
-            velocity.current.y += JUMP_HOLD_FORCE * player.respawning_animation_timer.percent_left();
+            velocity.current.y += JUMP_HOLD_FORCE * player.respawning_animation_timer.percent_left() * fixed_time.period.as_secs_f32();
         }
 
         if player.respawning_animation_timer.just_finished() {
             player.is_respawning = false;
             commands.entity(entity).remove::<Collider>();
             commands.entity(entity).insert({
-                Collider::cuboid(6.0, 9.0)
+                Collider::cuboid(PLAYER_HALF_WIDTH, PLAYER_HALF_HEIGHT)
             });
 
             for mut transform in q_spawn_points.iter_mut() {
@@ -529,18 +630,25 @@ fn restart_pin(
     }
 }
 
+// Under the same cover as `respawn_player`
 fn reset_level_after_restart(
-    q_player: Query<&Player>,
+    mut ev_transition_covered: EventReader<TransitionCovered>,
     mut q_pins: Query<&mut Pin>,
-    mut game_state: ResMut<Persistent<GameState>>,
+    mut game_state: ResMut<GameSave>,
     mut ev_pin_pickup: EventWriter<PinUiUpdated>,
     mut q_exits: Query<&mut Exit>,
 ) {
-    for player in q_player.iter() {
-        if player.respawning_animation_timer.just_finished() {
+    for TransitionCovered(kind) in ev_transition_covered.iter() {
+        if *kind == TransitionKind::Respawn {
             game_state.picked_keys = 0;
 
+            // Pins banked at a checkpoint stay collected
             for mut pin in q_pins.iter_mut() {
+                if pin.banked {
+                    game_state.picked_keys += 1;
+                    continue;
+                }
+
                 pin.state.update_value(PinState::Idle);
             }
 
@@ -555,9 +663,10 @@ fn reset_level_after_restart(
 
 fn handle_exit_door (
     q_exit_door: Query<(&Interaction, &RequiredKeys)>,
-    mut q_transition_left: Query<&mut bevy_tweening::Animator<Style>, (With<TransitionColumnLeftUi>, Without<TransitionColumnRightUi>)>,
-    mut q_transition_right: Query<&mut bevy_tweening::Animator<Style>, (With<TransitionColumnRightUi>, Without<TransitionColumnLeftUi>)>,
-    mut game_state: ResMut<Persistent<GameState>>,
+    mut game_state: ResMut<GameSave>,
+    mut run_timer: ResMut<RunTimer>,
+    replay_state: Res<ReplayState>,
+    mut ev_level_completed: EventWriter<LevelCompleted>,
 ) {
     for (interaction, require_keys) in q_exit_door.iter() {
         if !interaction.is_overlapping {
@@ -568,50 +677,15 @@ fn handle_exit_door (
             continue;
         }
 
-        let mut transition_left_column_animator = q_transition_left.single_mut();
-        let mut transition_right_column_animator = q_transition_right.single_mut();
-
-        let tween = Tween::new(
-            EaseFunction::QuarticInOut,
-            Duration::from_secs_f32(0.5),
-            UiPositionLens {
-                start: UiRect {
-                    left: Val::Percent(100.0),
-                    top: Val::Auto,
-                    right: Val::Auto,
-                    bottom: Val::Auto,
-                },
-                end: UiRect {
-                    left: Val::Percent(40.0),
-                    top: Val::Auto,
-                    right: Val::Auto,
-                    bottom: Val::Auto,
-                },
-            },
-        ).with_completed_event(4);
-
-        transition_left_column_animator.set_tweenable(tween);
-
-        let tween = Tween::new(
-            EaseFunction::QuarticInOut,
-            Duration::from_secs_f32(0.5),
-            UiPositionLens {
-                start: UiRect {
-                    right: Val::Percent(100.0),
-                    top: Val::Auto,
-                    left: Val::Auto,
-                    bottom: Val::Auto,
-                },
-                end: UiRect {
-                    right: Val::Percent(40.0),
-                    top: Val::Auto,
-                    left: Val::Auto,
-                    bottom: Val::Auto,
-                },
-            },
-        );
+        // Watching a replay shouldn't overwrite personal bests
+        if replay_state.is_playing() {
+            run_timer.complete_level();
+        } else {
+            record_level_time(&mut run_timer, &mut game_state);
+        }
+
+        ev_level_completed.send(LevelCompleted { level: game_state.current_level });
 
-        transition_right_column_animator.set_tweenable(tween);
         game_state.picked_keys = 0;
     }
 }
@@ -620,14 +694,14 @@ fn handle_player_interaction (
     q_player: Query<(&mut Transform, &Velocity), With<Player>>,
     mut q_interaction: Query<&mut Interaction>,
     rapier_context: Res<RapierContext>,
-    time: Res<Time>,
+    fixed_time: Res<FixedTime>,
 ) {
     for (transform, velocity) in q_player.iter() {
-        let shape = Collider::cuboid(6.0, 9.0);
+        let shape = Collider::cuboid(PLAYER_HALF_WIDTH, PLAYER_HALF_HEIGHT);
         let shape_pos = transform.translation.truncate();
         let shape_vel = Vec2::new(
-            velocity.current.x * time.delta_seconds(),
-            velocity.current.y * time.delta_seconds(),
+            velocity.current.x * fixed_time.period.as_secs_f32(),
+            velocity.current.y * fixed_time.period.as_secs_f32(),
         );
         let shape_rot = 0.0;
         let max_toi = 1.0;
@@ -665,17 +739,17 @@ fn reset_overlaps (
 }
 
 fn handle_player_hurt_collision(
-    mut q_player: Query<(&mut Transform, &mut Velocity, &mut Player)>,
-    mut q_limbs: Query<&mut Sprite, With<Limb>>,
-    mut q_limbs_2: Query<&mut TextureAtlasSprite, With<Limb>>,
+    mut q_player: Query<(&mut Transform, &mut Velocity, &mut Player, Entity)>,
+    mut q_limbs: Query<&mut Sprite, With<PlayerLimb>>,
+    mut q_limbs_2: Query<&mut TextureAtlasSprite, With<PlayerLimb>>,
     q_hit: Query<&HitComponent>,
     rapier_context: Res<RapierContext>,
-    time: Res<Time>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    fixed_time: Res<FixedTime>,
+    mut ev_play_sfx: EventWriter<PlaySfx>,
+    mut ev_player_died: EventWriter<PlayerDied>,
 ) {
-    for (transform, mut velocity, mut player) in q_player.iter_mut() {
-        player.hit_timer.tick(time.delta());
+    for (transform, mut velocity, mut player, player_entity) in q_player.iter_mut() {
+        player.hit_timer.tick(fixed_time.period);
         if player.hit_timer.just_finished() {
             for mut sprite in q_limbs.iter_mut() {
                 sprite.color = Color::Rgba{ red: 1.0, green: 1.0, blue: 1.0, alpha: 1.0 };
@@ -686,13 +760,13 @@ fn handle_player_hurt_collision(
             }
         }
 
-        if !player.respawn_timer.finished() && !player.respawn_timer.paused() {
+        if player.is_respawning {
             continue;
         }
 
-        let shape = Collider::cuboid(6.0, 9.0);
+        let shape = Collider::cuboid(PLAYER_HALF_WIDTH, PLAYER_HALF_HEIGHT);
         let shape_pos = transform.translation.truncate();
-        let shape_vel = Vec2::new(velocity.current.x * time.delta_seconds(), velocity.current.y * time.delta_seconds());
+        let shape_vel = Vec2::new(velocity.current.x * fixed_time.period.as_secs_f32(), velocity.current.y * fixed_time.period.as_secs_f32());
         let shape_rot = 0.0;
         let max_toi = 1.0;
         let filter = QueryFilter {
@@ -706,17 +780,10 @@ fn handle_player_hurt_collision(
             let hit_component = q_hit.get(entity);
 
             if hit_component.is_ok() {
-                player.respawn_timer = Timer::from_seconds(PLAYER_HIT_RESPAWN_TIME, TimerMode::Once);
                 player.is_respawning = true;
 
-                commands.spawn(AudioBundle {
-                    source: asset_server.load("audio/SFX_fall2.ogg"),
-                    settings: PlaybackSettings {
-                        volume: bevy::audio::Volume::Relative(VolumeLevel::new(0.2)),
-                        mode: PlaybackMode::Remove,
-                        ..default()
-                    },
-                });
+                ev_player_died.send(PlayerDied { player: player_entity });
+                ev_play_sfx.send(PlaySfx(Sfx::Hurt));
 
                 for mut sprite in q_limbs.iter_mut() {
                     sprite.color = Color::Rgba{ red: 1.0, green: 1.0, blue: 1.0, alpha: 0.0 };
@@ -743,18 +810,21 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
 }
 
 #[derive(Debug, Component)]
-struct PlayerLimbs {}
+struct RulerLimbs {}
 
 fn handle_stretching(
-    q_player_limbs_container: Query<&Children, With<PlayerLimbs>>,    
-    mut q_player_limbs: Query<(&mut Transform, &Limb)>,
-    q_player: Query<&Player, Without<PlayerLimbs>>,
+    q_ruler_limbs_container: Query<(&Parent, &Children), With<RulerLimbs>>,
+    mut q_ruler_limbs: Query<(&mut Transform, &Limb)>,
+    q_pose: Query<&RulerPose>,
 ) {
-    let player = q_player.get_single().unwrap();
+    for (parent, children) in q_ruler_limbs_container.iter() {
+        let pose = match q_pose.get(parent.get()) {
+            Ok(pose) => pose,
+            Err(..) => continue,
+        };
 
-    for children in q_player_limbs_container.iter() {
         for &child in children.iter() {
-            let child = q_player_limbs.get_mut(child);
+            let child = q_ruler_limbs.get_mut(child);
 
             let (mut transform, limb) = match child {
                 Ok(child) => child,
@@ -766,33 +836,29 @@ fn handle_stretching(
                 _ => continue,
             }
 
-            transform.translation.y = player.stretch;
+            transform.translation.y = pose.stretch;
         }
     }
 }
 
 fn handle_extension_stretch(
-    q_player_children: Query<(&Children, &PlayerLimbs)>,    
-    q_player: Query<&Player>,    
+    q_ruler_children: Query<(&Parent, &Children), With<RulerLimbs>>,
+    q_pose: Query<&RulerPose>,
     mut q_player_limbs: Query<(&mut Sprite, &Limb, &mut Transform)>,
-    mut q_player_limbs_2: Query<(&Animator, &Limb)>,
+    q_animated_limbs: Query<(&Animator, &Limb)>,
+    animation_sheets: Res<Assets<AnimationSheet>>,
 ) {
-    let mut extension_offset = HashMap::new();
-    extension_offset.insert("Idle".to_string(), vec![0, -1, -1, 0]);
-    extension_offset.insert("Move".to_string(), vec![0, 1, 3, 1, 0, 1, 3, 1, 0]);
-
-    let mut extension_frame = HashMap::new();
-    extension_frame.insert("Idle".to_string(), vec![0, 0, 0, 0]);
-    extension_frame.insert("Move".to_string(), vec![0, 0, 0, 0, 1, 1, 1, 1]);
+    for (parent, children) in q_ruler_children.iter() {
+        let pose = match q_pose.get(parent.get()) {
+            Ok(pose) => pose,
+            Err(..) => continue,
+        };
 
-    for (children, player) in q_player_children.iter() {
-        let mut offset = 0;
+        let mut offset = Vec2::ZERO;
         let mut frame = 0;
 
         for &child in children.iter() {
-            let child = q_player_limbs_2.get_mut(child);
-
-            let (animator, limb) = match child {
+            let (animator, limb) = match q_animated_limbs.get(child) {
                 Ok(child) => child,
                 Err(..) => continue,
             };
@@ -802,20 +868,10 @@ fn handle_extension_stretch(
                 _ => continue,
             }
 
-            let offsets = extension_offset.get(&animator.current_animation);
-            let offsets = match offsets {
-                Some(offsets) => offsets,
-                None => continue,
-            };
-
-            let frames = extension_frame.get(&animator.current_animation);
-            let frames = match frames {
-                Some(frames) => frames,
-                None => continue,
-            };
-
-            offset = offsets[animator.current_frame];
-            frame = frames[animator.current_frame];
+            if let Some(attachment) = animator.attachment(&animation_sheets, "extension") {
+                offset = attachment.offset;
+                frame = attachment.frame;
+            }
         }
 
         for &child in children.iter() {
@@ -831,10 +887,9 @@ fn handle_extension_stretch(
                 _ => continue,
             }
 
-            let player = q_player.get_single().unwrap();
-
-            sprite.rect = Some(Rect::new(frame as f32 * 12.0, 0.0, 12.0 + 12.0 * frame as f32, player.stretch + 4.0));
-            transform.translation.y = (player.stretch / 2.0) - 7.0 + offset as f32;
+            sprite.rect = Some(Rect::new(frame as f32 * 12.0, 0.0, 12.0 + 12.0 * frame as f32, pose.stretch + 4.0));
+            transform.translation.x = 0.5 + offset.x;
+            transform.translation.y = (pose.stretch / 2.0) - 7.0 + offset.y;
         }
     }
 }
@@ -865,26 +920,11 @@ fn create_transform_tween(from_x: f32, to_x: f32) -> Tween<Transform> {
 }
 
 fn handle_animation(
-    q_player: Query<(&Velocity, &Children, &Player)>,    
-    mut q_player_limbs: Query<(&Children, &mut bevy_tweening::Animator<Transform>, &Transform), With<PlayerLimbs>>,
-    mut q_limbs: Query<&mut Animator, With<Limb>>,
+    q_rulers: Query<(&RulerPose, &Children)>,
+    mut q_player_limbs: Query<(&Children, &mut bevy_tweening::Animator<Transform>, &Transform), With<RulerLimbs>>,
+    mut q_limbs: Query<&mut AnimationParams, With<Limb>>,
 ) {
-    for (velocity, children, player) in q_player.iter() {
-        let mut animation_name = "Idle".to_string();
-        let mut play_animation = true;
-
-        if velocity.current.x.abs() > 8.0 {
-            animation_name = "Move".to_string();
-        }
-
-        if player.grabbed_ceiling {
-            animation_name = "Extending".to_string();
-
-            if velocity.current.x.abs() < 8.0 {
-                play_animation = false; 
-            }
-        }
-
+    for (pose, children) in q_rulers.iter() {
         for &child in children.iter() {
             let child = q_player_limbs.get_mut(child);
 
@@ -893,33 +933,60 @@ fn handle_animation(
                 Err(..) => continue,
             };
 
-            if !player.is_respawning && velocity.current.x < 0.0 && transform.scale.x >= 1.0 {
+            if !pose.respawning && pose.velocity_x < 0.0 && transform.scale.x >= 1.0 {
                 animator.set_tweenable(create_transform_tween(1.0, -1.0));
             }
 
-            if !player.is_respawning && velocity.current.x > 0.0 && transform.scale.x <= -1.0 {
+            if !pose.respawning && pose.velocity_x > 0.0 && transform.scale.x <= -1.0 {
                 animator.set_tweenable(create_transform_tween(-1.0, 1.0));
             }
 
+            // Which clip plays is up to the controller in the animation file
             for &limb in player_limbs.iter() {
-                let player_limb = q_limbs.get_mut(limb);
-
-                let mut animator = match player_limb {
-                    Ok(player_limb) => player_limb,
+                let mut params = match q_limbs.get_mut(limb) {
+                    Ok(params) => params,
                     Err(..) => continue,
                 };
 
-                animator.current_animation = animation_name.clone();
-                animator.paused = !play_animation;
+                params.set_float("speed", pose.velocity_x.abs());
+                params.set_bool("grabbed_ceiling", pose.grabbed_ceiling);
             }
+        }
+    }
+}
+
+// Step frames in the animation file kick up dust, only for the player and not
+// for the ghost
+fn puff_dust_on_steps(
+    mut ev_animation: EventReader<AnimationEvent>,
+    q_parents: Query<&Parent>,
+    q_player: Query<(), With<Player>>,
+    mut q_dust_emitter: Query<&mut ParticleEmitter, With<DustParticleEmitter>>,
+) {
+    for event in ev_animation.iter() {
+        if event.name != "step" {
+            continue;
+        }
+
+        // Limb -> limbs container -> player
+        let owner = q_parents
+            .get(event.entity)
+            .and_then(|limbs| q_parents.get(limbs.get()))
+            .map(|owner| owner.get());
+
+        if !owner.map_or(false, |owner| q_player.contains(owner)) {
+            continue;
+        }
 
+        for mut emitter in q_dust_emitter.iter_mut() {
+            emitter.trigger();
         }
     }
 }
 
 fn open_exit(
     mut q_exits: Query<&mut Exit>,
-    game_state: Res<Persistent<GameState>>,
+    game_state: Res<GameSave>,
 ) {
     for mut exit in q_exits.iter_mut() {
         if game_state.picked_keys == game_state.required_keys {
@@ -942,23 +1009,41 @@ fn change_exit_sprite(
 
 fn spawn_player(
     mut commands: Commands,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     asset_server: Res<AssetServer>,
+    particle_effects: Res<ParticleEffects>,
 ) {
-    let texture_handle = asset_server.load("sprites/ruler_spirite.png");
-    let texture_atlas = TextureAtlas::from_grid(
-        texture_handle,
-        Vec2::new(19.0, 22.0),
-        16,
-        3,
-        None,
-        None,
-    );
+    let player = commands.spawn((
+        SpatialBundle::from_transform(Transform::from_xyz(50.0, 200.0, 0.0)),
+        TransformInterpolation::new(Vec3::new(50.0, 200.0, 0.0)),
+        PlayerBundle::default(),
+        RulerPose::default(),
+        CameraTarget::default(),
+    )).id();
 
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
+    let player_limbs = spawn_ruler_limbs(&mut commands, &asset_server, Color::WHITE, PlayerLimb {});
 
-    let mut respawn_timer = Timer::from_seconds(PLAYER_HIT_RESPAWN_TIME, TimerMode::Once);
-    respawn_timer.pause();
+    commands.entity(player)
+        .add_child(player_limbs);
+
+    commands.spawn((
+        Transform::from_xyz(0.0, 0.0, 2.0),
+        ParticleEmitter::new(particle_effects.dust.clone(), 4, Timer::from_seconds(0.4, TimerMode::Once)),
+        DustParticleEmitter {},
+    ));
+}
+
+// Legs, body, hands and the extension, animated from the `RulerPose` of
+// whatever they are attached to. Shared by the player and the ghost, `marker`
+// is added to every limb so either can be told apart.
+pub fn spawn_ruler_limbs(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    color: Color,
+    marker: impl Bundle + Clone,
+) -> Entity {
+    // Frames and the atlas grid live in the file, the atlas is filled in
+    // once it loads
+    let sheet = asset_server.load("animations/ruler.anim.json");
 
     let tween = Tween::new(
         EaseFunction::BounceOut,
@@ -969,165 +1054,58 @@ fn spawn_player(
         },
     );
 
-    let player = commands.spawn((
-        SpatialBundle::from_transform(Transform::from_xyz(50.0, 200.0, 0.0)),
-        RigidBody::KinematicVelocityBased,
-        Collider::cuboid(6.0, 9.0),
-        GroundDetector::default(),
-        GravityDir {
-            dir: 0.0,
-            slow_down: 1.0,
-        },
-        Velocity {
-            damping: 0.1,
-            max: Vec2::new(80.0, 300.0),
-            ..default()
-        },
-        Acceleration::default(),
-        Jump::default(),
-        KinematicCharacterController {
-            filter_flags: QueryFilterFlags::EXCLUDE_SENSORS,
-            ..default()
-        },
-        Player {
-            respawn_timer,
-            ..default()
-        },
-    )).id();
-
-    let player_limbs = commands.spawn((
-        PlayerLimbs {},
+    let ruler_limbs = commands.spawn((
+        RulerLimbs {},
         SpatialBundle::from_transform(Transform::from_xyz(0.0, 2.0, 1.0)),
         bevy_tweening::Animator::new(tween),
     )).id();
 
     // Spawn legs
-    let mut legs_animations = HashMap::new();
-
-    let legs_idle_animation = Animation {
-        frames: vec![0, 1, 2, 3],
-        looping: true,
-        fps: 5,
-    };
-
-    let legs_move_animation = Animation {
-        frames: (4..11).collect(),
-        looping: true,
-        fps: 12,
-    };
-
-    let legs_extending_animation = Animation {
-        frames: (12..15).collect(),
-        looping: true,
-        fps: 10,
-    };
-
-    legs_animations.insert("Idle".to_string(), legs_idle_animation);
-    legs_animations.insert("Move".to_string(), legs_move_animation);
-    legs_animations.insert("Extending".to_string(), legs_extending_animation);
-
     let legs = commands.spawn((
         SpriteSheetBundle {
-            texture_atlas: texture_atlas_handle.clone(),
-            sprite: TextureAtlasSprite::new(0),
+            sprite: TextureAtlasSprite {
+                color,
+                ..TextureAtlasSprite::new(0)
+            },
             transform: Transform::from_translation(Vec3::ZERO),
             ..default()
         },
-        Animator {
-            animations: legs_animations,
-            counter: 0.0,
-            current_animation: "Idle".to_string(),
-            prev_animation: "Idle".to_string(),
-            current_frame: 0,
-            paused: false,
-        },
-        Limb::new(LimbType::Legs)
+        Animator::new(sheet.clone(), "Legs", "Idle"),
+        AnimationParams::default(),
+        Limb::new(LimbType::Legs),
+        marker.clone(),
     )).id();
 
     // Spawn body
-    let mut body_animations = HashMap::new();
-
-    let body_idle_animation = Animation {
-        frames: vec![16, 17, 18, 19],
-        looping: true,
-        fps: 5,
-    };
-
-    let body_move_animation = Animation {
-        frames: (20..27).collect(),
-        looping: true,
-        fps: 12,
-    };
-
-    let body_extending_animation = Animation {
-        frames: (28..31).collect(),
-        looping: true,
-        fps: 10,
-    };
-
-    body_animations.insert("Idle".to_string(), body_idle_animation);
-    body_animations.insert("Move".to_string(), body_move_animation);
-    body_animations.insert("Extending".to_string(), body_extending_animation);
-
     let body = commands.spawn((
         SpriteSheetBundle {
-            texture_atlas: texture_atlas_handle.clone(),
-            sprite: TextureAtlasSprite::new(13),
+            sprite: TextureAtlasSprite {
+                color,
+                ..TextureAtlasSprite::new(13)
+            },
             transform: Transform::from_translation(Vec3::ZERO),
             ..default()
         },
-        Animator {
-            animations: body_animations,
-            counter: 0.0,
-            current_animation: "Idle".to_string(),
-            prev_animation: "Idle".to_string(),
-            current_frame: 0,
-            paused: false,
-        },
-        Limb::new(LimbType::Body)
+        Animator::new(sheet.clone(), "Body", "Idle"),
+        AnimationParams::default(),
+        Limb::new(LimbType::Body),
+        marker.clone(),
     )).id();
 
     // Spawn hands
-    let mut hands_animations = HashMap::new();
-
-    let hands_idle_animation = Animation {
-        frames: vec![32, 33, 34, 35],
-        looping: true,
-        fps: 5,
-    };
-
-    let hands_move_animation = Animation {
-        frames: (36..43).collect(),
-        looping: true,
-        fps: 12,
-    };
-
-    let hands_extending_animation = Animation {
-        frames: (44..47).collect(),
-        looping: true,
-        fps: 10,
-    };
-
-    hands_animations.insert("Idle".to_string(), hands_idle_animation);
-    hands_animations.insert("Move".to_string(), hands_move_animation);
-    hands_animations.insert("Extending".to_string(), hands_extending_animation);
-
     let hands = commands.spawn((
         SpriteSheetBundle {
-            texture_atlas: texture_atlas_handle.clone(),
-            sprite: TextureAtlasSprite::new(13),
+            sprite: TextureAtlasSprite {
+                color,
+                ..TextureAtlasSprite::new(13)
+            },
             transform: Transform::from_translation(Vec3::ZERO),
             ..default()
         },
-        Animator {
-            animations: hands_animations,
-            counter: 0.0,
-            current_animation: "Idle".to_string(),
-            prev_animation: "Idle".to_string(),
-            current_frame: 0,
-            paused: false,
-        },
-        Limb::new(LimbType::Hands)
+        Animator::new(sheet.clone(), "Hands", "Idle"),
+        AnimationParams::default(),
+        Limb::new(LimbType::Hands),
+        marker.clone(),
     )).id();
 
     // Spawn extension
@@ -1136,45 +1114,21 @@ fn spawn_player(
             texture: asset_server.load("sprites/ruler_extension_part.png"),
             transform: Transform::from_xyz(0.5, 0., 0.),
             sprite: Sprite {
+                color,
                 rect: Some(Rect::new(0.0, 0.0, 12.0, 0.0)),
                 ..default()
             },
             ..default()
         },
         Limb::new(LimbType::Extension),
+        marker,
     )).id();
 
-    // Attach limbs to player xD
-    commands.entity(player_limbs)
+    commands.entity(ruler_limbs)
         .add_child(extension)
         .add_child(legs)
         .add_child(body)
         .add_child(hands);
 
-    commands.entity(player)
-        .add_child(player_limbs);
-
-    let dust_handle = asset_server.load("sprites/dust-sheet-copy.png");
-    let dust_texture_atlas = TextureAtlas::from_grid(
-        dust_handle,
-        Vec2::new(24.0, 24.0),
-        4,
-        1,
-        None,
-        None,
-    );
-
-    let dust_texture_atlas = texture_atlases.add(dust_texture_atlas);
-
-    commands.spawn((
-        Transform::from_xyz(0.0, 0.0, 2.0),
-        ParticleEmitter {
-            frames: vec![0, 1, 2, 3],
-            particle_lifetime: 0.4,
-            handle: dust_texture_atlas,
-            spawning: false,
-            spawn_timer: Timer::from_seconds(0.4, TimerMode::Once),
-        },
-        DustParticleEmitter {},
-    ));
+    ruler_limbs
 }
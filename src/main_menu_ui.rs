@@ -1,12 +1,12 @@
-use std::{time::Duration};
-
-use bevy::{prelude::{Component, ChildBuilder, AssetServer, Res, ButtonBundle, Color, TextBundle, default, BuildChildren, Query, Changed, Without, With, NodeBundle, Commands, ResMut, ImageBundle, AudioBundle, PlaybackSettings, AudioSink, AudioSinkPlayback}, ui::{Style, Val, UiRect, JustifyContent, AlignItems, BackgroundColor, UiImage, PositionType, FlexDirection, ZIndex, GridTrack, Display}, text::TextStyle, audio::PlaybackMode};
+use bevy::{prelude::{Component, ChildBuilder, AssetServer, Res, ButtonBundle, Color, TextBundle, default, BuildChildren, Query, Changed, With, NodeBundle, Commands, ResMut, ImageBundle, EventWriter}, ui::{Style, Val, UiRect, JustifyContent, AlignItems, BackgroundColor, UiImage, PositionType, FlexDirection, ZIndex, GridTrack, Display}, text::TextStyle};
 use bevy_ecs_ldtk::LevelSelection;
 use bevy_persistent::Persistent;
-use bevy_tweening::{Tween, EaseFunction, lens::UiPositionLens};
-use kt_common::components::{ui::{PlayButtonUi, MainColumnUi, LevelSelectColumnUi, LevelSelectButtonUi, TransitionColumnLeftUi, TransitionColumnRightUi, ButtonClickSound}, despawnable::Despawnable};
+use kt_common::{components::{ui::{PlayButtonUi, LevelSelectButtonUi, ButtonClickSound}, despawnable::Despawnable}, events::{TransitionKind, TransitionStyle, RequestTransition}};
+
+use kt_core::audio::{PlaySfx, Sfx};
+use kt_util::constants::LEVEL_COUNT;
 
-use crate::save_game::GameState;
+use crate::{save_game::{GameState, GameSave, LevelStats, SaveNotice}, menu_navigation::{Focusable, MenuPage, MenuFocus, MenuColumn, show_menu_page}, run_timer::format_time, save_slots::create_slot_column, settings::Settings, settings_menu::{create_settings_button, create_settings_page}};
 
 pub fn create_play_button(parent: &mut ChildBuilder, asset_server: &Res<AssetServer>) {
     parent
@@ -25,7 +25,8 @@ pub fn create_play_button(parent: &mut ChildBuilder, asset_server: &Res<AssetSer
                 image: UiImage::new(asset_server.load("sprites/button.png")),
                 ..default()
             },
-            PlayButtonUi {}
+            PlayButtonUi {},
+            Focusable { page: MenuPage::Main, order: 0 },
         ))
         .with_children(|parent| {
             parent.spawn(TextBundle::from_section(
@@ -41,34 +42,26 @@ pub fn create_play_button(parent: &mut ChildBuilder, asset_server: &Res<AssetSer
 
 pub fn handle_play_button_interactions(
     mut q_interaction: Query<&bevy::ui::Interaction, (Changed<bevy::ui::Interaction>, With<PlayButtonUi>)>,
-    mut q_main_column: Query<&mut Style, (With<MainColumnUi>, Without<LevelSelectColumnUi>)>,
-    mut q_level_select_column: Query<&mut Style, (With<LevelSelectColumnUi>, Without<MainColumnUi>)>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    mut q_columns: Query<(&MenuColumn, &mut Style)>,
+    mut menu_focus: ResMut<MenuFocus>,
+    mut ev_play_sfx: EventWriter<PlaySfx>,
 ) {
     for interaction in &mut q_interaction {
         if let bevy::ui::Interaction::Pressed = *interaction {
-            commands.spawn(AudioBundle {
-                source: asset_server.load("audio/btn_click.ogg"),
-                settings: PlaybackSettings {
-                    mode: PlaybackMode::Remove,
-                    ..default()
-                },
-            });
-
-            let mut main_column_style = q_main_column.single_mut();
-            let mut level_select_column_style = q_level_select_column.single_mut();
+            ev_play_sfx.send(PlaySfx(Sfx::ButtonClick));
 
-            main_column_style.left = Val::Percent(100.0);
-            level_select_column_style.right = Val::Auto;
+            show_menu_page(MenuPage::SlotSelect, &mut menu_focus, &mut q_columns);
         }
     }
 }
 
+// Goes back to the page `to`
 #[derive(Clone, Component, Debug, Default)]
-pub struct BackButtonUi {}
+pub struct BackButtonUi {
+    pub to: MenuPage,
+}
 
-pub fn create_back_button(parent: &mut ChildBuilder, asset_server: &Res<AssetServer>) {
+pub fn create_back_button(parent: &mut ChildBuilder, page: MenuPage, to: MenuPage, asset_server: &Res<AssetServer>) {
     parent
         .spawn((
             ButtonBundle {
@@ -85,7 +78,8 @@ pub fn create_back_button(parent: &mut ChildBuilder, asset_server: &Res<AssetSer
                 image: UiImage::new(asset_server.load("sprites/button.png")),
                 ..default()
             },
-            BackButtonUi {}
+            BackButtonUi { to },
+            Focusable { page, order: usize::MAX },
         ))
         .with_children(|parent| {
             parent.spawn(TextBundle::from_section(
@@ -100,37 +94,41 @@ pub fn create_back_button(parent: &mut ChildBuilder, asset_server: &Res<AssetSer
 }
 
 pub fn handle_back_button_interactions(
-    mut q_interaction: Query<&bevy::ui::Interaction, (Changed<bevy::ui::Interaction>, With<BackButtonUi>)>,
-    mut q_main_column: Query<&mut Style, (With<MainColumnUi>, Without<LevelSelectColumnUi>)>,
-    mut q_level_select_column: Query<&mut Style, (With<LevelSelectColumnUi>, Without<MainColumnUi>)>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    mut q_interaction: Query<(&bevy::ui::Interaction, &BackButtonUi), Changed<bevy::ui::Interaction>>,
+    mut q_columns: Query<(&MenuColumn, &mut Style)>,
+    mut menu_focus: ResMut<MenuFocus>,
+    mut ev_play_sfx: EventWriter<PlaySfx>,
 ) {
-    for interaction in &mut q_interaction {
+    for (interaction, back_button) in &mut q_interaction {
         if let bevy::ui::Interaction::Pressed = *interaction {
-            commands.spawn(AudioBundle {
-                source: asset_server.load("audio/btn_click.ogg"),
-                settings: PlaybackSettings {
-                    mode: PlaybackMode::Remove,
-                    ..default()
-                },
-            });
-
-            let mut main_column_style = q_main_column.single_mut();
-            let mut level_select_column_style = q_level_select_column.single_mut();
+            ev_play_sfx.send(PlaySfx(Sfx::ButtonClick));
 
-            level_select_column_style.right = Val::Percent(100.0);
-            main_column_style.left = Val::Auto;
+            show_menu_page(back_button.to, &mut menu_focus, &mut q_columns);
         }
     }
 }
 
-pub fn create_level_button(grid: &mut ChildBuilder, number: isize, asset_server: &Res<AssetServer>) {
+const COMPLETED_BADGE_COLOR: Color = Color::rgb(0.35, 0.8, 0.3);
+const ATTEMPTED_BADGE_COLOR: Color = Color::rgb(0.85, 0.3, 0.25);
+
+pub fn create_level_button(grid: &mut ChildBuilder, number: isize, stats: Option<&LevelStats>, asset_server: &Res<AssetServer>) {
     let level = format!("{:02}", number);
+    let best_time = stats.and_then(|stats| stats.best_time);
+
+    // Green once finished, red when it was tried but never finished
+    let badge_color = match stats {
+        Some(stats) if stats.completed => Some(COMPLETED_BADGE_COLOR),
+        Some(stats) if stats.attempts > 0 => Some(ATTEMPTED_BADGE_COLOR),
+        _ => None,
+    };
 
     grid
         .spawn(NodeBundle {
-            style: Style::default(),
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            },
             ..default()
         })
         .with_children(|parent| {
@@ -151,6 +149,7 @@ pub fn create_level_button(grid: &mut ChildBuilder, number: isize, asset_server:
                         ..default()
                     },
                     LevelSelectButtonUi { level: number },
+                    Focusable { page: MenuPage::LevelSelect, order: number as usize },
                 ))
                 .with_children(|button| {
                     button.spawn(TextBundle::from_section(
@@ -161,10 +160,52 @@ pub fn create_level_button(grid: &mut ChildBuilder, number: isize, asset_server:
                             color: Color::rgb(1.0, 1.0, 1.0),
                         },
                     ));
+
+                    if let Some(badge_color) = badge_color {
+                        button.spawn(NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                top: Val::Px(4.0),
+                                right: Val::Px(4.0),
+                                width: Val::Px(10.0),
+                                height: Val::Px(10.0),
+                                ..default()
+                            },
+                            background_color: BackgroundColor(badge_color),
+                            ..default()
+                        });
+                    }
                 });
+
+            parent.spawn(TextBundle::from_section(
+                best_time.map_or(String::new(), format_time),
+                TextStyle {
+                    font: asset_server.load("fonts/ThaleahFat.ttf"),
+                    font_size: 20.0,
+                    color: Color::rgb(0.96, 0.67, 0.1),
+                },
+            ));
         });
 }
 
+#[derive(Clone, Component, Debug, Default)]
+pub struct LevelGridUi {}
+
+// Why the picked slot couldn't be loaded as it was, empty if it could
+#[derive(Clone, Component, Debug, Default)]
+pub struct SaveNoticeUi {}
+
+// Rebuilt whenever another save slot is picked
+pub fn create_level_buttons(grid: &mut ChildBuilder, game_state: &GameState, asset_server: &Res<AssetServer>) {
+    for number in 1..=game_state.unlocked_levels {
+        create_level_button(grid, number, game_state.stats(number), asset_server);
+    }
+
+    for _ in game_state.unlocked_levels..LEVEL_COUNT {
+        create_disabled_level_button(grid, asset_server);
+    }
+}
+
 pub fn create_disabled_level_button(grid: &mut ChildBuilder, asset_server: &Res<AssetServer>) {
     grid
         .spawn(NodeBundle {
@@ -194,67 +235,22 @@ pub fn create_disabled_level_button(grid: &mut ChildBuilder, asset_server: &Res<
 
 pub fn handle_level_button_interactions(
     mut q_interaction: Query<(&bevy::ui::Interaction, &LevelSelectButtonUi), Changed<bevy::ui::Interaction>>,
-    mut q_transition_left: Query<&mut bevy_tweening::Animator<Style>, (With<TransitionColumnLeftUi>, Without<TransitionColumnRightUi>)>,
-    mut q_transition_right: Query<&mut bevy_tweening::Animator<Style>, (With<TransitionColumnRightUi>, Without<TransitionColumnLeftUi>)>,
-    mut game_state: ResMut<Persistent<GameState>>,
+    mut game_state: ResMut<GameSave>,
     mut level_selection: ResMut<LevelSelection>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    mut ev_play_sfx: EventWriter<PlaySfx>,
+    mut ev_request_transition: EventWriter<RequestTransition>,
 ) {
     for (interaction, level_select_button) in &mut q_interaction {
         if let bevy::ui::Interaction::Pressed = *interaction {
-            commands.spawn(AudioBundle {
-                source: asset_server.load("audio/SFX_powerUp10.ogg"),
-                settings: PlaybackSettings {
-                    mode: PlaybackMode::Remove,
-                    ..default()
-                },
-            });
+            ev_play_sfx.send(PlaySfx(Sfx::LevelStart));
 
-            let mut transition_left_column_animator = q_transition_left.single_mut();
-            let mut transition_right_column_animator = q_transition_right.single_mut();
-
-            let tween = Tween::new(
-                EaseFunction::QuarticInOut,
-                Duration::from_secs_f32(0.5),
-                UiPositionLens {
-                    start: UiRect {
-                        left: Val::Percent(100.0),
-                        top: Val::Auto,
-                        right: Val::Auto,
-                        bottom: Val::Auto,
-                    },
-                    end: UiRect {
-                        left: Val::Percent(40.0),
-                        top: Val::Auto,
-                        right: Val::Auto,
-                        bottom: Val::Auto,
-                    },
-                },
-            ).with_completed_event(3);
-
-            transition_left_column_animator.set_tweenable(tween);
-
-            let tween = Tween::new(
-                EaseFunction::QuarticInOut,
-                Duration::from_secs_f32(0.5),
-                UiPositionLens {
-                    start: UiRect {
-                        right: Val::Percent(100.0),
-                        top: Val::Auto,
-                        left: Val::Auto,
-                        bottom: Val::Auto,
-                    },
-                    end: UiRect {
-                        right: Val::Percent(40.0),
-                        top: Val::Auto,
-                        left: Val::Auto,
-                        bottom: Val::Auto,
-                    },
-                },
-            );
+            ev_request_transition.send(RequestTransition {
+                kind: TransitionKind::EnterGame,
+                style: TransitionStyle::Fade,
+                duration: 0.5,
+                focus: None,
+            });
 
-            transition_right_column_animator.set_tweenable(tween);
             game_state.current_level = level_select_button.level;
             *level_selection = LevelSelection::Index(level_select_button.level as usize - 1);
             // *level_selection = LevelSelection::Index(5);
@@ -265,7 +261,9 @@ pub fn handle_level_button_interactions(
 pub fn setup_menu(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    game_state: Res<Persistent<GameState>>,
+    game_state: Res<GameSave>,
+    save_notice: Res<SaveNotice>,
+    settings: Res<Persistent<Settings>>,
 ) {
     let main_menu_ui_container = (
         NodeBundle {
@@ -320,7 +318,7 @@ pub fn setup_menu(
             z_index: ZIndex::Global(1),
             ..default()
         },
-        MainColumnUi {},
+        MenuColumn { page: MenuPage::Main },
     );
 
     let level_select_column = (
@@ -338,21 +336,39 @@ pub fn setup_menu(
             z_index: ZIndex::Global(1),
             ..default()
         },
-        LevelSelectColumnUi {},
+        MenuColumn { page: MenuPage::LevelSelect },
     );
 
-    let grid_container = NodeBundle {
-        style: Style {
-            display: Display::Grid,
-            grid_template_columns: vec![GridTrack::flex(1.0), GridTrack::flex(1.0), GridTrack::flex(1.0), GridTrack::flex(1.0)],
-            margin: UiRect::vertical(Val::Px(32.0)),
+    let settings_column = (
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(80.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                right: Val::Percent(100.0),
+                ..default()
+            },
+            z_index: ZIndex::Global(1),
             ..default()
         },
-        ..default()
-    };
+        MenuColumn { page: MenuPage::Settings },
+    );
 
-    let unlocked_levels = game_state.unlocked_levels;
-    let locked_levels = 12 - game_state.unlocked_levels;
+    let grid_container = (
+        NodeBundle {
+            style: Style {
+                display: Display::Grid,
+                grid_template_columns: vec![GridTrack::flex(1.0), GridTrack::flex(1.0), GridTrack::flex(1.0), GridTrack::flex(1.0)],
+                margin: UiRect::vertical(Val::Px(32.0)),
+                ..default()
+            },
+            ..default()
+        },
+        LevelGridUi {},
+    );
 
     commands
         .spawn(main_menu_ui_container)
@@ -365,8 +381,26 @@ pub fn setup_menu(
                     parent.spawn(logo);
 
                     create_play_button(parent, &asset_server);
+                    create_settings_button(parent, &asset_server);
+
+                    if let Some(notice) = &save_notice.0 {
+                        parent.spawn(TextBundle::from_section(
+                            notice.clone(),
+                            TextStyle {
+                                font: asset_server.load("fonts/ThaleahFat.ttf"),
+                                font_size: 24.0,
+                                color: Color::rgb(0.96, 0.67, 0.1),
+                            },
+                        ));
+                    }
                 });
 
+            create_slot_column(parent, &asset_server);
+
+            parent
+                .spawn(settings_column)
+                .with_children(|parent| create_settings_page(parent, &settings.audio, MenuPage::Main, &asset_server));
+
             parent
                 .spawn(level_select_column)
                 .with_children(|parent| {
@@ -379,18 +413,23 @@ pub fn setup_menu(
                         }
                     ));
 
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: asset_server.load("fonts/ThaleahFat.ttf"),
+                                font_size: 24.0,
+                                color: Color::rgb(0.96, 0.67, 0.1),
+                            },
+                        ),
+                        SaveNoticeUi {},
+                    ));
+
                     parent
                         .spawn(grid_container)
-                        .with_children(|grid| {
-                            for number in 1..=unlocked_levels {
-                                create_level_button(grid, number, &asset_server);
-                            }
-                            for _ in 1..=locked_levels {
-                                create_disabled_level_button(grid, &asset_server);
-                            }
-                        });
+                        .with_children(|grid| create_level_buttons(grid, &game_state, &asset_server));
 
-                    create_back_button(parent, &asset_server);
+                    create_back_button(parent, MenuPage::LevelSelect, MenuPage::SlotSelect, &asset_server);
                 });
         });
 
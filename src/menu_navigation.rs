@@ -0,0 +1,146 @@
+use bevy::{prelude::{Component, Resource, Res, ResMut, Query, Entity, Input, Color}, ui::{Interaction, BackgroundColor, Style, Val}};
+use kt_core::input::InputAction;
+
+const FOCUSED_BUTTON_COLOR: Color = Color::rgb(1.0, 0.8, 0.35);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MenuPage {
+    #[default]
+    Main,
+    SlotSelect,
+    LevelSelect,
+    Pause,
+    Settings,
+}
+
+impl MenuPage {
+    fn columns(&self) -> usize {
+        match self {
+            MenuPage::SlotSelect => 3,
+            MenuPage::LevelSelect => 4,
+            MenuPage::Settings => 2,
+            _ => 1,
+        }
+    }
+}
+
+// Full screen column holding the buttons of a page, only the one of the
+// current page is on screen.
+#[derive(Clone, Component, Debug)]
+pub struct MenuColumn {
+    pub page: MenuPage,
+}
+
+// Buttons that can be reached with the d-pad/arrow keys. `order` is the
+// position in the page, read left to right, top to bottom.
+#[derive(Clone, Component, Debug)]
+pub struct Focusable {
+    pub page: MenuPage,
+    pub order: usize,
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct MenuFocus {
+    pub page: MenuPage,
+    pub index: usize,
+    pressed: Option<Entity>,
+}
+
+impl MenuFocus {
+    pub fn show_page(&mut self, page: MenuPage) {
+        self.page = page;
+        self.index = 0;
+    }
+}
+
+pub fn show_menu_page(
+    page: MenuPage,
+    menu_focus: &mut MenuFocus,
+    q_columns: &mut Query<(&MenuColumn, &mut Style)>,
+) {
+    menu_focus.show_page(page);
+
+    for (column, mut style) in q_columns.iter_mut() {
+        if column.page == page {
+            style.left = Val::Auto;
+            style.right = Val::Auto;
+        } else if column.page == MenuPage::Main {
+            style.left = Val::Percent(100.0);
+        } else {
+            style.right = Val::Percent(100.0);
+        }
+    }
+}
+
+pub fn reset_menu_focus(
+    mut menu_focus: ResMut<MenuFocus>,
+) {
+    *menu_focus = MenuFocus::default();
+}
+
+pub fn navigate_menu_focus(
+    actions: Res<Input<InputAction>>,
+    mut menu_focus: ResMut<MenuFocus>,
+    mut q_focusable: Query<(Entity, &Focusable, &mut Interaction, &mut BackgroundColor)>,
+) {
+    // Buttons "pressed" with the gamepad have to be released manually,
+    // bevy only does that for mouse clicks.
+    if let Some(entity) = menu_focus.pressed.take() {
+        if let Ok((_, _, mut interaction, _)) = q_focusable.get_mut(entity) {
+            *interaction = Interaction::None;
+        }
+    }
+
+    let mut entries: Vec<(usize, Entity)> = q_focusable
+        .iter()
+        .filter(|(_, focusable, _, _)| focusable.page == menu_focus.page)
+        .map(|(entity, focusable, _, _)| (focusable.order, entity))
+        .collect();
+
+    if entries.is_empty() {
+        return;
+    }
+
+    entries.sort_by_key(|(order, _)| *order);
+
+    let columns = menu_focus.page.columns();
+    let last = entries.len() - 1;
+    let mut index = menu_focus.index.min(last);
+
+    if actions.just_pressed(InputAction::MenuRight) {
+        index = (index + 1).min(last);
+    }
+
+    if actions.just_pressed(InputAction::MenuLeft) {
+        index = index.saturating_sub(1);
+    }
+
+    if actions.just_pressed(InputAction::MenuDown) {
+        index = (index + columns).min(last);
+    }
+
+    if actions.just_pressed(InputAction::MenuUp) {
+        index = index.saturating_sub(columns);
+    }
+
+    menu_focus.index = index;
+
+    for (position, (_, entity)) in entries.iter().enumerate() {
+        let (_, _, mut interaction, mut background_color) = match q_focusable.get_mut(*entity) {
+            Ok(focusable) => focusable,
+            Err(..) => continue,
+        };
+
+        if position != index {
+            background_color.0 = Color::WHITE;
+            continue;
+        }
+
+        background_color.0 = FOCUSED_BUTTON_COLOR;
+
+        if actions.just_pressed(InputAction::Confirm) {
+            *interaction = Interaction::Pressed;
+            menu_focus.pressed = Some(*entity);
+        }
+    }
+}
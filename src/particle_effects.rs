@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use bevy::{prelude::{Resource, FromWorld, World, AssetServer, Assets, Vec2, Color, EventReader, EventWriter, Query, Transform, Res}, sprite::TextureAtlas};
+use kt_common::events::{PinCollected, PlayerDied};
+use kt_core::particle::{ParticleEffect, SpawnShape, Curve, EmitParticles};
+
+const SPARKLE_COUNT: usize = 10;
+const DEATH_BURST_COUNT: usize = 16;
+
+// Every effect is cut from the dust sheet and tinted
+#[derive(Resource, Debug, Clone)]
+pub struct ParticleEffects {
+    pub dust: Arc<ParticleEffect>,
+    pub sparkle: Arc<ParticleEffect>,
+    pub death: Arc<ParticleEffect>,
+    pub shavings: Arc<ParticleEffect>,
+}
+
+impl FromWorld for ParticleEffects {
+    fn from_world(world: &mut World) -> Self {
+        let dust_handle = world.resource::<AssetServer>().load("sprites/dust-sheet-copy.png");
+        let dust_texture_atlas = TextureAtlas::from_grid(
+            dust_handle,
+            Vec2::new(24.0, 24.0),
+            4,
+            1,
+            None,
+            None,
+        );
+
+        let atlas = world.resource_mut::<Assets<TextureAtlas>>().add(dust_texture_atlas);
+
+        let dust = ParticleEffect {
+            lifetime_jitter: 0.05,
+            shape: SpawnShape::Rect { half_size: Vec2::new(6.0, 1.0) },
+            velocity_min: Vec2::new(-30.0, 0.0),
+            velocity_max: Vec2::new(30.0, 15.0),
+            drag: 4.0,
+            alpha: Curve::linear(1.0, 0.0),
+            scale: Curve::linear(1.0, 0.6),
+            ..ParticleEffect::new(atlas.clone(), vec![0, 1, 2, 3], 0.4)
+        };
+
+        let sparkle = ParticleEffect {
+            lifetime_jitter: 0.15,
+            shape: SpawnShape::Circle { radius: 4.0 },
+            velocity_min: Vec2::new(-60.0, -60.0),
+            velocity_max: Vec2::new(60.0, 60.0),
+            drag: 3.0,
+            color: Curve::linear(Color::WHITE, Color::rgb(1.0, 0.85, 0.3)),
+            alpha: Curve { keys: vec![(0.0, 1.0), (0.6, 1.0), (1.0, 0.0)] },
+            scale: Curve::linear(0.5, 0.1),
+            ..ParticleEffect::new(atlas.clone(), vec![3], 0.5)
+        };
+
+        let death = ParticleEffect {
+            lifetime_jitter: 0.2,
+            shape: SpawnShape::Circle { radius: 6.0 },
+            velocity_min: Vec2::new(-120.0, -60.0),
+            velocity_max: Vec2::new(120.0, 160.0),
+            gravity: Vec2::new(0.0, -300.0),
+            drag: 1.0,
+            color: Curve::linear(Color::WHITE, Color::GRAY),
+            alpha: Curve::linear(1.0, 0.0),
+            scale: Curve::linear(0.8, 0.3),
+            ..ParticleEffect::new(atlas.clone(), vec![0, 1, 2, 3], 0.6)
+        };
+
+        let shavings = ParticleEffect {
+            lifetime_jitter: 0.2,
+            shape: SpawnShape::Rect { half_size: Vec2::new(4.0, 2.0) },
+            velocity_min: Vec2::new(-20.0, 20.0),
+            velocity_max: Vec2::new(20.0, 50.0),
+            gravity: Vec2::new(0.0, -200.0),
+            color: Curve::constant(Color::rgb(0.8, 0.6, 0.35)),
+            alpha: Curve { keys: vec![(0.0, 1.0), (0.7, 1.0), (1.0, 0.0)] },
+            scale: Curve::constant(0.3),
+            ..ParticleEffect::new(atlas, vec![3], 0.5)
+        };
+
+        ParticleEffects {
+            dust: Arc::new(dust),
+            sparkle: Arc::new(sparkle),
+            death: Arc::new(death),
+            shavings: Arc::new(shavings),
+        }
+    }
+}
+
+pub fn sparkle_on_pin_collected(
+    mut ev_pin_collected: EventReader<PinCollected>,
+    mut ev_emit_particles: EventWriter<EmitParticles>,
+    q_transforms: Query<&Transform>,
+    effects: Res<ParticleEffects>,
+) {
+    for event in ev_pin_collected.iter() {
+        let transform = match q_transforms.get(event.pin) {
+            Ok(transform) => transform,
+            Err(..) => continue,
+        };
+
+        ev_emit_particles.send(EmitParticles {
+            effect: effects.sparkle.clone(),
+            position: transform.translation,
+            count: SPARKLE_COUNT,
+        });
+    }
+}
+
+pub fn burst_on_player_died(
+    mut ev_player_died: EventReader<PlayerDied>,
+    mut ev_emit_particles: EventWriter<EmitParticles>,
+    q_transforms: Query<&Transform>,
+    effects: Res<ParticleEffects>,
+) {
+    for event in ev_player_died.iter() {
+        let transform = match q_transforms.get(event.player) {
+            Ok(transform) => transform,
+            Err(..) => continue,
+        };
+
+        ev_emit_particles.send(EmitParticles {
+            effect: effects.death.clone(),
+            position: transform.translation,
+            count: DEATH_BURST_COUNT,
+        });
+    }
+}
@@ -0,0 +1,244 @@
+use bevy::{prelude::{Component, Bundle, ChildBuilder, AssetServer, Res, ResMut, ButtonBundle, Color, TextBundle, default, BuildChildren, Query, Changed, With, Or, NodeBundle, Commands, Entity, DespawnRecursiveExt, States, State, NextState, Input, Handle, Time, Vec2, EventWriter}, ui::{Style, Val, UiRect, JustifyContent, AlignItems, BackgroundColor, UiImage, PositionType, FlexDirection, ZIndex, Interaction}, text::TextStyle};
+use bevy_ecs_ldtk::{LevelSelection, assets::LdtkAsset};
+use bevy_persistent::Persistent;
+use bevy_rapier2d::prelude::RapierConfiguration;
+use kt_common::{components::{despawnable::Despawnable, player::Player, velocity::Velocity, ui::InGameUi}, events::{TransitionStyle, LevelRestarted}};
+use kt_core::{audio::{PlaySfx, Sfx}, input::InputAction, particle::{Particle, ParticleEmitter}, replay::ReplayState};
+
+use crate::{AppState, save_game::GameSave, run_timer::RunTimer, replays::{LevelChecksum, start_recording}, menu_navigation::{Focusable, MenuPage, MenuFocus, MenuColumn, show_menu_page}, settings::Settings, settings_menu::create_settings_page, screen_transitions::{ScreenTransition, respawn_transition}};
+
+// Only meaningful in `AppState::InGame`, always `Running` everywhere else
+#[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseAction {
+    Resume,
+    Restart,
+    Settings,
+    Quit,
+}
+
+#[derive(Clone, Component, Debug, Default)]
+pub struct PauseMenuUi {}
+
+#[derive(Clone, Component, Debug)]
+pub struct PauseButtonUi {
+    pub action: PauseAction,
+}
+
+fn create_pause_button(parent: &mut ChildBuilder, label: &str, page: MenuPage, order: usize, button: impl Bundle, asset_server: &Res<AssetServer>) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    height: Val::Px(65.0),
+                    width: Val::Px(350.0),
+                    margin: UiRect::all(Val::Px(8.0)),
+                    padding: UiRect::horizontal(Val::Px(6.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::WHITE),
+                image: UiImage::new(asset_server.load("sprites/button.png")),
+                ..default()
+            },
+            button,
+            Focusable { page, order },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font: asset_server.load("fonts/ThaleahFat.ttf"),
+                    font_size: 40.0,
+                    color: Color::rgb(1.0, 1.0, 1.0),
+                },
+            ));
+        });
+}
+
+fn pause_column(page: MenuPage, hidden: bool) -> (NodeBundle, MenuColumn) {
+    (
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                right: if hidden { Val::Percent(100.0) } else { Val::Auto },
+                ..default()
+            },
+            ..default()
+        },
+        MenuColumn { page },
+    )
+}
+
+pub fn toggle_pause(
+    actions: Res<Input<InputAction>>,
+    pause_state: Res<State<PauseState>>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+) {
+    if !actions.just_pressed(InputAction::Pause) {
+        return;
+    }
+
+    match pause_state.get() {
+        PauseState::Running => next_pause_state.set(PauseState::Paused),
+        PauseState::Paused => next_pause_state.set(PauseState::Running),
+    }
+}
+
+// Stopping `Time` freezes everything driven by it: fixed ticks (movement and
+// Rapier), tweens, timers and particles.
+pub fn pause_game(
+    mut time: ResMut<Time>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut menu_focus: ResMut<MenuFocus>,
+    settings: Res<Persistent<Settings>>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    time.pause();
+    rapier_config.physics_pipeline_active = false;
+    menu_focus.show_page(MenuPage::Pause);
+
+    let overlay = (
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            background_color: BackgroundColor(Color::rgba(0.0, 0.0, 0.0, 0.6)),
+            z_index: ZIndex::Global(50),
+            ..default()
+        },
+        PauseMenuUi {},
+    );
+
+    commands
+        .spawn(overlay)
+        .with_children(|parent| {
+            parent
+                .spawn(pause_column(MenuPage::Pause, false))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Paused",
+                        TextStyle {
+                            font: asset_server.load("fonts/ThaleahFat.ttf"),
+                            font_size: 62.0,
+                            color: Color::rgb(0.96, 0.67, 0.1),
+                        }
+                    ));
+
+                    create_pause_button(parent, "resume", MenuPage::Pause, 0, PauseButtonUi { action: PauseAction::Resume }, &asset_server);
+                    create_pause_button(parent, "restart level", MenuPage::Pause, 1, PauseButtonUi { action: PauseAction::Restart }, &asset_server);
+                    create_pause_button(parent, "settings", MenuPage::Pause, 2, PauseButtonUi { action: PauseAction::Settings }, &asset_server);
+                    create_pause_button(parent, "quit to menu", MenuPage::Pause, 3, PauseButtonUi { action: PauseAction::Quit }, &asset_server);
+                });
+
+            parent
+                .spawn(pause_column(MenuPage::Settings, true))
+                .with_children(|parent| create_settings_page(parent, &settings.audio, MenuPage::Pause, &asset_server));
+        });
+}
+
+pub fn resume_game(
+    mut time: ResMut<Time>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    q_pause_menu: Query<Entity, With<PauseMenuUi>>,
+    mut commands: Commands,
+) {
+    time.unpause();
+    rapier_config.physics_pipeline_active = true;
+
+    for pause_menu in q_pause_menu.iter() {
+        commands.entity(pause_menu).despawn_recursive();
+    }
+}
+
+pub fn handle_pause_button_interactions(
+    mut q_interaction: Query<(&Interaction, &PauseButtonUi), Changed<Interaction>>,
+    mut q_columns: Query<(&MenuColumn, &mut Style)>,
+    mut q_player: Query<(&mut Player, &mut Velocity)>,
+    mut menu_focus: ResMut<MenuFocus>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    game_state: Res<GameSave>,
+    mut run_timer: ResMut<RunTimer>,
+    mut replay_state: ResMut<ReplayState>,
+    level_selection: Res<LevelSelection>,
+    level_checksum: Res<LevelChecksum>,
+    mut ev_play_sfx: EventWriter<PlaySfx>,
+    mut screen_transition: ResMut<ScreenTransition>,
+    mut ev_level_restarted: EventWriter<LevelRestarted>,
+) {
+    for (interaction, pause_button) in &mut q_interaction {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        ev_play_sfx.send(PlaySfx(Sfx::ButtonClick));
+
+        match pause_button.action {
+            PauseAction::Resume => next_pause_state.set(PauseState::Running),
+            PauseAction::Restart => {
+                // Same as getting hit, the player is sent back to the spawn
+                // point and `reset_level_after_restart` resets the level once
+                // the transition covers the screen
+                for (mut player, mut velocity) in q_player.iter_mut() {
+                    player.is_respawning = true;
+                    velocity.current = Vec2::ZERO;
+                }
+
+                run_timer.start_level(game_state.current_level);
+                screen_transition.request(respawn_transition(TransitionStyle::Dissolve, None));
+                ev_level_restarted.send(LevelRestarted);
+
+                let recording = matches!(*replay_state, ReplayState::Recording(..));
+
+                if let (true, LevelSelection::Index(index)) = (recording, &*level_selection) {
+                    start_recording(&mut replay_state, *index as u32, level_checksum.0);
+                }
+
+                next_pause_state.set(PauseState::Running);
+            },
+            PauseAction::Settings => show_menu_page(MenuPage::Settings, &mut menu_focus, &mut q_columns),
+            PauseAction::Quit => {
+                game_state.persist().ok();
+                run_timer.running = false;
+                *replay_state = ReplayState::Idle;
+
+                next_pause_state.set(PauseState::Running);
+                next_app_state.set(AppState::MainMenu);
+            },
+        }
+    }
+}
+
+// Everything `InGame` spawned, so entering it again starts from scratch
+pub fn cleanup_in_game(
+    q_entities: Query<Entity, Or<(
+        With<Despawnable>,
+        With<Handle<LdtkAsset>>,
+        With<Player>,
+        With<ParticleEmitter>,
+        With<Particle>,
+        With<InGameUi>,
+    )>>,
+    mut commands: Commands,
+) {
+    for entity in q_entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -1,26 +1,13 @@
-use std::time::Duration;
+use std::{time::Duration, collections::{HashSet, HashMap}};
 
-use bevy::{prelude::{Query, Transform, Entity, Commands, Res, AssetServer, Added, Vec3, SpatialBundle, With, Without, Color, default, Vec2, BuildChildren, Image, ResMut, EventWriter, Assets, DespawnRecursiveExt}, sprite::{SpriteBundle, Sprite, TextureAtlas, TextureAtlasSprite, SpriteSheetBundle}, render::render_resource::Texture, time::{Timer, TimerMode}};
-use bevy_persistent::Persistent;
-use bevy_rapier2d::prelude::{Collider, RigidBody, Sensor, GravityScale};
-use bevy_tweening::{Tween, EaseFunction, lens::{TransformPositionLens, SpriteColorLens}, RepeatCount};
-use kt_common::{components::{platform::Platform, despawnable::Despawnable, ldtk::{ElevatorInstance, SpawnPoint, WallDefinition, PointTo, Elevator, Level, PlatformInstance, SharpenerInstance, PinInstance, ExitBundle, ExitInstance, RequiredKeys, Exit, HitComponent, TextInstance, Value}, player::Player, pin::Pin, sharpener::Sharpener, interaction::Interaction}, events::PinUiUpdated};
-use kt_util::constants::{Z_INDEX_PENCIL_BOX, PLAYER_HIT_RESPAWN_TIME};
+use bevy::{prelude::{Query, Transform, Entity, Commands, Res, AssetServer, Added, Vec3, SpatialBundle, With, Without, Color, default, Vec2, BuildChildren, Image, Handle, ResMut, EventWriter, Assets, DespawnRecursiveExt, Quat}, sprite::{SpriteBundle, Sprite, TextureAtlas, TextureAtlasSprite, SpriteSheetBundle, Anchor}, render::render_resource::Texture, time::{Timer, TimerMode}};
+use bevy_rapier2d::prelude::{Collider, RigidBody, Sensor, GravityScale, CollisionGroups, Group};
+use bevy_tweening::{Tween, EaseFunction, lens::{TransformPositionLens, TransformRotateZLens, SpriteColorLens}, RepeatCount};
+use kt_common::{components::{platform::Platform, mover::{Mover, MoverMode, MoverEasing}, despawnable::Despawnable, ldtk::{ElevatorInstance, SpawnPoint, WallDefinition, PointTo, Level, MoverInstance, PlatformInstance, SharpenerInstance, PinInstance, ExitBundle, ExitInstance, RequiredKeys, Exit, HitComponent, TextInstance, Value, CameraZoneInstance, OneWayPlatformDefinition, SlopeDefinition, SlopeKind}, one_way_platform::{OneWayPlatform, ONE_WAY_PLATFORM_GROUP}, player::Player, pin::Pin, sharpener::Sharpener, stapler::{Stapler, StaplerJaw}, wiring::{WireId, Switch, SwitchKind, Door}, interaction::Interaction}, events::PinUiUpdated, tile_colliders::{Point, wall_colliders, one_way_platform_colliders, slope_colliders}};
+use kt_core::{particle::ParticleEmitter, camera::{CameraZone, CameraZoneMode}, physics::TransformInterpolation};
+use kt_util::constants::{ELEVATOR_SPEED, TILE_SIZE, Z_INDEX_PENCIL_BOX, PLAYER_HIT_RESPAWN_TIME, STAPLER_OPEN_ANGLE};
 
-use crate::save_game::GameState;
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-pub struct Point {
-    pub x: i32,
-    pub y: i32,
-}
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub struct Rectangle {
-    pub top_left: Point,
-    pub width: usize,
-    pub height: usize,
-}
+use crate::{save_game::GameSave, particle_effects::ParticleEffects, wiring::SWITCH_OFF_COLOR};
 
 pub fn process_text(
     q_entity: Query<(&Transform, Entity, &Value), Added<TextInstance>>,
@@ -110,47 +97,80 @@ pub fn process_platform(
 }
 
 pub fn process_elevator(
-    q_entity: Query<(&Transform, &Level, Entity), Added<ElevatorInstance>>,
+    q_entity: Query<(&Transform, &Level, &WireId, Entity), Added<ElevatorInstance>>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
 ) {
     let texture_handle = asset_server.load("sprites/elevator.png");
-    for (transform, level, entity) in q_entity.iter() {
+    for (transform, level, wire_id, entity) in q_entity.iter() {
 
         commands
             .entity(entity)
             .despawn_recursive();
 
-        commands.spawn((
-            SpriteBundle {
-                transform: Transform::from_xyz(
-                    transform.translation.x,
-                    transform.translation.y,
-                    0.0,
-                ),
-                texture: texture_handle.clone(),
-                ..default()
-            },
-            GravityScale(0.0),
-            Collider::cuboid(16.0, 5.5),
-            RigidBody::KinematicPositionBased,
-            Despawnable {},
-            Elevator {
-                direction: Vec2::new(0.0, 0.3),
-                initial_position: transform.translation.truncate(),
-            },
-            level.clone(),
-        ));
+        let mut mover = Mover::new(
+            vec![Vec2::ZERO, Vec2::new(0.0, level.0 as f32 * TILE_SIZE)],
+            MoverMode::PingPong,
+            ELEVATOR_SPEED,
+            MoverEasing::Linear,
+            0.0,
+            false,
+        );
+        mover.origin = transform.translation.truncate();
+
+        spawn_mover(&mut commands, texture_handle.clone(), mover, wire_id.clone());
     }
 }
 
+pub fn process_mover(
+    q_entity: Query<(&Transform, &MoverInstance, &WireId, Entity), Added<MoverInstance>>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let texture_handle = asset_server.load("sprites/elevator.png");
+    for (transform, mover_instance, wire_id, entity) in q_entity.iter() {
+
+        commands
+            .entity(entity)
+            .despawn_recursive();
+
+        let mut mover = mover_instance.mover.clone();
+        mover.origin = transform.translation.truncate();
+
+        spawn_mover(&mut commands, texture_handle.clone(), mover, wire_id.clone());
+    }
+}
+
+fn spawn_mover(commands: &mut Commands, texture_handle: Handle<Image>, mover: Mover, wire_id: WireId) {
+    commands.spawn((
+        SpriteBundle {
+            transform: Transform::from_xyz(
+                mover.origin.x,
+                mover.origin.y,
+                0.0,
+            ),
+            texture: texture_handle,
+            ..default()
+        },
+        GravityScale(0.0),
+        Collider::cuboid(16.0, 5.5),
+        RigidBody::KinematicPositionBased,
+        // Moved on the fixed tick, so drawn between ticks like the player
+        TransformInterpolation::new(mover.origin.extend(0.0)),
+        Despawnable {},
+        mover,
+        wire_id,
+    ));
+}
+
 pub fn process_sharpener(
-    q_entity: Query<(&Transform, &PointTo, Entity), Added<SharpenerInstance>>,
+    q_entity: Query<(&Transform, &PointTo, &WireId, Entity), Added<SharpenerInstance>>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    particle_effects: Res<ParticleEffects>,
 ) {
     let texture_handle = asset_server.load("sprites/sharpener.png");
-    for (transform, point_to, entity) in q_entity.iter() {
+    for (transform, point_to, wire_id, entity) in q_entity.iter() {
 
         commands
             .entity(entity)
@@ -184,7 +204,99 @@ pub fn process_sharpener(
             Sharpener {},
             HitComponent {},
             Interaction::default(),
+            wire_id.clone(),
             bevy_tweening::Animator::new(tween),
+            // Shavings trail behind it while it moves
+            ParticleEmitter::new(particle_effects.shavings.clone(), 1, Timer::from_seconds(0.25, TimerMode::Repeating)),
+        ));
+    }
+}
+
+// There's no stapler art yet, it's drawn as a base with a jaw hinged at the
+// back that `cycle_staplers` swings shut
+pub fn process_stapler(
+    q_entity: Query<Entity, Added<Stapler>>,
+    mut commands: Commands,
+) {
+    for entity in q_entity.iter() {
+        let open_angle = STAPLER_OPEN_ANGLE.to_radians();
+        let idle = Tween::new(
+            EaseFunction::SineInOut,
+            Duration::from_secs_f32(0.0),
+            TransformRotateZLens {
+                start: open_angle,
+                end: open_angle,
+            }
+        );
+
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgb_u8(38, 43, 68),
+                    custom_size: Some(Vec2::new(20.0, 6.0)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(0.0, -9.0, 0.0),
+                ..default()
+            });
+
+            parent.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgb_u8(190, 74, 47),
+                        custom_size: Some(Vec2::new(20.0, 4.0)),
+                        anchor: Anchor::CenterLeft,
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(-10.0, -4.0, 0.1)
+                        .with_rotation(Quat::from_rotation_z(open_angle)),
+                    ..default()
+                },
+                StaplerJaw {},
+                bevy_tweening::Animator::new(idle),
+            ));
+        });
+    }
+}
+
+// No art for these yet, they're plain rects that light up while on
+pub fn process_switch(
+    q_entity: Query<(&Switch, Entity), Added<Switch>>,
+    mut commands: Commands,
+) {
+    for (switch, entity) in q_entity.iter() {
+        let size = match switch.kind {
+            SwitchKind::Button => Vec2::new(12.0, 8.0),
+            SwitchKind::PressurePlate => Vec2::new(20.0, 6.0),
+            SwitchKind::Lever => Vec2::new(4.0, 16.0),
+        };
+
+        commands.entity(entity).insert((
+            Sprite {
+                color: SWITCH_OFF_COLOR,
+                custom_size: Some(size),
+                ..default()
+            },
+            Handle::<Image>::default(),
+        ));
+    }
+}
+
+pub fn process_door(
+    q_entity: Query<(&Door, Entity), Added<Door>>,
+    mut commands: Commands,
+) {
+    for (door, entity) in q_entity.iter() {
+        commands.entity(entity).insert((
+            Sprite {
+                color: Color::rgb_u8(115, 62, 57),
+                custom_size: Some(door.size),
+                ..default()
+            },
+            Handle::<Image>::default(),
+            Collider::cuboid(door.size.x / 2.0, door.size.y / 2.0),
+            RigidBody::Fixed,
+            CollisionGroups::default(),
         ));
     }
 }
@@ -281,7 +393,7 @@ pub fn process_spawn_point(
 pub fn process_exit (
     q_entity: Query<(&Transform, &RequiredKeys, Entity), Added<ExitInstance>>,
     mut commands: Commands,
-    mut game_state: ResMut<Persistent<GameState>>,
+    mut game_state: ResMut<GameSave>,
     mut ev_pin_pickup: EventWriter<PinUiUpdated>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     asset_server: Res<AssetServer>,
@@ -327,100 +439,87 @@ pub fn process_exit (
     }
 }
 
+pub fn process_camera_zone(
+    q_entity: Query<(&CameraZoneInstance, Entity), Added<CameraZoneInstance>>,
+    mut commands: Commands,
+) {
+    for (instance, entity) in q_entity.iter() {
+        let mode = if instance.lock { CameraZoneMode::Lock } else { CameraZoneMode::Clamp };
+
+        commands.entity(entity).insert(CameraZone {
+            size: instance.size,
+            mode,
+        });
+    }
+}
+
 pub fn setup_walls(
     mut commands: Commands,
     q_walls: Query<(&Transform, Entity), With<WallDefinition>>,
+    q_slopes: Query<(&Transform, &SlopeDefinition, Entity)>,
 ) {
-    let mut points: Vec<Point> = vec![];
+    let mut walls: HashSet<Point> = HashSet::new();
+    let mut slopes: HashMap<Point, SlopeKind> = HashMap::new();
 
-    for (transform, entity) in q_walls.iter() {
+    for (transform, slope, entity) in q_slopes.iter() {
         commands
             .entity(entity)
-            .remove::<RigidBody>()
-            .remove::<Collider>()
-            .remove::<WallDefinition>();
+            .remove::<SlopeDefinition>();
 
-        points.push(
-            Point {
-                x: (transform.translation.x / 24.0) as i32,
-                y: (transform.translation.y / 24.0) as i32,
-            }
-        );
+        slopes.insert(Point::from_translation(transform.translation), slope.kind);
     }
 
-    if points.is_empty() {
-        return;
+    for (translation, collider) in slope_colliders(&slopes) {
+        commands.spawn((
+            SpatialBundle::from_transform(Transform::from_xyz(translation.x, translation.y, 0.0)),
+            RigidBody::Fixed,
+            collider,
+            Despawnable {},
+        ));
     }
 
-    let points_with_neighbors = find_points_with_neighbors(&points);
-    let neighbor_set: std::collections::HashSet<_> = points_with_neighbors.into_iter().collect();
-    points.retain(|p| !neighbor_set.contains(p));
+    for (transform, entity) in q_walls.iter() {
+        commands
+            .entity(entity)
+            .remove::<RigidBody>()
+            .remove::<Collider>()
+            .remove::<WallDefinition>();
 
-    let rects = find_rectangles(&points);
+        walls.insert(Point::from_translation(transform.translation));
+    }
 
-    for rect in rects.iter() {
+    for (translation, collider) in wall_colliders(&walls) {
         commands.spawn((
-            SpatialBundle::from_transform(Transform::from_xyz(
-                rect.top_left.x as f32 * 24.0 + rect.width as f32 * 24.0 / 2.0,
-                rect.top_left.y as f32 * 24.0 + 12.0, 0.0
-            )),
+            SpatialBundle::from_transform(Transform::from_xyz(translation.x, translation.y, 0.0)),
             RigidBody::Fixed,
-            Collider::cuboid(12.0 * rect.width as f32, 12.0),
+            collider,
             Despawnable {},
         ));
     }
 }
 
-fn find_points_with_neighbors(points: &[Point]) -> Vec<Point> {
-    let mut result = Vec::new();
+pub fn setup_one_way_platforms(
+    mut commands: Commands,
+    q_one_way_platforms: Query<(&Transform, Entity), With<OneWayPlatformDefinition>>,
+) {
+    let mut cells: HashSet<Point> = HashSet::new();
 
-    for point in points {
-        let has_left_neighbor = points.iter().any(|p| p.x == point.x - 1 && p.y == point.y);
-        let has_right_neighbor = points.iter().any(|p| p.x == point.x + 1 && p.y == point.y);
-        let has_top_neighbor = points.iter().any(|p| p.y == point.y + 1 && p.x == point.x);
-        let has_bottom_neighbor = points.iter().any(|p| p.y == point.y - 1 && p.x == point.x);
+    for (transform, entity) in q_one_way_platforms.iter() {
+        commands
+            .entity(entity)
+            .remove::<OneWayPlatformDefinition>();
 
-        if has_left_neighbor && has_right_neighbor && has_top_neighbor && has_bottom_neighbor {
-            result.push(*point);
-        }
+        cells.insert(Point::from_translation(transform.translation));
     }
 
-    result
-}
-
-fn find_rectangles(points: &[Point]) -> Vec<Rectangle> {
-    let mut rectangles = Vec::new();
-    let mut sorted_points = points.to_vec();
-    sorted_points.sort_by(|a, b| a.y.cmp(&b.y).then(a.x.cmp(&b.x)));
-
-    for i in 0..sorted_points.len() {
-        let point = sorted_points[i];
-        // Skip if this point is already part of a rectangle
-        if rectangles.iter().any(|r| is_point_in_rectangle(r, &point)) {
-            continue;
-        }
-
-        // Find the maximum width
-        let mut width = 1;
-        while i + width < sorted_points.len() && sorted_points[i + width].y == point.y && sorted_points[i + width].x == point.x + width as i32 {
-            width += 1;
-        }
-
-        // Add the rectangle
-        rectangles.push(Rectangle {
-            top_left: point,
-            width,
-            height: 1,
-        });
+    for (translation, collider) in one_way_platform_colliders(&cells) {
+        commands.spawn((
+            SpatialBundle::from_transform(Transform::from_xyz(translation.x, translation.y, 0.0)),
+            RigidBody::Fixed,
+            collider,
+            CollisionGroups::new(ONE_WAY_PLATFORM_GROUP, Group::ALL),
+            OneWayPlatform {},
+            Despawnable {},
+        ));
     }
-
-    rectangles
 }
-
-fn is_point_in_rectangle(rect: &Rectangle, point: &Point) -> bool {
-    point.x >= rect.top_left.x
-        && point.x < rect.top_left.x + rect.width as i32
-        && point.y >= rect.top_left.y
-        && point.y < rect.top_left.y + rect.height as i32
-}
-
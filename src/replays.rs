@@ -0,0 +1,187 @@
+use std::path::PathBuf;
+
+use bevy::{prelude::{Resource, Res, ResMut, EventReader, Assets, Commands, Query, Entity, With, NextState, DespawnRecursiveExt}, log::{info, error}};
+use bevy_ecs_ldtk::{LevelEvent, LevelSelection, assets::LdtkLevel, ldtk};
+use kt_common::{components::despawnable::Despawnable, events::{TransitionCovered, TransitionKind}};
+use kt_core::replay::{Replay, ReplayState, Checksum, ReplayFinished};
+
+use crate::{AppState, save_game::{GameSave, config_dir}, run_timer::RunTimer, ghost::best_replay_path};
+
+// Replay passed with `--replay <file>`, started as soon as its level is spawned
+#[derive(Resource, Debug, Default)]
+pub struct PendingReplay(pub Option<Replay>);
+
+#[derive(Resource, Debug, Default)]
+pub struct LevelChecksum(pub u64);
+
+pub fn load_replay_from_args() -> PendingReplay {
+    let mut args = std::env::args().skip_while(|arg| arg != "--replay").skip(1);
+
+    let path = match args.next() {
+        Some(path) => PathBuf::from(path),
+        None => return PendingReplay(None),
+    };
+
+    match Replay::load(&path) {
+        Ok(replay) => {
+            info!("playing replay {} of level {}", path.display(), replay.level);
+            PendingReplay(Some(replay))
+        },
+        Err(error) => {
+            error!("could not load replay {}: {}", path.display(), error);
+            PendingReplay(None)
+        },
+    }
+}
+
+fn replay_path(level: u32) -> PathBuf {
+    config_dir().join("replays").join(format!("level-{:02}.ktr", level))
+}
+
+// Only what affects the simulation goes in, so redrawing tiles or moving the
+// level around in the world doesn't invalidate replays.
+pub fn level_checksum(level: &ldtk::Level) -> u64 {
+    let mut checksum = Checksum::default();
+
+    checksum.write_i32(level.px_wid);
+    checksum.write_i32(level.px_hei);
+
+    for layer in level.layer_instances.iter().flatten() {
+        checksum.write_str(&layer.identifier);
+
+        for value in layer.int_grid_csv.iter() {
+            checksum.write_i32(*value);
+        }
+
+        for entity in layer.entity_instances.iter() {
+            checksum.write_str(&entity.identifier);
+            checksum.write_i32(entity.px.x);
+            checksum.write_i32(entity.px.y);
+            checksum.write_i32(entity.width);
+            checksum.write_i32(entity.height);
+
+            for field in entity.field_instances.iter() {
+                checksum.write_str(&field.identifier);
+                checksum.write_str(&format!("{:?}", field.value));
+            }
+        }
+    }
+
+    checksum.finish()
+}
+
+// The main menu is skipped when a replay is passed on the command line
+pub fn skip_menu_for_replay(
+    pending_replay: Res<PendingReplay>,
+    mut commands: Commands,
+    q_despawnable: Query<Entity, With<Despawnable>>,
+    mut level_selection: ResMut<LevelSelection>,
+    mut game_state: ResMut<GameSave>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let replay = match &pending_replay.0 {
+        Some(replay) => replay,
+        None => return,
+    };
+
+    for despawnable_entity in q_despawnable.iter() {
+        commands.entity(despawnable_entity).despawn_recursive();
+    }
+
+    *level_selection = LevelSelection::Index(replay.level as usize);
+    game_state.current_level = replay.level as isize + 1;
+    next_state.set(AppState::InGame);
+}
+
+pub fn start_level_replay(
+    mut ev_level: EventReader<LevelEvent>,
+    levels: Res<Assets<LdtkLevel>>,
+    level_selection: Res<LevelSelection>,
+    mut pending_replay: ResMut<PendingReplay>,
+    mut replay_state: ResMut<ReplayState>,
+    mut current_checksum: ResMut<LevelChecksum>,
+) {
+    for event in ev_level.iter() {
+        let iid = match event {
+            LevelEvent::Spawned(iid) => iid,
+            _ => continue,
+        };
+
+        let level = match levels.iter().find(|(_, level)| &level.level.iid == iid) {
+            Some((_, level)) => &level.level,
+            None => continue,
+        };
+
+        let level_index = match *level_selection {
+            LevelSelection::Index(index) => index as u32,
+            _ => continue,
+        };
+
+        let checksum = level_checksum(level);
+        current_checksum.0 = checksum;
+
+        if let Some(replay) = pending_replay.0.take() {
+            if let Err(error) = replay.check_level(checksum) {
+                error!("refusing to play replay: {}", error);
+                *replay_state = ReplayState::Idle;
+                continue;
+            }
+
+            *replay_state = ReplayState::Playing { replay, cursor: 0 };
+            continue;
+        }
+
+        if replay_state.is_playing() {
+            continue;
+        }
+
+        start_recording(&mut replay_state, level_index, checksum);
+    }
+}
+
+// Also used to throw away the current recording when the level is restarted
+pub fn start_recording(replay_state: &mut ReplayState, level: u32, checksum: u64) {
+    *replay_state = ReplayState::Recording(Replay::new(level, checksum));
+}
+
+// Saved once the exit transition covers the screen, one file per level
+pub fn save_level_replay(
+    mut ev_transition_covered: EventReader<TransitionCovered>,
+    mut replay_state: ResMut<ReplayState>,
+    run_timer: Res<RunTimer>,
+) {
+    for TransitionCovered(kind) in ev_transition_covered.iter() {
+        if *kind != TransitionKind::NextLevel {
+            continue;
+        }
+
+        let replay = match replay_state.take_recording() {
+            Some(replay) => replay,
+            None => continue,
+        };
+
+        let path = replay_path(replay.level);
+
+        if let Err(error) = replay.save(&path) {
+            error!("could not save replay {}: {}", path.display(), error);
+        }
+
+        if !run_timer.new_best {
+            continue;
+        }
+
+        let path = best_replay_path(replay.level);
+
+        if let Err(error) = replay.save(&path) {
+            error!("could not save replay {}: {}", path.display(), error);
+        }
+    }
+}
+
+pub fn log_replay_finished(
+    mut ev_replay_finished: EventReader<ReplayFinished>,
+) {
+    for _ in ev_replay_finished.iter() {
+        info!("replay finished");
+    }
+}
@@ -0,0 +1,134 @@
+use bevy::prelude::{Resource, Res, ResMut, EventReader};
+use bevy_ecs_ldtk::LevelEvent;
+use kt_util::constants::{FIXED_TIMESTEP, LEVEL_COUNT};
+
+use crate::save_game::{GameState, GameSave, RunRecord};
+
+// Counts simulation ticks rather than wall time, so a level takes exactly as
+// long no matter the frame rate and replays finish with the same time.
+#[derive(Resource, Debug, Default)]
+pub struct RunTimer {
+    pub level: isize,
+    pub ticks: u32,
+    pub running: bool,
+    // Cumulative splits of the full run in progress, empty when the current
+    // attempt didn't start from the first level.
+    pub splits: Vec<f32>,
+    // Set when the last completed level beat its personal best
+    pub new_best: bool,
+    in_run: bool,
+}
+
+impl RunTimer {
+    pub fn elapsed(&self) -> f32 {
+        self.ticks as f32 * FIXED_TIMESTEP
+    }
+
+    pub fn run_elapsed(&self) -> Option<f32> {
+        if !self.in_run {
+            return None;
+        }
+
+        Some(self.splits.last().copied().unwrap_or(0.0) + self.elapsed())
+    }
+
+    pub fn start_level(&mut self, level: isize) {
+        if level == 1 {
+            self.splits.clear();
+            self.in_run = true;
+        } else if self.splits.len() as isize != level - 1 {
+            // Started from the level select or went back, not a full run
+            self.splits.clear();
+            self.in_run = false;
+        }
+
+        self.level = level;
+        self.ticks = 0;
+        self.running = true;
+        self.new_best = false;
+    }
+
+    // Stops the timer and returns the level time, once per attempt
+    pub fn complete_level(&mut self) -> Option<f32> {
+        if !self.running {
+            return None;
+        }
+
+        self.running = false;
+
+        let time = self.elapsed();
+
+        if let Some(total) = self.run_elapsed() {
+            self.splits.push(total);
+        }
+
+        Some(time)
+    }
+}
+
+pub fn format_time(seconds: f32) -> String {
+    let hundredths = (seconds * 100.0).round() as u32;
+
+    format!("{}:{:02}.{:02}", hundredths / 6000, hundredths / 100 % 60, hundredths % 100)
+}
+
+pub fn format_delta(seconds: f32) -> String {
+    let sign = if seconds < 0.0 { "-" } else { "+" };
+
+    format!("{}{:.2}", sign, seconds.abs())
+}
+
+pub fn start_run_timer(
+    mut ev_level: EventReader<LevelEvent>,
+    game_state: Res<GameSave>,
+    mut run_timer: ResMut<RunTimer>,
+) {
+    for event in ev_level.iter() {
+        if let LevelEvent::Spawned(_) = event {
+            run_timer.start_level(game_state.current_level);
+        }
+    }
+}
+
+pub fn tick_run_timer(
+    mut run_timer: ResMut<RunTimer>,
+) {
+    if run_timer.running {
+        run_timer.ticks += 1;
+    }
+}
+
+// Called when the exit door fires, updates the personal bests
+pub fn record_level_time(run_timer: &mut RunTimer, game_state: &mut GameState) {
+    let time = match run_timer.complete_level() {
+        Some(time) => time,
+        None => return,
+    };
+
+    let picked_keys = game_state.picked_keys;
+    let stats = game_state.stats_mut(run_timer.level);
+
+    stats.completed = true;
+    stats.best_pins = stats.best_pins.max(picked_keys);
+    run_timer.new_best = stats.best_time.map_or(true, |best_time| time < best_time);
+
+    if run_timer.new_best {
+        stats.best_time = Some(time);
+    }
+
+    if run_timer.splits.len() as isize != LEVEL_COUNT {
+        return;
+    }
+
+    let run = RunRecord {
+        splits: run_timer.splits.clone(),
+    };
+
+    let is_best_run = game_state.best_run
+        .as_ref()
+        .map_or(true, |best_run| run.total() < best_run.total());
+
+    if is_best_run {
+        game_state.best_run = Some(run);
+    }
+}
@@ -1,25 +1,77 @@
-use std::path::Path;
+use std::{fmt, fs, io, ops::{Deref, DerefMut}, path::{Path, PathBuf}};
 
 use bevy_persistent::prelude::*;
-use bevy::{prelude::{Commands, Resource, ReflectResource}, reflect::Reflect};
-use serde::{Serialize, Deserialize};
+use bevy::{prelude::{Resource, ReflectResource}, reflect::Reflect, utils::HashMap, log::{warn, error}};
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+use serde_json::{Value, Map};
+
+// Bump when the layout of `GameState` changes and add a migration below
+pub const SAVE_VERSION: u64 = 3;
+
+pub const SLOT_COUNT: usize = 3;
+
+// Migrations run on the raw JSON, `MIGRATIONS[n]` upgrades version n to n + 1
+const MIGRATIONS: [fn(&mut Map<String, Value>); SAVE_VERSION as usize] = [
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+];
+
+// Cumulative time at the end of every level of a full run, the last split is
+// the run total.
+#[derive(Debug, Clone, Default, Reflect, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub splits: Vec<f32>,
+}
+
+impl RunRecord {
+    pub fn total(&self) -> f32 {
+        self.splits.last().copied().unwrap_or(0.0)
+    }
+}
+
+#[derive(Debug, Clone, Default, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelStats {
+    pub completed: bool,
+    pub deaths: u32,
+    // Times the level was entered, restarts after a death don't count
+    pub attempts: u32,
+    pub best_time: Option<f32>,
+    // Most pins picked up in a single attempt
+    pub best_pins: isize,
+}
 
 #[derive(Resource, Debug, Clone, Reflect, Serialize, Deserialize)]
 #[reflect(Resource)]
 pub struct GameState {
+    #[serde(default)]
+    pub version: u64,
     pub unlocked_levels: isize,
     pub current_level: isize,
     pub picked_keys: isize,
     pub required_keys: isize,
+    // Keyed by level number, levels never entered have no entry
+    #[serde(default)]
+    pub level_stats: HashMap<isize, LevelStats>,
+    #[serde(default)]
+    pub best_run: Option<RunRecord>,
+    // Seconds spent in levels
+    #[serde(default)]
+    pub play_time: f32,
 }
 
 impl Default for GameState {
     fn default() -> Self {
         GameState {
+            version: SAVE_VERSION,
             unlocked_levels: 1,
             current_level: 1,
             picked_keys: 0,
             required_keys: 1,
+            level_stats: HashMap::default(),
+            best_run: None,
+            play_time: 0.0,
         }
     }
 }
@@ -30,24 +82,277 @@ impl GameState {
             self.unlocked_levels = self.current_level;
         }
     }
+
+    pub fn stats(&self, level: isize) -> Option<&LevelStats> {
+        self.level_stats.get(&level)
+    }
+
+    pub fn stats_mut(&mut self, level: isize) -> &mut LevelStats {
+        self.level_stats.entry(level).or_default()
+    }
+}
+
+// The loaded slot. It only lives in memory when no file could be made for it,
+// progress is lost on quit then.
+#[derive(Resource)]
+pub enum GameSave {
+    File(Persistent<GameState>, PathBuf),
+    Memory(GameState),
+}
+
+impl GameSave {
+    // A save that was just written is good, so it becomes the backup
+    pub fn persist(&self) -> Result<(), PersistenceError> {
+        let (game_state, path) = match self {
+            GameSave::File(game_state, path) => (game_state, path),
+            GameSave::Memory(..) => return Ok(()),
+        };
+
+        game_state.persist()?;
+        fs::copy(path, backup_path(path)).ok();
+
+        Ok(())
+    }
 }
 
-pub fn load(mut commands: Commands) {
-    let config_dir = dirs::config_dir()
+impl Deref for GameSave {
+    type Target = GameState;
+
+    fn deref(&self) -> &GameState {
+        match self {
+            GameSave::File(game_state, ..) => game_state,
+            GameSave::Memory(game_state) => game_state,
+        }
+    }
+}
+
+impl DerefMut for GameSave {
+    fn deref_mut(&mut self) -> &mut GameState {
+        match self {
+            GameSave::File(game_state, ..) => game_state,
+            GameSave::Memory(game_state) => game_state,
+        }
+    }
+}
+
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir()
         .map(|native_config_dir| native_config_dir.join("pixel-arena"))
-        .unwrap_or(Path::new("local").join("configuration"));
+        .unwrap_or(Path::new("local").join("configuration"))
+}
+
+// Shown in the main menu when the save couldn't be loaded as it was
+#[derive(Resource, Debug, Default)]
+pub struct SaveNotice(pub Option<String>);
+
+// Index of the save slot `GameSave` was loaded from
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct ActiveSlot(pub usize);
+
+#[derive(Debug)]
+pub enum SaveError {
+    Parse(serde_json::Error),
+    NotAnObject,
+    TooNew(u64),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Parse(error) => write!(f, "could not parse save: {}", error),
+            SaveError::NotAnObject => write!(f, "save is not a JSON object"),
+            SaveError::TooNew(version) => write!(f, "save version {} is newer than this build supports", version),
+        }
+    }
+}
+
+// Unversioned saves, the first version field came with this migration
+fn migrate_v0_to_v1(save: &mut Map<String, Value>) {
+    save.entry("best_times").or_insert_with(|| Value::Object(Map::new()));
+    save.entry("best_run").or_insert(Value::Null);
+}
+
+fn migrate_v1_to_v2(save: &mut Map<String, Value>) {
+    save.entry("play_time").or_insert(Value::from(0.0));
+}
+
+// Best times moved into the per level stats. Every level before the last
+// unlocked one must have been finished.
+fn migrate_v2_to_v3(save: &mut Map<String, Value>) {
+    let best_times = match save.remove("best_times") {
+        Some(Value::Object(best_times)) => best_times,
+        _ => Map::new(),
+    };
+
+    let unlocked_levels = save.get("unlocked_levels").and_then(Value::as_i64).unwrap_or(1);
+    let mut level_stats = Map::new();
+
+    for level in 1..unlocked_levels {
+        level_stats.insert(level.to_string(), serde_json::json!({ "completed": true }));
+    }
+
+    for (level, best_time) in best_times {
+        level_stats.insert(level, serde_json::json!({ "completed": true, "best_time": best_time }));
+    }
 
-    commands.insert_resource(
-        Persistent::<GameState>::builder()
-            .name("game state")
+    save.insert("level_stats".to_string(), Value::Object(level_stats));
+}
+
+pub fn parse_save(contents: &str) -> Result<GameState, SaveError> {
+    let mut save = match serde_json::from_str::<Value>(contents).map_err(SaveError::Parse)? {
+        Value::Object(save) => save,
+        _ => return Err(SaveError::NotAnObject),
+    };
+
+    let version = save.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version > SAVE_VERSION {
+        return Err(SaveError::TooNew(version));
+    }
+
+    for migrate in MIGRATIONS[version as usize..].iter() {
+        migrate(&mut save);
+    }
+
+    save.insert("version".to_string(), Value::from(SAVE_VERSION));
+
+    serde_json::from_value(Value::Object(save)).map_err(SaveError::Parse)
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    path.with_extension("json.bak")
+}
+
+// Brings the save on disk up to date before it's handed to `Persistent`. A
+// save that can't be read is moved aside and replaced by the last good one.
+fn prepare_save(path: &Path) -> Option<String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(..) => return None,
+    };
+
+    let error = match parse_save(&contents) {
+        Ok(game_state) => {
+            fs::copy(path, backup_path(path)).ok();
+            write_save(path, &game_state);
+            return None;
+        },
+        Err(error) => error,
+    };
+
+    error!("{}: {}", path.display(), error);
+
+    let broken_path = path.with_extension("json.broken");
+    fs::rename(path, &broken_path).ok();
+
+    let backup = fs::read_to_string(backup_path(path))
+        .ok()
+        .and_then(|contents| parse_save(&contents).ok());
+
+    match backup {
+        Some(game_state) => {
+            write_save(path, &game_state);
+            Some("Your save couldn't be read, the last backup was restored.".to_string())
+        },
+        None => Some("Your save couldn't be read, starting a new one.".to_string()),
+    }
+}
+
+fn write_save(path: &Path, game_state: &GameState) {
+    let written = serde_json::to_string_pretty(game_state)
+        .map_err(|error| error.to_string())
+        .and_then(|contents| fs::write(path, contents).map_err(|error| error.to_string()));
+
+    if let Err(error) = written {
+        warn!("could not write {}: {}", path.display(), error);
+    }
+}
+
+// Falls back to the defaults when the file can't be used, so a bad file never
+// stops the game from starting.
+pub fn persistent_or_default<T>(name: &str, path: PathBuf, default: T) -> Result<Persistent<T>, PersistenceError>
+where T: Resource + Serialize + DeserializeOwned + Clone {
+    let build = |path: &Path| {
+        Persistent::<T>::builder()
+            .name(name)
             .format(StorageFormat::Json)
-            .path(config_dir.join("game-state.json"))
-            .default(GameState { unlocked_levels: 1, current_level: 0, picked_keys: 0, required_keys: 0 })
+            .path(path)
+            .default(default.clone())
             .build()
-            .expect("failed to initialize game state")
-    );
+    };
+
+    match build(&path) {
+        Ok(persistent) => Ok(persistent),
+        Err(error) => {
+            error!("could not load {}: {}", name, error);
+            fs::rename(&path, path.with_extension("json.broken")).ok();
+            build(&path)
+        },
+    }
+}
+
+pub fn slot_path(slot: usize) -> PathBuf {
+    config_dir().join(format!("game-state-{}.json", slot + 1))
+}
+
+// The save of a slot as it is on disk, without loading it
+pub fn read_slot(slot: usize) -> Option<GameState> {
+    fs::read_to_string(slot_path(slot))
+        .ok()
+        .and_then(|contents| parse_save(&contents).ok())
+}
+
+pub fn copy_slot(from: usize, to: usize) -> io::Result<()> {
+    let (from, to) = (slot_path(from), slot_path(to));
+
+    // Otherwise the old backup of the target would be restored over the copy
+    if !backup_path(&from).exists() {
+        fs::remove_file(backup_path(&to)).ok();
+    }
+
+    fs::copy(&from, &to)?;
+    fs::copy(backup_path(&from), backup_path(&to)).ok();
+
+    Ok(())
+}
+
+pub fn delete_slot(slot: usize) {
+    let path = slot_path(slot);
+
+    fs::remove_file(backup_path(&path)).ok();
+    fs::remove_file(path).ok();
+}
+
+// Builds before save slots kept a single `game-state.json`, it becomes the
+// first slot.
+fn migrate_single_save() {
+    let legacy_path = config_dir().join("game-state.json");
+    let first_slot = slot_path(0);
+
+    if legacy_path.exists() && !first_slot.exists() {
+        fs::rename(&legacy_path, &first_slot).ok();
+        fs::rename(backup_path(&legacy_path), backup_path(&first_slot)).ok();
+    }
+}
+
+pub fn load(slot: usize) -> (GameSave, SaveNotice) {
+    migrate_single_save();
+
+    let path = slot_path(slot);
+    let mut notice = prepare_save(&path);
+    let default = GameState { current_level: 0, required_keys: 0, ..GameState::default() };
+
+    let game_save = match persistent_or_default("game state", path.clone(), default.clone()) {
+        Ok(game_state) => GameSave::File(game_state, path),
+        Err(error) => {
+            // Most likely the config dir isn't writable, play without saving
+            error!("could not create game state: {}", error);
+            notice = Some("Progress can't be saved on this machine.".to_string());
+
+            GameSave::Memory(default)
+        },
+    };
 
-    dbg!("load!");
+    (game_save, SaveNotice(notice))
 }
 
 // pub fn load(
@@ -0,0 +1,332 @@
+use bevy::{prelude::{Component, Bundle, ChildBuilder, AssetServer, Res, ResMut, ButtonBundle, Color, TextBundle, default, BuildChildren, Query, Changed, With, Without, NodeBundle, Commands, Entity, DespawnRecursiveExt, Children, Time, EventWriter, Local}, ui::{Style, Val, UiRect, JustifyContent, AlignItems, BackgroundColor, UiImage, PositionType, FlexDirection, ZIndex}, text::{Text, TextStyle}, log::error};
+use bevy_persistent::Persistent;
+
+use kt_core::audio::{PlaySfx, Sfx};
+use kt_util::constants::LEVEL_COUNT;
+
+use crate::{save_game::{GameSave, ActiveSlot, SLOT_COUNT, load, read_slot, copy_slot, delete_slot}, settings::Settings, menu_navigation::{Focusable, MenuPage, MenuFocus, MenuColumn, show_menu_page}, main_menu_ui::{LevelGridUi, SaveNoticeUi, create_level_buttons, create_back_button}};
+
+#[derive(Clone, Component, Debug, Default)]
+pub struct SlotButtonUi {
+    pub slot: usize,
+}
+
+#[derive(Clone, Component, Debug, Default)]
+pub struct SlotSummaryUi {
+    pub slot: usize,
+}
+
+#[derive(Clone, Component, Debug, Default)]
+pub struct CopySlotButtonUi {
+    pub slot: usize,
+}
+
+// Deleting takes two presses, the first one only arms the button
+#[derive(Clone, Component, Debug, Default)]
+pub struct DeleteSlotButtonUi {
+    pub slot: usize,
+    pub armed: bool,
+}
+
+pub fn format_play_time(seconds: f32) -> String {
+    let seconds = seconds as u32;
+
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+fn slot_summary(slot: usize) -> String {
+    match read_slot(slot) {
+        Some(game_state) => format!(
+            "{}/{} levels  {}",
+            game_state.unlocked_levels,
+            LEVEL_COUNT,
+            format_play_time(game_state.play_time),
+        ),
+        None => "empty".to_string(),
+    }
+}
+
+fn create_small_slot_button(parent: &mut ChildBuilder, label: &str, order: usize, button: impl Bundle, asset_server: &Res<AssetServer>) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    height: Val::Px(65.0),
+                    width: Val::Px(120.0),
+                    margin: UiRect::all(Val::Px(8.0)),
+                    padding: UiRect::horizontal(Val::Px(6.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::WHITE),
+                image: UiImage::new(asset_server.load("sprites/button.png")),
+                ..default()
+            },
+            button,
+            Focusable { page: MenuPage::SlotSelect, order },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font: asset_server.load("fonts/ThaleahFat.ttf"),
+                    font_size: 32.0,
+                    color: Color::rgb(1.0, 1.0, 1.0),
+                },
+            ));
+        });
+}
+
+fn create_slot_row(parent: &mut ChildBuilder, slot: usize, asset_server: &Res<AssetServer>) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            height: Val::Px(65.0),
+                            width: Val::Px(350.0),
+                            margin: UiRect::all(Val::Px(8.0)),
+                            padding: UiRect::horizontal(Val::Px(6.0)),
+                            flex_direction: FlexDirection::Column,
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: BackgroundColor(Color::WHITE),
+                        image: UiImage::new(asset_server.load("sprites/button.png")),
+                        ..default()
+                    },
+                    SlotButtonUi { slot },
+                    Focusable { page: MenuPage::SlotSelect, order: slot * 3 },
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        format!("slot {}", slot + 1),
+                        TextStyle {
+                            font: asset_server.load("fonts/ThaleahFat.ttf"),
+                            font_size: 32.0,
+                            color: Color::rgb(1.0, 1.0, 1.0),
+                        },
+                    ));
+
+                    parent.spawn((
+                        TextBundle::from_section(
+                            slot_summary(slot),
+                            TextStyle {
+                                font: asset_server.load("fonts/ThaleahFat.ttf"),
+                                font_size: 20.0,
+                                color: Color::rgb(0.96, 0.67, 0.1),
+                            },
+                        ),
+                        SlotSummaryUi { slot },
+                    ));
+                });
+
+            create_small_slot_button(parent, "copy", slot * 3 + 1, CopySlotButtonUi { slot }, asset_server);
+            create_small_slot_button(parent, "delete", slot * 3 + 2, DeleteSlotButtonUi { slot, armed: false }, asset_server);
+        });
+}
+
+pub fn create_slot_column(parent: &mut ChildBuilder, asset_server: &Res<AssetServer>) {
+    let slot_column = (
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(80.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                right: Val::Percent(100.0),
+                ..default()
+            },
+            z_index: ZIndex::Global(1),
+            ..default()
+        },
+        MenuColumn { page: MenuPage::SlotSelect },
+    );
+
+    parent
+        .spawn(slot_column)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Choose save",
+                TextStyle {
+                    font: asset_server.load("fonts/ThaleahFat.ttf"),
+                    font_size: 62.0,
+                    color: Color::rgb(0.96, 0.67, 0.1),
+                }
+            ));
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        margin: UiRect::vertical(Val::Px(32.0)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for slot in 0..SLOT_COUNT {
+                        create_slot_row(parent, slot, asset_server);
+                    }
+                });
+
+            create_back_button(parent, MenuPage::SlotSelect, MenuPage::Main, asset_server);
+        });
+}
+
+pub fn handle_slot_button_interactions(
+    mut q_interaction: Query<(&bevy::ui::Interaction, &SlotButtonUi), Changed<bevy::ui::Interaction>>,
+    mut q_columns: Query<(&MenuColumn, &mut Style)>,
+    q_level_grid: Query<(Entity, Option<&Children>), With<LevelGridUi>>,
+    mut q_notices: Query<&mut Text, With<SaveNoticeUi>>,
+    mut menu_focus: ResMut<MenuFocus>,
+    mut settings: ResMut<Persistent<Settings>>,
+    mut active_slot: ResMut<ActiveSlot>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut ev_play_sfx: EventWriter<PlaySfx>,
+) {
+    for (interaction, slot_button) in &mut q_interaction {
+        if let bevy::ui::Interaction::Pressed = *interaction {
+            ev_play_sfx.send(PlaySfx(Sfx::ButtonClick));
+
+            let (game_state, save_notice) = load(slot_button.slot);
+
+            for mut text in q_notices.iter_mut() {
+                text.sections[0].value = save_notice.0.clone().unwrap_or_default();
+            }
+
+            // The level grid was built from the slot loaded at startup
+            for (grid, children) in q_level_grid.iter() {
+                for child in children.into_iter().flatten() {
+                    commands.entity(*child).despawn_recursive();
+                }
+
+                commands.entity(grid)
+                    .with_children(|grid| create_level_buttons(grid, &game_state, &asset_server));
+            }
+
+            commands.insert_resource(game_state);
+            commands.insert_resource(save_notice);
+            active_slot.0 = slot_button.slot;
+
+            settings
+                .update(|settings| settings.last_slot = slot_button.slot)
+                .ok();
+
+            show_menu_page(MenuPage::LevelSelect, &mut menu_focus, &mut q_columns);
+        }
+    }
+}
+
+// The first press picks the slot to copy from, the second one the slot to
+// copy to. Pressing the first slot again cancels. Copying over a save takes
+// another press on the target, like deleting one.
+pub fn handle_copy_slot_button_interactions(
+    q_interaction: Query<(&bevy::ui::Interaction, &CopySlotButtonUi), Changed<bevy::ui::Interaction>>,
+    q_copy_buttons: Query<(&CopySlotButtonUi, &Children)>,
+    mut q_text: Query<&mut Text, Without<SlotSummaryUi>>,
+    mut q_summaries: Query<(&SlotSummaryUi, &mut Text)>,
+    mut copy_from: Local<Option<usize>>,
+    mut copy_to: Local<Option<usize>>,
+    mut ev_play_sfx: EventWriter<PlaySfx>,
+) {
+    for (interaction, copy_button) in q_interaction.iter() {
+        if *interaction != bevy::ui::Interaction::Pressed {
+            continue;
+        }
+
+        ev_play_sfx.send(PlaySfx(Sfx::ButtonClick));
+
+        match *copy_from {
+            Some(from) if from == copy_button.slot => {
+                *copy_from = None;
+                *copy_to = None;
+            },
+            Some(..) if read_slot(copy_button.slot).is_some() && *copy_to != Some(copy_button.slot) => {
+                *copy_to = Some(copy_button.slot);
+            },
+            Some(from) => {
+                if let Err(error) = copy_slot(from, copy_button.slot) {
+                    error!("could not copy slot {} to {}: {}", from + 1, copy_button.slot + 1, error);
+                }
+
+                *copy_from = None;
+                *copy_to = None;
+                refresh_slot_summaries(&mut q_summaries);
+            },
+            None if read_slot(copy_button.slot).is_some() => *copy_from = Some(copy_button.slot),
+            None => {},
+        }
+
+        for (button, children) in q_copy_buttons.iter() {
+            let label = match *copy_from {
+                Some(from) if from == button.slot => "cancel",
+                Some(..) if *copy_to == Some(button.slot) => "sure?",
+                Some(..) => "here",
+                None => "copy",
+            };
+
+            for child in children.iter() {
+                if let Ok(mut text) = q_text.get_mut(*child) {
+                    text.sections[0].value = label.to_string();
+                }
+            }
+        }
+    }
+}
+
+pub fn handle_delete_slot_button_interactions(
+    mut q_interaction: Query<(&bevy::ui::Interaction, &mut DeleteSlotButtonUi, &Children), Changed<bevy::ui::Interaction>>,
+    mut q_text: Query<&mut Text, Without<SlotSummaryUi>>,
+    mut q_summaries: Query<(&SlotSummaryUi, &mut Text)>,
+    mut ev_play_sfx: EventWriter<PlaySfx>,
+) {
+    for (interaction, mut delete_button, children) in &mut q_interaction {
+        if let bevy::ui::Interaction::Pressed = *interaction {
+            ev_play_sfx.send(PlaySfx(Sfx::ButtonClick));
+
+            delete_button.armed = !delete_button.armed;
+
+            // The loaded game state is left alone, nothing saves it from the
+            // menus and picking a slot loads it again. Loading it here would
+            // write the deleted slot right back.
+            if !delete_button.armed {
+                delete_slot(delete_button.slot);
+                refresh_slot_summaries(&mut q_summaries);
+            }
+
+            for child in children.iter() {
+                if let Ok(mut text) = q_text.get_mut(*child) {
+                    text.sections[0].value = if delete_button.armed { "sure?" } else { "delete" }.to_string();
+                }
+            }
+        }
+    }
+}
+
+fn refresh_slot_summaries(q_summaries: &mut Query<(&SlotSummaryUi, &mut Text)>) {
+    for (summary, mut text) in q_summaries.iter_mut() {
+        text.sections[0].value = slot_summary(summary.slot);
+    }
+}
+
+pub fn track_play_time(
+    time: Res<Time>,
+    mut game_state: ResMut<GameSave>,
+) {
+    game_state.play_time += time.delta_seconds();
+}
@@ -1,89 +1,351 @@
-use std::time::Duration;
+use std::collections::VecDeque;
 
-use bevy::{prelude::{EventReader, Commands, Query, Entity, With, ResMut, Without, NextState, Res, AssetServer, NodeBundle, default, Color, BuildChildren, World, DespawnRecursiveExt}, ui::{Style, UiRect, Val, PositionType, FlexDirection, JustifyContent, BackgroundColor, ZIndex}};
+use bevy::{prelude::{EventReader, EventWriter, Commands, Query, Entity, With, ResMut, NextState, Res, NodeBundle, default, Color, BuildChildren, DespawnRecursiveExt, Component, Resource, Assets, Image, Vec2, Camera, GlobalTransform, FixedTime, Without}, ui::{Style, Val, PositionType, FlexDirection, FlexWrap, JustifyContent, BackgroundColor, ZIndex, Display, UiImage}, render::render_resource::{Extent3d, TextureDimension, TextureFormat}, window::{Window, PrimaryWindow}};
 use bevy_ecs_ldtk::LevelSelection;
-use bevy_persistent::Persistent;
 // use bevy_save::WorldSaveableExt;
-use bevy_tweening::{TweenCompleted, Tween, EaseFunction, lens::UiPositionLens, EaseMethod, Delay};
-use kt_common::components::{despawnable::Despawnable, ui::{TransitionColumnLeftUi, TransitionColumnRightUi}};
+use kt_common::{components::{despawnable::Despawnable, player::Player, ui::{TransitionColumnLeftUi, TransitionColumnRightUi}}, events::{TransitionCovered, TransitionFinished, TransitionKind, TransitionStyle, RequestTransition, LevelCompleted, PlayerDied}};
+use kt_util::constants::PLAYER_HIT_RESPAWN_TIME;
 
-use crate::{AppState, save_game::GameState};
+use crate::{AppState, save_game::GameSave};
+
+// How long the screen stays fully covered between the two halves
+pub const TRANSITION_HOLD: f32 = 0.1;
+
+const DISSOLVE_COLUMNS: usize = 16;
+const DISSOLVE_ROWS: usize = 9;
+const IRIS_MASK_SIZE: u32 = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TransitionPhase {
+    Covering,
+    Covered,
+    Revealing,
+}
+
+#[derive(Debug)]
+struct ActiveTransition {
+    request: RequestTransition,
+    phase: TransitionPhase,
+    // Seconds into the current phase
+    elapsed: f32,
+    // Where the iris closes, in UI pixels
+    focus: Vec2,
+}
+
+impl ActiveTransition {
+    fn phase_duration(&self) -> f32 {
+        match self.phase {
+            TransitionPhase::Covered => TRANSITION_HOLD,
+            TransitionPhase::Covering | TransitionPhase::Revealing => self.request.duration,
+        }
+    }
+
+    fn percent(&self) -> f32 {
+        let duration = self.phase_duration();
+
+        if duration <= 0.0 {
+            return 1.0;
+        }
+
+        (self.elapsed / duration).min(1.0)
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct ScreenTransition {
+    active: Option<ActiveTransition>,
+    queue: VecDeque<RequestTransition>,
+}
+
+impl ScreenTransition {
+    // For systems on the fixed tick, which can't wait a frame for
+    // `queue_transitions` without the transition starting late
+    pub fn request(&mut self, request: RequestTransition) {
+        if self.is_pending(request.kind) {
+            return;
+        }
+
+        self.queue.push_back(request);
+    }
+
+    fn is_pending(&self, kind: TransitionKind) -> bool {
+        self.active.as_ref().map_or(false, |active| active.request.kind == kind)
+            || self.queue.iter().any(|request| request.kind == kind)
+    }
+
+    // How much of the screen is covered, from 0 to 1
+    fn coverage(&self) -> f32 {
+        let active = match &self.active {
+            Some(active) => active,
+            None => return 0.0,
+        };
+
+        match active.phase {
+            TransitionPhase::Covering => ease_in_out_quart(active.percent()),
+            TransitionPhase::Covered => 1.0,
+            TransitionPhase::Revealing => 1.0 - ease_in_out_quart(active.percent()),
+        }
+    }
+}
+
+// Every style has its own full screen container, only the running one is shown
+#[derive(Clone, Component, Debug)]
+pub struct TransitionStyleUi(pub TransitionStyle);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IrisPart {
+    Hole,
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+// The hole is a black square with a transparent circle, the four other parts
+// fill the screen around it
+#[derive(Clone, Component, Debug)]
+pub struct TransitionIrisUi {
+    part: IrisPart,
+}
+
+#[derive(Clone, Component, Debug)]
+pub struct TransitionDissolveUi {
+    // Coverage at which this square shows up
+    threshold: f32,
+}
+
+fn ease_in_out_quart(t: f32) -> f32 {
+    if t < 0.5 {
+        8.0 * t.powi(4)
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(4) / 2.0
+    }
+}
+
+pub fn respawn_transition(style: TransitionStyle, focus: Option<Vec2>) -> RequestTransition {
+    // `respawn_player` moves the player once this covers the screen
+    RequestTransition {
+        kind: TransitionKind::Respawn,
+        style,
+        duration: PLAYER_HIT_RESPAWN_TIME,
+        focus,
+    }
+}
+
+pub fn queue_transitions(
+    mut ev_request_transition: EventReader<RequestTransition>,
+    mut screen_transition: ResMut<ScreenTransition>,
+) {
+    for request in ev_request_transition.iter() {
+        screen_transition.request(*request);
+    }
+}
+
+// On the fixed tick, so what happens under the cover (respawns above all)
+// lands on the same tick every time a replay is played
+pub fn advance_transition(
+    mut screen_transition: ResMut<ScreenTransition>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform)>,
+    fixed_time: Res<FixedTime>,
+    mut ev_transition_covered: EventWriter<TransitionCovered>,
+    mut ev_transition_finished: EventWriter<TransitionFinished>,
+) {
+    let mut delta = fixed_time.period.as_secs_f32();
+
+    // Time left over from a phase carries into the next one, and into the
+    // next queued transition, so none of them run long
+    loop {
+        if screen_transition.active.is_none() {
+            let request = match screen_transition.queue.pop_front() {
+                Some(request) => request,
+                None => return,
+            };
+
+            let screen_center = q_window
+                .get_single()
+                .map_or(Vec2::ZERO, |window| Vec2::new(window.width(), window.height()) / 2.0);
+
+            let focus = request.focus
+                .and_then(|focus| {
+                    let (camera, camera_transform) = q_camera.get_single().ok()?;
+                    camera.world_to_viewport(camera_transform, focus.extend(0.0))
+                })
+                .unwrap_or(screen_center);
+
+            screen_transition.active = Some(ActiveTransition {
+                request,
+                phase: TransitionPhase::Covering,
+                elapsed: 0.0,
+                focus,
+            });
+        }
+
+        let active = match screen_transition.active.as_mut() {
+            Some(active) => active,
+            None => return,
+        };
+
+        active.elapsed += delta;
+
+        let duration = active.phase_duration();
+
+        if active.elapsed < duration {
+            return;
+        }
+
+        delta = active.elapsed - duration;
+        active.elapsed = 0.0;
+
+        match active.phase {
+            TransitionPhase::Covering => {
+                active.phase = TransitionPhase::Covered;
+                ev_transition_covered.send(TransitionCovered(active.request.kind));
+            },
+            TransitionPhase::Covered => {
+                active.phase = TransitionPhase::Revealing;
+            },
+            TransitionPhase::Revealing => {
+                ev_transition_finished.send(TransitionFinished(active.request.kind));
+                screen_transition.active = None;
+            },
+        }
+    }
+}
+
+pub fn draw_transition(
+    screen_transition: Res<ScreenTransition>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    mut q_containers: Query<(&TransitionStyleUi, &mut Style), (Without<TransitionColumnLeftUi>, Without<TransitionColumnRightUi>, Without<TransitionIrisUi>)>,
+    mut q_transition_left: Query<&mut Style, (With<TransitionColumnLeftUi>, Without<TransitionColumnRightUi>, Without<TransitionIrisUi>)>,
+    mut q_transition_right: Query<&mut Style, (With<TransitionColumnRightUi>, Without<TransitionColumnLeftUi>, Without<TransitionIrisUi>)>,
+    mut q_fade: Query<(&TransitionStyleUi, &mut BackgroundColor), (Without<TransitionDissolveUi>, Without<TransitionIrisUi>)>,
+    mut q_iris: Query<(&TransitionIrisUi, &mut Style), (Without<TransitionColumnLeftUi>, Without<TransitionColumnRightUi>)>,
+    mut q_dissolve: Query<(&TransitionDissolveUi, &mut BackgroundColor), Without<TransitionStyleUi>>,
+) {
+    let style = screen_transition.active.as_ref().map(|active| active.request.style);
+    let coverage = screen_transition.coverage();
+
+    for (container, mut container_style) in q_containers.iter_mut() {
+        container_style.display = if style == Some(container.0) { Display::Flex } else { Display::None };
+    }
+
+    match style {
+        Some(TransitionStyle::Columns) => {
+            for mut column_style in q_transition_left.iter_mut() {
+                column_style.left = Val::Percent(100.0 - 60.0 * coverage);
+            }
+
+            for mut column_style in q_transition_right.iter_mut() {
+                column_style.right = Val::Percent(100.0 - 60.0 * coverage);
+            }
+        },
+        Some(TransitionStyle::Fade) => {
+            for (container, mut background_color) in q_fade.iter_mut() {
+                if container.0 == TransitionStyle::Fade {
+                    background_color.0 = Color::rgba(0.0, 0.0, 0.0, coverage);
+                }
+            }
+        },
+        Some(TransitionStyle::Iris) => {
+            let window = match q_window.get_single() {
+                Ok(window) => window,
+                Err(..) => return,
+            };
+
+            let size = Vec2::new(window.width(), window.height());
+            let focus = screen_transition.active.as_ref().map_or(size / 2.0, |active| active.focus);
+
+            // Far enough to clear the corner furthest from the focus
+            let max_radius = focus.max(size - focus).length() + 1.0;
+            let radius = max_radius * (1.0 - coverage);
+
+            for (iris, mut iris_style) in q_iris.iter_mut() {
+                let (left, top, width, height) = match iris.part {
+                    IrisPart::Hole => (focus.x - radius, focus.y - radius, radius * 2.0, radius * 2.0),
+                    IrisPart::Top => (0.0, 0.0, size.x, focus.y - radius),
+                    IrisPart::Bottom => (0.0, focus.y + radius, size.x, size.y - focus.y - radius),
+                    IrisPart::Left => (0.0, focus.y - radius, focus.x - radius, radius * 2.0),
+                    IrisPart::Right => (focus.x + radius, focus.y - radius, size.x - focus.x - radius, radius * 2.0),
+                };
+
+                iris_style.left = Val::Px(left);
+                iris_style.top = Val::Px(top);
+                iris_style.width = Val::Px(width.max(0.0));
+                iris_style.height = Val::Px(height.max(0.0));
+            }
+        },
+        Some(TransitionStyle::Dissolve) => {
+            for (square, mut background_color) in q_dissolve.iter_mut() {
+                background_color.0 = if coverage > square.threshold { Color::BLACK } else { Color::NONE };
+            }
+        },
+        None => {},
+    }
+}
 
 // Main Menu -> In Game
 pub fn complete_transition_event_handler(
-    mut q_event: EventReader<TweenCompleted>,
+    mut ev_transition_covered: EventReader<TransitionCovered>,
     mut commands: Commands,
     q_despawnable: Query<Entity, With<Despawnable>>,
     mut next_state: ResMut<NextState<AppState>>,
-    mut q_transition_left: Query<&mut bevy_tweening::Animator<Style>, (With<TransitionColumnLeftUi>, Without<TransitionColumnRightUi>)>,
-    mut q_transition_right: Query<&mut bevy_tweening::Animator<Style>, (With<TransitionColumnRightUi>, Without<TransitionColumnLeftUi>)>,
 ) {
-    for _event in q_event.iter() {
+    for TransitionCovered(kind) in ev_transition_covered.iter() {
+        if *kind != TransitionKind::EnterGame {
+            continue;
+        }
+
         next_state.set(AppState::InGame);
 
         for despawnable_entity in q_despawnable.iter() {
             commands.entity(despawnable_entity).despawn();
         }
+    }
+}
 
-        let mut transition_left_column_animator = q_transition_left.single_mut();
-        let mut transition_right_column_animator = q_transition_right.single_mut();
-
-        let tween = Tween::new(
-            EaseFunction::QuarticInOut,
-            Duration::from_secs_f32(0.5),
-            UiPositionLens {
-                start: UiRect {
-                    left: Val::Percent(40.0),
-                    top: Val::Auto,
-                    right: Val::Auto,
-                    bottom: Val::Auto,
-                },
-                end: UiRect {
-                    left: Val::Percent(100.0),
-                    top: Val::Auto,
-                    right: Val::Auto,
-                    bottom: Val::Auto,
-                },
-            },
-        );
-
-        transition_left_column_animator.set_tweenable(tween);
-
-        let tween = Tween::new(
-            EaseFunction::QuarticInOut,
-            Duration::from_secs_f32(0.5),
-            UiPositionLens {
-                start: UiRect {
-                    right: Val::Percent(40.0),
-                    top: Val::Auto,
-                    left: Val::Auto,
-                    bottom: Val::Auto,
-                },
-                end: UiRect {
-                    right: Val::Percent(100.0),
-                    top: Val::Auto,
-                    left: Val::Auto,
-                    bottom: Val::Auto,
-                },
-            },
-        );
+// Covers the level once the exit is reached
+pub fn start_level_exit_transition(
+    mut ev_level_completed: EventReader<LevelCompleted>,
+    mut ev_request_transition: EventWriter<RequestTransition>,
+) {
+    for _event in ev_level_completed.iter() {
+        ev_request_transition.send(RequestTransition {
+            kind: TransitionKind::NextLevel,
+            style: TransitionStyle::Columns,
+            duration: 0.5,
+            focus: None,
+        });
+    }
+}
 
-        transition_right_column_animator.set_tweenable(tween);
+// Runs on the fixed tick right after the hit, so the transition starts on the
+// same tick as well
+pub fn start_respawn_transition(
+    mut ev_player_died: EventReader<PlayerDied>,
+    mut screen_transition: ResMut<ScreenTransition>,
+    q_player: Query<&GlobalTransform, With<Player>>,
+) {
+    for event in ev_player_died.iter() {
+        let focus = q_player
+            .get(event.player)
+            .ok()
+            .map(|transform| transform.translation().truncate());
+
+        screen_transition.request(respawn_transition(TransitionStyle::Iris, focus));
     }
 }
 
 // In game between levels
 pub fn switch_levels_transition_event_handler (
-    mut q_event: EventReader<TweenCompleted>,
+    mut ev_transition_covered: EventReader<TransitionCovered>,
     mut commands: Commands,
     q_despawnable: Query<Entity, With<Despawnable>>,
-    mut q_transition_left: Query<&mut bevy_tweening::Animator<Style>, (With<TransitionColumnLeftUi>, Without<TransitionColumnRightUi>)>,
-    mut q_transition_right: Query<&mut bevy_tweening::Animator<Style>, (With<TransitionColumnRightUi>, Without<TransitionColumnLeftUi>)>,
     mut level_selection: ResMut<LevelSelection>,
-    mut game_state: ResMut<Persistent<GameState>>,
+    mut game_state: ResMut<GameSave>,
 ) {
-    for event in q_event.iter() {
-        if event.user_data != 4 {
+    for TransitionCovered(kind) in ev_transition_covered.iter() {
+        if *kind != TransitionKind::NextLevel {
             continue;
         }
 
@@ -95,60 +357,15 @@ pub fn switch_levels_transition_event_handler (
         for despawnable_entity in q_despawnable.iter() {
             commands.entity(despawnable_entity).despawn_recursive();
         }
-
-        let mut transition_left_column_animator = q_transition_left.single_mut();
-        let mut transition_right_column_animator = q_transition_right.single_mut();
-
-        let tween = Tween::new(
-            EaseFunction::QuarticInOut,
-            Duration::from_secs_f32(0.5),
-            UiPositionLens {
-                start: UiRect {
-                    left: Val::Percent(40.0),
-                    top: Val::Auto,
-                    right: Val::Auto,
-                    bottom: Val::Auto,
-                },
-                end: UiRect {
-                    left: Val::Percent(100.0),
-                    top: Val::Auto,
-                    right: Val::Auto,
-                    bottom: Val::Auto,
-                },
-            },
-        );
-
-        transition_left_column_animator.set_tweenable(tween);
-
-        let tween = Tween::new(
-            EaseFunction::QuarticInOut,
-            Duration::from_secs_f32(0.5),
-            UiPositionLens {
-                start: UiRect {
-                    right: Val::Percent(40.0),
-                    top: Val::Auto,
-                    left: Val::Auto,
-                    bottom: Val::Auto,
-                },
-                end: UiRect {
-                    right: Val::Percent(100.0),
-                    top: Val::Auto,
-                    left: Val::Auto,
-                    bottom: Val::Auto,
-                },
-            },
-        ).with_completed_event(2);
-
-        transition_right_column_animator.set_tweenable(tween);
     }
 }
 
 pub fn save_game_after_transition (
-    mut q_event: EventReader<TweenCompleted>,
-    game_state: Res<Persistent<GameState>>,
+    mut ev_transition_finished: EventReader<TransitionFinished>,
+    game_state: Res<GameSave>,
 ) {
-    for event in q_event.iter() {
-        if event.user_data != 2 {
+    for TransitionFinished(kind) in ev_transition_finished.iter() {
+        if *kind != TransitionKind::NextLevel {
             continue;
         }
 
@@ -157,31 +374,56 @@ pub fn save_game_after_transition (
     }
 }
 
-pub fn setup_transition_ui(
-   mut commands: Commands,
-) {
-    let ui_container = (
+fn iris_mask() -> Image {
+    let size = IRIS_MASK_SIZE as f32;
+    let radius = size / 2.0;
+    let mut data = Vec::with_capacity((IRIS_MASK_SIZE * IRIS_MASK_SIZE * 4) as usize);
+
+    for y in 0..IRIS_MASK_SIZE {
+        for x in 0..IRIS_MASK_SIZE {
+            let offset = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - Vec2::splat(radius);
+            let alpha = if offset.length() < radius { 0 } else { 255 };
+
+            data.extend_from_slice(&[0, 0, 0, alpha]);
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: IRIS_MASK_SIZE,
+            height: IRIS_MASK_SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+fn transition_container(style: TransitionStyle) -> (NodeBundle, TransitionStyleUi) {
+    (
         NodeBundle {
             style: Style {
                 position_type: PositionType::Absolute,
                 width: Val::Percent(100.0),
                 height: Val::Percent(100.0),
-                flex_direction: FlexDirection::Row,
-                justify_content: JustifyContent::Center,
+                display: Display::None,
                 ..default()
             },
+            z_index: ZIndex::Global(99),
             ..default()
         },
-    );
+        TransitionStyleUi(style),
+    )
+}
 
-    let tween = Tween::new(
-        EaseMethod::Linear,
-        Duration::from_secs(0),
-        UiPositionLens {
-            start: UiRect::DEFAULT,
-            end: UiRect::DEFAULT,
-        },
-    );
+pub fn setup_transition_ui(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let (mut columns_container, columns_style) = transition_container(TransitionStyle::Columns);
+    columns_container.style.flex_direction = FlexDirection::Row;
+    columns_container.style.justify_content = JustifyContent::Center;
 
     let transition_column_left = (
         NodeBundle {
@@ -197,16 +439,6 @@ pub fn setup_transition_ui(
             ..default()
         },
         TransitionColumnLeftUi {},
-        bevy_tweening::Animator::new(Delay::new(Duration::from_secs_f32(1.0)).then(tween)),
-    );
-
-    let tween = Tween::new(
-        EaseMethod::Linear,
-        Duration::from_secs(0),
-        UiPositionLens {
-            start: UiRect::DEFAULT,
-            end: UiRect::DEFAULT,
-        },
     );
 
     let transition_column_right = (
@@ -223,11 +455,10 @@ pub fn setup_transition_ui(
             ..default()
         },
         TransitionColumnRightUi {},
-        bevy_tweening::Animator::new(Delay::new(Duration::from_secs_f32(1.0)).then(tween)),
     );
 
     commands
-        .spawn(ui_container)
+        .spawn((columns_container, columns_style))
         .with_children(|parent| {
             parent
                 .spawn(transition_column_left);
@@ -235,4 +466,59 @@ pub fn setup_transition_ui(
             parent
                 .spawn(transition_column_right);
         });
+
+    commands.spawn(transition_container(TransitionStyle::Fade));
+
+    let iris_mask = images.add(iris_mask());
+
+    commands
+        .spawn(transition_container(TransitionStyle::Iris))
+        .with_children(|parent| {
+            for part in [IrisPart::Hole, IrisPart::Top, IrisPart::Bottom, IrisPart::Left, IrisPart::Right] {
+                let mut iris_part = NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        ..default()
+                    },
+                    background_color: BackgroundColor(Color::BLACK),
+                    ..default()
+                };
+
+                if part == IrisPart::Hole {
+                    iris_part.background_color = BackgroundColor(Color::WHITE);
+                    parent.spawn((iris_part, UiImage::new(iris_mask.clone()), TransitionIrisUi { part }));
+                } else {
+                    parent.spawn((iris_part, TransitionIrisUi { part }));
+                }
+            }
+        });
+
+    let (mut dissolve_container, dissolve_style) = transition_container(TransitionStyle::Dissolve);
+    dissolve_container.style.flex_direction = FlexDirection::Row;
+    dissolve_container.style.flex_wrap = FlexWrap::Wrap;
+
+    let squares = DISSOLVE_COLUMNS * DISSOLVE_ROWS;
+
+    commands
+        .spawn((dissolve_container, dissolve_style))
+        .with_children(|parent| {
+            for index in 0..squares {
+                // 37 shares no factor with the square count, so this visits
+                // every square once in a scattered order
+                let threshold = (index * 37 % squares) as f32 / squares as f32;
+
+                parent.spawn((
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Percent(100.0 / DISSOLVE_COLUMNS as f32),
+                            height: Val::Percent(100.0 / DISSOLVE_ROWS as f32),
+                            ..default()
+                        },
+                        background_color: BackgroundColor(Color::NONE),
+                        ..default()
+                    },
+                    TransitionDissolveUi { threshold },
+                ));
+            }
+        });
 }
@@ -0,0 +1,52 @@
+use bevy::prelude::{Res, ResMut, Resource, DetectChanges};
+use bevy_persistent::prelude::*;
+use kt_core::{input::InputBindings, audio::AudioBuses};
+use serde::{Serialize, Deserialize};
+
+use crate::save_game::{config_dir, persistent_or_default};
+
+// Player preferences that live next to the save slots but are shared
+// between playthroughs.
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Settings {
+    #[serde(default)]
+    pub bindings: InputBindings,
+    #[serde(default)]
+    pub last_slot: usize,
+    #[serde(default)]
+    pub audio: AudioBuses,
+}
+
+pub fn load_settings() -> Persistent<Settings> {
+    let mut settings = persistent_or_default("settings", config_dir().join("settings.json"), Settings::default())
+        .or_else(|_| persistent_or_default("settings", std::env::temp_dir().join("pixel-arena-settings.json"), Settings::default()))
+        .expect("failed to initialize settings");
+
+    settings
+        .update(|settings| settings.bindings.fill_missing_from(&InputBindings::default()))
+        .ok();
+
+    settings
+}
+
+pub fn apply_input_bindings(
+    settings: Res<Persistent<Settings>>,
+    mut bindings: ResMut<InputBindings>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    *bindings = settings.bindings.clone();
+}
+
+pub fn apply_audio_settings(
+    settings: Res<Persistent<Settings>>,
+    mut audio_buses: ResMut<AudioBuses>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    *audio_buses = settings.audio.clone();
+}
@@ -0,0 +1,273 @@
+use bevy::{prelude::{Component, ChildBuilder, AssetServer, Res, ResMut, ButtonBundle, Color, TextBundle, default, BuildChildren, Query, Changed, With, NodeBundle, Local, EventWriter, DetectChanges}, ui::{Style, Val, UiRect, JustifyContent, AlignItems, BackgroundColor, UiImage, FlexDirection, Interaction, RelativeCursorPosition}, text::TextStyle};
+use bevy_persistent::Persistent;
+use kt_core::audio::{AudioBus, AudioBuses, PlaySfx, Sfx};
+
+use crate::{settings::Settings, menu_navigation::{Focusable, MenuPage, MenuFocus, MenuColumn, show_menu_page}, main_menu_ui::create_back_button};
+
+const VOLUME_STEP: f32 = 0.1;
+const SLIDER_FILL_COLOR: Color = Color::rgb(0.96, 0.67, 0.1);
+const SLIDER_TRACK_COLOR: Color = Color::rgb(0.2, 0.2, 0.2);
+
+#[derive(Clone, Component, Debug, Default)]
+pub struct SettingsButtonUi {}
+
+// Clicking or dragging anywhere on the track sets the volume
+#[derive(Clone, Component, Debug)]
+pub struct VolumeSliderUi {
+    pub bus: AudioBus,
+}
+
+#[derive(Clone, Component, Debug)]
+pub struct VolumeFillUi {
+    pub bus: AudioBus,
+}
+
+// The - and + buttons next to a slider, also how gamepads change volumes
+#[derive(Clone, Component, Debug)]
+pub struct VolumeStepButtonUi {
+    pub bus: AudioBus,
+    pub step: f32,
+}
+
+fn bus_label(bus: AudioBus) -> &'static str {
+    match bus {
+        AudioBus::Master => "master",
+        AudioBus::Music => "music",
+        AudioBus::Sfx => "effects",
+        AudioBus::Ui => "menus",
+    }
+}
+
+fn create_step_button(parent: &mut ChildBuilder, label: &str, bus: AudioBus, step: f32, order: usize, asset_server: &Res<AssetServer>) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    height: Val::Px(65.0),
+                    width: Val::Px(65.0),
+                    margin: UiRect::all(Val::Px(8.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::WHITE),
+                image: UiImage::new(asset_server.load("sprites/small_btn.png")),
+                ..default()
+            },
+            VolumeStepButtonUi { bus, step },
+            Focusable { page: MenuPage::Settings, order },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font: asset_server.load("fonts/ThaleahFat.ttf"),
+                    font_size: 40.0,
+                    color: Color::rgb(1.0, 1.0, 1.0),
+                },
+            ));
+        });
+}
+
+fn create_volume_row(parent: &mut ChildBuilder, bus: AudioBus, row: usize, audio_buses: &AudioBuses, asset_server: &Res<AssetServer>) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    bus_label(bus),
+                    TextStyle {
+                        font: asset_server.load("fonts/ThaleahFat.ttf"),
+                        font_size: 32.0,
+                        color: Color::rgb(1.0, 1.0, 1.0),
+                    },
+                ).with_style(Style {
+                    width: Val::Px(140.0),
+                    ..default()
+                }),
+            );
+
+            create_step_button(parent, "-", bus, -VOLUME_STEP, row * 2, asset_server);
+
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(300.0),
+                            height: Val::Px(20.0),
+                            ..default()
+                        },
+                        background_color: BackgroundColor(SLIDER_TRACK_COLOR),
+                        ..default()
+                    },
+                    RelativeCursorPosition::default(),
+                    VolumeSliderUi { bus },
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(audio_buses.get(bus) * 100.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: BackgroundColor(SLIDER_FILL_COLOR),
+                            ..default()
+                        },
+                        VolumeFillUi { bus },
+                    ));
+                });
+
+            create_step_button(parent, "+", bus, VOLUME_STEP, row * 2 + 1, asset_server);
+        });
+}
+
+// Shared by the main menu and the pause menu, `back_to` is the page the back
+// button returns to.
+pub fn create_settings_page(parent: &mut ChildBuilder, audio_buses: &AudioBuses, back_to: MenuPage, asset_server: &Res<AssetServer>) {
+    parent.spawn(TextBundle::from_section(
+        "Settings",
+        TextStyle {
+            font: asset_server.load("fonts/ThaleahFat.ttf"),
+            font_size: 62.0,
+            color: Color::rgb(0.96, 0.67, 0.1),
+        }
+    ));
+
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                margin: UiRect::vertical(Val::Px(32.0)),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for (row, bus) in AudioBus::ALL.iter().enumerate() {
+                create_volume_row(parent, *bus, row, audio_buses, asset_server);
+            }
+        });
+
+    create_back_button(parent, MenuPage::Settings, back_to, asset_server);
+}
+
+pub fn create_settings_button(parent: &mut ChildBuilder, asset_server: &Res<AssetServer>) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    height: Val::Px(65.0),
+                    width: Val::Px(350.0),
+                    margin: UiRect::all(Val::Px(8.0)),
+                    padding: UiRect::horizontal(Val::Px(6.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::WHITE),
+                image: UiImage::new(asset_server.load("sprites/button.png")),
+                ..default()
+            },
+            SettingsButtonUi {},
+            Focusable { page: MenuPage::Main, order: 1 },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "settings",
+                TextStyle {
+                    font: asset_server.load("fonts/ThaleahFat.ttf"),
+                    font_size: 40.0,
+                    color: Color::rgb(1.0, 1.0, 1.0),
+                },
+            ));
+        });
+}
+
+pub fn handle_settings_button_interactions(
+    mut q_interaction: Query<&Interaction, (Changed<Interaction>, With<SettingsButtonUi>)>,
+    mut q_columns: Query<(&MenuColumn, &mut Style)>,
+    mut menu_focus: ResMut<MenuFocus>,
+    mut ev_play_sfx: EventWriter<PlaySfx>,
+) {
+    for interaction in &mut q_interaction {
+        if *interaction == Interaction::Pressed {
+            ev_play_sfx.send(PlaySfx(Sfx::ButtonClick));
+            show_menu_page(MenuPage::Settings, &mut menu_focus, &mut q_columns);
+        }
+    }
+}
+
+pub fn handle_volume_step_interactions(
+    mut q_interaction: Query<(&Interaction, &VolumeStepButtonUi), Changed<Interaction>>,
+    mut settings: ResMut<Persistent<Settings>>,
+    mut ev_play_sfx: EventWriter<PlaySfx>,
+) {
+    for (interaction, step_button) in &mut q_interaction {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        ev_play_sfx.send(PlaySfx(Sfx::ButtonClick));
+
+        settings
+            .update(|settings| {
+                let volume = settings.audio.get(step_button.bus) + step_button.step;
+                // Snapped so repeated steps don't drift away from round values
+                settings.audio.set(step_button.bus, (volume * 10.0).round() / 10.0);
+            })
+            .ok();
+    }
+}
+
+// Dragging changes the volume live, the file is only written on release
+pub fn handle_volume_slider_interactions(
+    q_sliders: Query<(&Interaction, &RelativeCursorPosition, &VolumeSliderUi)>,
+    mut settings: ResMut<Persistent<Settings>>,
+    mut dragging: Local<bool>,
+) {
+    let mut pressed = false;
+
+    for (interaction, cursor_position, slider) in q_sliders.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        pressed = true;
+
+        let volume = match cursor_position.normalized {
+            Some(position) => (position.x.clamp(0.0, 1.0) * 20.0).round() / 20.0,
+            None => continue,
+        };
+
+        if settings.audio.get(slider.bus) != volume {
+            settings.audio.set(slider.bus, volume);
+        }
+    }
+
+    if *dragging && !pressed {
+        settings.persist().ok();
+    }
+
+    *dragging = pressed;
+}
+
+pub fn update_volume_fills(
+    settings: Res<Persistent<Settings>>,
+    mut q_fills: Query<(&VolumeFillUi, &mut Style)>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for (fill, mut style) in q_fills.iter_mut() {
+        style.width = Val::Percent(settings.audio.get(fill.bus) * 100.0);
+    }
+}
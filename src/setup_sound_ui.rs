@@ -1,9 +1,22 @@
-use bevy::{prelude::{AssetServer, Commands, Res, NodeBundle, default, BuildChildren, ButtonBundle, Changed, Query, AudioBundle, PlaybackSettings, With, ResMut, GlobalVolume, AudioSink, AudioSinkPlayback, Entity}, ui::{Style, PositionType, Val, UiImage, UiRect, FlexDirection, JustifyContent, AlignItems, Interaction}, audio::{PlaybackMode, VolumeLevel}};
+use bevy::{prelude::{AssetServer, Commands, Res, NodeBundle, default, BuildChildren, ButtonBundle, Changed, Query, With, ResMut, EventWriter, DetectChanges}, ui::{Style, PositionType, Val, UiImage, UiRect, FlexDirection, JustifyContent, AlignItems, Interaction}};
+use bevy_persistent::Persistent;
 use kt_common::components::ui::{SoundUi, MuteButtonUi};
+use kt_core::audio::{PlaySfx, Sfx};
+
+use crate::settings::Settings;
+
+fn mute_button_image(muted: bool, asset_server: &AssetServer) -> UiImage {
+    if muted {
+        UiImage::new(asset_server.load("sprites/audio_disabled.png"))
+    } else {
+        UiImage::new(asset_server.load("sprites/sound.png"))
+    }
+}
 
 pub fn sound_ui (
     mut commands: Commands,
-    asset_server: Res<AssetServer>
+    asset_server: Res<AssetServer>,
+    settings: Res<Persistent<Settings>>,
 ) {
     let sound_ui_container = (
         NodeBundle {
@@ -31,7 +44,7 @@ pub fn sound_ui (
                     margin: UiRect::left(Val::Px(9.0)),
                     ..default()
                 },
-                image: UiImage::new(asset_server.load("sprites/sound.png")),
+                image: mute_button_image(settings.audio.muted, &asset_server),
                 ..default()
             },
             MuteButtonUi {},
@@ -40,84 +53,33 @@ pub fn sound_ui (
     });
 }
 
+// Muting silences the master bus, the volumes set in the settings are kept
 pub fn handle_sound_button_interactions(
-    q_sound_ui_container: Query<Entity, With<SoundUi>>,
-    mut q_interaction: Query<(&Interaction, Entity), (Changed<Interaction>, With<MuteButtonUi>)>,
-    mut global_volume: ResMut<GlobalVolume>,
-    music_controller: Query<&AudioSink>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    mut q_interaction: Query<&Interaction, (Changed<Interaction>, With<MuteButtonUi>)>,
+    mut settings: ResMut<Persistent<Settings>>,
+    mut ev_play_sfx: EventWriter<PlaySfx>,
 ) {
-    for (interaction, entity) in &mut q_interaction {
+    for interaction in &mut q_interaction {
         if let Interaction::Pressed = *interaction {
-            commands.spawn(AudioBundle {
-                source: asset_server.load("audio/btn_click.ogg"),
-                settings: PlaybackSettings {
-                    mode: PlaybackMode::Remove,
-                    ..default()
-                },
-            });
-
-            if global_volume.volume.get() == 0.0 {
-                global_volume.volume = VolumeLevel::new(1.0);
-                for sink in music_controller.iter() {
-                    sink.play();
-                }
+            ev_play_sfx.send(PlaySfx(Sfx::ButtonClick));
 
-                commands.entity(entity).remove_parent();
-                commands.entity(entity).despawn();
-
-                for container in q_sound_ui_container.iter() {
-                    commands
-                        .entity(container)
-                        .with_children(|parent| {
-                            parent.spawn(
-                                (ButtonBundle {
-                                    style: Style {
-                                        width: Val::Px(30.0),
-                                        height: Val::Px(32.0),
-                                        margin: UiRect::left(Val::Px(9.0)),
-                                        ..default()
-                                    },
-                                    image: UiImage::new(asset_server.load("sprites/sound.png")),
-                                    ..default()
-                                },
-                                MuteButtonUi {},
-                                )
-                            );
-                        });
-                }
-
-            } else {
-                global_volume.volume = VolumeLevel::new(0.0);
-                for sink in music_controller.iter() {
-                    sink.pause();
-                }
+            settings
+                .update(|settings| settings.audio.muted = !settings.audio.muted)
+                .ok();
+        }
+    }
+}
 
-                commands.entity(entity).remove_parent();
-                commands.entity(entity).despawn();
+pub fn update_mute_button(
+    settings: Res<Persistent<Settings>>,
+    mut q_mute_button: Query<&mut UiImage, With<MuteButtonUi>>,
+    asset_server: Res<AssetServer>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
 
-                for container in q_sound_ui_container.iter() {
-                    commands
-                        .entity(container)
-                        .with_children(|parent| {
-                            parent.spawn(
-                                (ButtonBundle {
-                                    style: Style {
-                                        width: Val::Px(30.0),
-                                        height: Val::Px(32.0),
-                                        margin: UiRect::left(Val::Px(9.0)),
-                                        ..default()
-                                    },
-                                    image: UiImage::new(asset_server.load("sprites/audio_disabled.png")),
-                                    ..default()
-                                },
-                                MuteButtonUi {},
-                                )
-                            );
-                        });
-                }
-            }
-        }
+    for mut image in q_mute_button.iter_mut() {
+        *image = mute_button_image(settings.audio.muted, &asset_server);
     }
 }
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use bevy::{prelude::{Query, Res, Commands, Entity, Color, Changed, With, Visibility, Transform, FixedTime, EventReader}, sprite::Sprite};
+use bevy_rapier2d::prelude::{CollisionGroups, Group};
+use bevy_tweening::AnimatorState;
+use kt_common::{components::{wiring::{Switch, SwitchKind, Gate, GateKind, Wires, WireId, Powered, Door}, interaction::Interaction, mover::Mover, sharpener::Sharpener, ldtk::{HitComponent, Spikes}}, events::LevelRestarted};
+
+pub const SWITCH_OFF_COLOR: Color = Color::rgb(0.35, 0.35, 0.4);
+const SWITCH_ON_COLOR: Color = Color::rgb(0.38, 0.75, 0.35);
+
+pub fn update_switches(
+    mut q_switches: Query<(&mut Switch, &Interaction)>,
+) {
+    for (mut switch, interaction) in q_switches.iter_mut() {
+        let touched = interaction.is_overlapping;
+
+        match switch.kind {
+            SwitchKind::Button => switch.is_on |= touched,
+            SwitchKind::PressurePlate => switch.is_on = touched,
+            SwitchKind::Lever => {
+                if touched && !switch.was_touched {
+                    switch.is_on = !switch.is_on;
+                }
+            },
+        }
+
+        switch.was_touched = touched;
+    }
+}
+
+// Gates read what reached them last tick, so a chain of gates takes a tick
+// per gate and loops in the wiring can't hang the game
+pub fn update_gates(
+    mut q_gates: Query<(&mut Gate, Option<&Powered>)>,
+    fixed_time: Res<FixedTime>,
+) {
+    for (mut gate, powered) in q_gates.iter_mut() {
+        let powered = powered.cloned().unwrap_or_default();
+        let input = match gate.kind {
+            GateKind::And => powered.all_on(),
+            _ => powered.is_on(),
+        };
+        let rising = input && !gate.was_powered;
+
+        match gate.kind {
+            GateKind::And | GateKind::Or => gate.is_on = input,
+            GateKind::Toggle => {
+                if rising {
+                    gate.is_on = !gate.is_on;
+                }
+            },
+            GateKind::Timed => {
+                if rising {
+                    gate.time_left = gate.duration;
+                }
+
+                gate.time_left = (gate.time_left - fixed_time.period.as_secs_f32()).max(0.0);
+                gate.is_on = gate.time_left > 0.0;
+            },
+        }
+
+        gate.was_powered = input;
+    }
+}
+
+// Counts the wires into every target and how many of them are on
+pub fn propagate_signals(
+    q_switches: Query<(&Switch, &Wires)>,
+    q_gates: Query<(&Gate, &Wires)>,
+    mut q_targets: Query<(&WireId, Option<&mut Powered>, Entity)>,
+    mut commands: Commands,
+) {
+    let mut signals: HashMap<&str, Powered> = HashMap::new();
+
+    let outputs = q_switches
+        .iter()
+        .map(|(switch, wires)| (switch.is_on, wires))
+        .chain(q_gates.iter().map(|(gate, wires)| (gate.is_on, wires)));
+
+    for (is_on, wires) in outputs {
+        for target in wires.targets.iter() {
+            let powered = signals.entry(target.as_str()).or_default();
+            powered.inputs += 1;
+
+            if is_on {
+                powered.inputs_on += 1;
+            }
+        }
+    }
+
+    for (wire_id, powered, entity) in q_targets.iter_mut() {
+        let signal = match signals.get(wire_id.0.as_str()) {
+            Some(signal) => signal.clone(),
+            None => continue,
+        };
+
+        match powered {
+            Some(mut powered) => {
+                if *powered != signal {
+                    *powered = signal;
+                }
+            },
+            None => {
+                commands.entity(entity).insert(signal);
+            },
+        }
+    }
+}
+
+pub fn update_switch_sprites(
+    mut q_switches: Query<(&Switch, &mut Sprite), Changed<Switch>>,
+) {
+    for (switch, mut sprite) in q_switches.iter_mut() {
+        sprite.color = if switch.is_on { SWITCH_ON_COLOR } else { SWITCH_OFF_COLOR };
+    }
+}
+
+pub fn power_doors(
+    mut q_doors: Query<(&Powered, &mut CollisionGroups, &mut Sprite), (With<Door>, Changed<Powered>)>,
+) {
+    for (powered, mut groups, mut sprite) in q_doors.iter_mut() {
+        let is_open = powered.is_on();
+
+        groups.memberships = if is_open { Group::NONE } else { Group::ALL };
+        sprite.color.set_a(if is_open { 0.2 } else { 1.0 });
+    }
+}
+
+// Wired movers and elevators only run while powered
+pub fn power_movers(
+    mut q_movers: Query<(&mut Mover, &Powered), Changed<Powered>>,
+) {
+    for (mut mover, powered) in q_movers.iter_mut() {
+        mover.is_active = powered.is_on();
+    }
+}
+
+pub fn power_sharpeners(
+    mut q_sharpeners: Query<(&mut bevy_tweening::Animator<Transform>, &Powered), (With<Sharpener>, Changed<Powered>)>,
+) {
+    for (mut animator, powered) in q_sharpeners.iter_mut() {
+        animator.state = if powered.is_on() { AnimatorState::Playing } else { AnimatorState::Paused };
+    }
+}
+
+// Powered spikes pull back in and stop hurting
+pub fn power_spikes(
+    mut q_spikes: Query<(&Powered, &mut Visibility, Entity), (With<Spikes>, Changed<Powered>)>,
+    mut commands: Commands,
+) {
+    for (powered, mut visibility, entity) in q_spikes.iter_mut() {
+        if powered.is_on() {
+            *visibility = Visibility::Hidden;
+            commands.entity(entity).remove::<HitComponent>();
+        } else {
+            *visibility = Visibility::Inherited;
+            commands.entity(entity).insert(HitComponent {});
+        }
+    }
+}
+
+// Buttons pressed and levers pulled before a restart don't carry over
+pub fn reset_wiring_on_restart(
+    mut ev_level_restarted: EventReader<LevelRestarted>,
+    mut q_switches: Query<&mut Switch>,
+    mut q_gates: Query<&mut Gate>,
+    mut q_powered: Query<&mut Powered>,
+) {
+    if ev_level_restarted.iter().count() == 0 {
+        return;
+    }
+
+    for mut switch in q_switches.iter_mut() {
+        switch.is_on = false;
+        switch.was_touched = false;
+    }
+
+    for mut gate in q_gates.iter_mut() {
+        gate.is_on = false;
+        gate.was_powered = false;
+        gate.time_left = 0.0;
+    }
+
+    for mut powered in q_powered.iter_mut() {
+        powered.inputs_on = 0;
+    }
+}